@@ -5,7 +5,7 @@ fn get_api_version(libusb_source: &Path) {
     use std::io::BufRead;
     if let Ok(f) = fs::File::open(libusb_source) {
         let f = std::io::BufReader::new(f);
-        for line in f.lines().flatten() {
+        for line in f.lines().map_while(Result::ok) {
             if line.starts_with("#define LIBUSB_API_VERSION") {
                 if let Some(api_version) = line.rsplit(' ').next().and_then(|s| {
                     if let Some(s) = s.strip_prefix("0x") {
@@ -14,9 +14,16 @@ fn get_api_version(libusb_source: &Path) {
                         None
                     }
                 }) {
+                    if api_version >= 0x01000107 {
+                        println!("cargo:rustc-cfg=libusb_set_log_cb");
+                    }
                     if api_version >= 0x01000108 {
                         println!("cargo:rustc-cfg=libusb_hotplug_get_user_data");
                     }
+                    if api_version >= 0x0100010A {
+                        println!("cargo:rustc-cfg=libusb_init_context");
+                        println!("cargo:rustc-cfg=libusb_iad");
+                    }
                 }
                 break;
             }
@@ -25,6 +32,11 @@ fn get_api_version(libusb_source: &Path) {
 }
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(libusb_hotplug_get_user_data)");
+    println!("cargo:rustc-check-cfg=cfg(libusb_set_log_cb)");
+    println!("cargo:rustc-check-cfg=cfg(libusb_init_context)");
+    println!("cargo:rustc-check-cfg=cfg(libusb_iad)");
+
     if let Ok(include_path) = std::env::var("DEP_USB_1.0_INCLUDE") {
         let path = PathBuf::from(include_path);
         get_api_version(path.join("libusb.h").as_path());
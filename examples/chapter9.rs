@@ -0,0 +1,47 @@
+// yusb/examples/chapter9.rs
+//
+// Runs the Chapter 9 conformance tests on a device.
+//
+// With no arguments, this looks for the Linux `g_zero` gadget, which can
+// be run on the `dummy_hcd` virtual host controller:
+//
+//   $ sudo modprobe dummy_hcd
+//   $ sudo modprobe g_zero
+//   $ sudo cargo run --example chapter9
+//
+
+use std::process;
+use yusb::chapter9::Chapter9Runner;
+
+/// The VID:PID of the Linux `g_zero` gadget.
+const G_ZERO: (u16, u16) = (0x0525, 0xa4a0);
+
+fn parse_vid_pid(arg: &str) -> Option<(u16, u16)> {
+    let (vid, pid) = arg.split_once(':')?;
+    Some((
+        u16::from_str_radix(vid, 16).ok()?,
+        u16::from_str_radix(pid, 16).ok()?,
+    ))
+}
+
+fn main() {
+    let (vid, pid) = match std::env::args().nth(1) {
+        Some(arg) => parse_vid_pid(&arg).unwrap_or_else(|| {
+            eprintln!("usage: chapter9 [<vid>:<pid>]");
+            process::exit(2);
+        }),
+        None => G_ZERO,
+    };
+
+    let mut handle = yusb::open_device_with_vid_pid(vid, pid).unwrap_or_else(|| {
+        eprintln!("could not open device {:04x}:{:04x}", vid, pid);
+        process::exit(2);
+    });
+
+    let report = Chapter9Runner::new().run(&mut handle);
+    println!("{}", report);
+
+    if !report.is_pass() {
+        process::exit(1);
+    }
+}
@@ -1,3 +1,4 @@
+use std::{thread, time::Duration};
 use yusb::{self as usb, Context, Device, HotplugBuilder};
 
 struct HotPlugHandler;
@@ -22,20 +23,16 @@ fn main() -> usb::Result<()> {
     if usb::has_hotplug() {
         let context = Context::new()?;
 
-        let mut reg = Some(
-            HotplugBuilder::new()
-                .enumerate(true)
-                .register(context.clone(), Box::new(HotPlugHandler {}))?,
-        );
-
-        loop {
-            context.handle_events(None).unwrap();
-            if let Some(reg) = reg.take() {
-                context.unregister_callback(reg);
-                break;
-            }
-        }
-        Ok(())
+        let _reg = HotplugBuilder::new()
+            .enumerate(true)
+            .register(context.clone(), Box::new(HotPlugHandler {}))?;
+
+        let events = context.spawn_event_thread()?;
+
+        println!("Watching for devices for 30 seconds...");
+        thread::sleep(Duration::from_secs(30));
+
+        events.stop()
     } else {
         eprint!("libusb hotplug api unsupported");
         Ok(())
@@ -101,12 +101,20 @@ fn print_device(device_desc: &DeviceDescriptor, handle: &mut Option<UsbDevice>)
         device_desc.usb_version().minor(),
         device_desc.usb_version().sub_minor()
     );
-    println!("  bDeviceClass        {:#04x}", device_desc.class_code());
+    println!(
+        "  bDeviceClass        {:#04x} {}",
+        device_desc.class_code(),
+        device_desc.class()
+    );
     println!(
         "  bDeviceSubClass     {:#04x}",
         device_desc.sub_class_code()
     );
-    println!("  bDeviceProtocol     {:#04x}", device_desc.protocol_code());
+    println!(
+        "  bDeviceProtocol     {:#04x} {}",
+        device_desc.protocol_code(),
+        device_desc.class_triple().function_name().unwrap_or("")
+    );
     println!("  bMaxPacketSize0      {:3}", device_desc.max_packet_size());
     println!("  idVendor          {vid:#06x} {vendor_name}",);
     println!("  idProduct         {pid:#06x} {product_name}",);
@@ -188,16 +196,18 @@ fn print_interface(interface_desc: &InterfaceDescriptor, handle: &mut Option<Usb
         interface_desc.num_endpoints()
     );
     println!(
-        "      bInterfaceClass     {:#04x}",
-        interface_desc.class_code()
+        "      bInterfaceClass     {:#04x} {}",
+        interface_desc.class_code(),
+        interface_desc.class()
     );
     println!(
         "      bInterfaceSubClass  {:#04x}",
         interface_desc.sub_class_code()
     );
     println!(
-        "      bInterfaceProtocol  {:#04x}",
-        interface_desc.protocol_code()
+        "      bInterfaceProtocol  {:#04x} {}",
+        interface_desc.protocol_code(),
+        interface_desc.class_triple().function_name().unwrap_or("")
     );
     println!(
         "      iInterface           {:3} {}",
@@ -14,7 +14,8 @@ fn convert_argument(input: &str) -> u16 {
     if input.starts_with("0x") {
         return u16::from_str_radix(input.trim_start_matches("0x"), 16).unwrap();
     }
-    u16::from_str_radix(input, 10)
+    input
+        .parse::<u16>()
         .expect("Invalid input, be sure to add `0x` for hexadecimal values.")
 }
 
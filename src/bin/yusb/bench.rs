@@ -0,0 +1,585 @@
+// yusb/src/bin/yusb/bench.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The `bench` command, which measures the throughput and latency of bulk
+//! or interrupt transfers.
+//!
+//! This is meant to be run against a device that sources and sinks data
+//! as fast as it can, like the source/sink function of the Linux
+//! `g_zero` gadget.
+
+use crate::{
+    filter::{BusDev, DeviceFilter, VidPid},
+    list::speed_name,
+    print_json, queue, CliResult,
+};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use std::{
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use yusb::{ConfigDescriptor, DeviceHandle, Direction, Error, Speed, TransferStats, TransferType};
+
+/// Measure the throughput and latency of an endpoint pair.
+///
+/// For each mode, transfer size, and queue depth, this keeps that many
+/// transfers in flight on each endpoint for the duration, and reports the
+/// throughput and the latency of the transfers. The synchronous mode makes
+/// each transfer from its own thread, so a depth of one is a plain loop.
+/// The asynchronous mode submits that many `libusb` transfers at once, and
+/// resubmits each one as it completes.
+///
+/// With no endpoints given, this uses the first bulk or interrupt IN/OUT
+/// pair in the active configuration, like the one in the source/sink
+/// function of the Linux `g_zero` gadget.
+#[derive(Debug, Args)]
+#[command(group(clap::ArgGroup::new("device").required(true).multiple(true)))]
+pub struct BenchArgs {
+    /// Select the device with the vendor and/or product ID, in hex.
+    #[arg(short = 'd', value_name = "[vendor]:[product]", group = "device")]
+    vid_pid: Option<VidPid>,
+
+    /// Select the device on the bus and/or with the device number, in
+    /// decimal.
+    #[arg(short = 's', value_name = "[[bus]:][devnum]", group = "device")]
+    bus_dev: Option<BusDev>,
+
+    /// The IN endpoint to read, like 0x81.
+    #[arg(long = "in", value_name = "ADDR", value_parser = parse_endpoint)]
+    in_endpoint: Option<u8>,
+
+    /// The OUT endpoint to write, like 0x01.
+    #[arg(long = "out", value_name = "ADDR", value_parser = parse_endpoint)]
+    out_endpoint: Option<u8>,
+
+    /// The transfer sizes to sweep, in bytes, with an optional "k" suffix.
+    #[arg(
+        long,
+        value_name = "SIZES",
+        value_delimiter = ',',
+        value_parser = parse_size,
+        default_value = "512,4k,16k,64k"
+    )]
+    sizes: Vec<usize>,
+
+    /// The transfer modes to sweep.
+    #[arg(
+        long,
+        value_name = "MODES",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "sync,async"
+    )]
+    modes: Vec<Mode>,
+
+    /// The queue depths to sweep.
+    #[arg(
+        long,
+        value_name = "DEPTHS",
+        value_delimiter = ',',
+        default_value = "1,2,4"
+    )]
+    depths: Vec<usize>,
+
+    /// The time to run each case, in seconds.
+    #[arg(long, value_name = "SECS", default_value = "2")]
+    duration: f64,
+
+    /// Detach the kernel driver from the interface while benchmarking.
+    #[arg(long)]
+    detach: bool,
+
+    /// The timeout for each transfer, in milliseconds, or 0 to
+    /// wait forever.
+    #[arg(short, long, value_name = "MS", default_value = "1000")]
+    timeout: u64,
+
+    /// Print the results as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Parses an endpoint address, in hex with or without the 0x prefix.
+fn parse_endpoint(s: &str) -> std::result::Result<u8, String> {
+    u8::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("'{}' is not a valid endpoint address", s))
+}
+
+/// Parses a transfer size, like "512" or "64k".
+fn parse_size(s: &str) -> std::result::Result<usize, String> {
+    let (num, mult) = match s.strip_suffix(|c| c == 'k' || c == 'K') {
+        Some(num) => (num, 1024),
+        None => (s, 1),
+    };
+    match num.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n * mult),
+        _ => Err(format!("'{}' is not a valid transfer size", s)),
+    }
+}
+
+/// The way that the transfers are made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Synchronous transfers, one thread for each in flight.
+    Sync,
+    /// Asynchronous transfers, all submitted from one thread.
+    Async,
+}
+
+impl Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::Sync => "sync",
+            Mode::Async => "async",
+        }
+    }
+}
+
+/// An endpoint to benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Endpoint {
+    address: u8,
+    transfer_type: TransferType,
+}
+
+impl Endpoint {
+    fn direction(&self) -> Direction {
+        Direction::from(self.address & 0x80)
+    }
+
+    /// Makes one transfer, returning the error, if any.
+    fn transfer(&self, handle: &DeviceHandle, buf: &mut [u8], timeout: Duration) -> Option<Error> {
+        let res = match (self.direction(), self.transfer_type) {
+            (Direction::In, TransferType::Interrupt) => {
+                handle.read_interrupt(self.address, buf, timeout)
+            }
+            (Direction::In, _) => handle.read_bulk(self.address, buf, timeout),
+            (Direction::Out, TransferType::Interrupt) => {
+                handle.write_interrupt(self.address, buf, timeout)
+            }
+            (Direction::Out, _) => handle.write_bulk(self.address, buf, timeout),
+        };
+        res.err().map(|err| err.kind())
+    }
+}
+
+/// The endpoints to benchmark, and the interface that they're on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoints {
+    interface: u8,
+    endpoints: Vec<Endpoint>,
+}
+
+/// Finds the endpoints to benchmark in the first alternate setting of the
+/// configuration.
+///
+/// If neither address is given, this uses the first interface that has a
+/// bulk or interrupt endpoint, with the first such IN and OUT endpoints on
+/// it. Otherwise, it uses the given endpoints, which must be on the same
+/// interface.
+fn find_endpoints(
+    config: &ConfigDescriptor,
+    in_addr: Option<u8>,
+    out_addr: Option<u8>,
+) -> Option<Endpoints> {
+    let auto = in_addr.is_none() && out_addr.is_none();
+
+    for iface in config.interfaces() {
+        let alt = match iface.descriptors().next() {
+            Some(alt) => alt,
+            None => continue,
+        };
+        let candidates: Vec<_> = alt
+            .endpoint_descriptors()
+            .filter(|ep| {
+                matches!(
+                    ep.transfer_type(),
+                    TransferType::Bulk | TransferType::Interrupt
+                )
+            })
+            .map(|ep| Endpoint {
+                address: ep.address(),
+                transfer_type: ep.transfer_type(),
+            })
+            .collect();
+
+        let pick = |dir: Direction, addr: Option<u8>| match (auto, addr) {
+            (true, _) => candidates.iter().find(|ep| ep.direction() == dir).copied(),
+            (false, Some(addr)) => candidates.iter().find(|ep| ep.address == addr).copied(),
+            (false, None) => None,
+        };
+        let endpoints: Vec<_> = [pick(Direction::In, in_addr), pick(Direction::Out, out_addr)]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let wanted = if auto {
+            1
+        } else {
+            usize::from(in_addr.is_some()) + usize::from(out_addr.is_some())
+        };
+        if !endpoints.is_empty() && endpoints.len() >= wanted {
+            return Some(Endpoints {
+                interface: iface.number(),
+                endpoints,
+            });
+        }
+    }
+    None
+}
+
+/// The result of one benchmark case.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct BenchResult {
+    endpoint: u8,
+    direction: &'static str,
+    mode: &'static str,
+    size: usize,
+    depth: usize,
+    transfers: u64,
+    bytes: u64,
+    errors: u64,
+    timeouts: u64,
+    elapsed_secs: f64,
+    mb_per_sec: f64,
+    percent_of_bus: f64,
+    latency_p50_us: u64,
+    latency_p90_us: u64,
+    latency_p99_us: u64,
+}
+
+impl BenchResult {
+    const HEADER: &'static str =
+        "Endpoint Mode   Size    Depth  Transfers      MB/s   % bus       p50       p90       p99  Errors";
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#04x} {:<3} {:<5} {:>7} {:>6} {:>10} {:>9.2} {:>6.1}% {:>9} {:>9} {:>9} {:>7}",
+            self.endpoint,
+            self.direction,
+            self.mode,
+            format_size(self.size),
+            self.depth,
+            self.transfers,
+            self.mb_per_sec,
+            self.percent_of_bus,
+            format_latency(self.latency_p50_us),
+            format_latency(self.latency_p90_us),
+            format_latency(self.latency_p99_us),
+            self.errors
+        )
+    }
+}
+
+/// Formats a size, in kibibytes when it's a whole number of them.
+fn format_size(size: usize) -> String {
+    match size {
+        n if n >= 1024 && n % 1024 == 0 => format!("{}k", n / 1024),
+        n => n.to_string(),
+    }
+}
+
+/// Formats a latency in microseconds, switching to milliseconds for the
+/// longer ones.
+fn format_latency(us: u64) -> String {
+    match us {
+        us if us < 10_000 => format!("{}us", us),
+        us => format!("{:.1}ms", us as f64 / 1000.0),
+    }
+}
+
+/// Gets the theoretical data rate of the bus, in megabytes per second.
+fn bus_mb_per_sec(speed: Speed) -> f64 {
+    speed.as_mbps() / 8.0
+}
+
+/// Keeps `depth` synchronous transfers in flight on the endpoint for the
+/// duration, each from its own thread, and gets their statistics from the
+/// handle metrics.
+fn run_sync(
+    handle: &Arc<DeviceHandle>,
+    ep: Endpoint,
+    size: usize,
+    depth: usize,
+    duration: Duration,
+    timeout: Duration,
+) -> TransferStats {
+    handle.reset_metrics();
+    let deadline = Instant::now() + duration;
+
+    let workers: Vec<_> = (0..depth)
+        .map(|_| {
+            let handle = Arc::clone(handle);
+            thread::spawn(move || {
+                let mut buf = vec![0u8; size];
+                while Instant::now() < deadline {
+                    match ep.transfer(&handle, &mut buf, timeout) {
+                        None | Some(Error::Timeout) => {}
+                        // Anything else, like a halt, won't fix itself.
+                        Some(_) => break,
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    handle
+        .metrics()
+        .and_then(|m| m.endpoint(ep.address).cloned())
+        .unwrap_or_default()
+}
+
+/// Runs one case, keeping `depth` transfers in flight on the endpoint for
+/// the duration.
+fn run_case(
+    handle: &Arc<DeviceHandle>,
+    ep: Endpoint,
+    mode: Mode,
+    size: usize,
+    depth: usize,
+    duration: Duration,
+    timeout: Duration,
+) -> yusb::Result<BenchResult> {
+    let start = Instant::now();
+    let stats = match mode {
+        Mode::Sync => run_sync(handle, ep, size, depth, duration, timeout),
+        Mode::Async => queue::run(
+            handle,
+            ep.address,
+            ep.transfer_type,
+            size,
+            depth,
+            duration,
+            timeout,
+        )?,
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let mb_per_sec = stats.bytes as f64 / elapsed / 1e6;
+    let bus = bus_mb_per_sec(handle.device().speed());
+
+    Ok(BenchResult {
+        endpoint: ep.address,
+        direction: match ep.direction() {
+            Direction::In => "in",
+            Direction::Out => "out",
+        },
+        mode: mode.name(),
+        size,
+        depth,
+        transfers: stats.transfers,
+        bytes: stats.bytes,
+        errors: stats.error_count(),
+        timeouts: stats.timeouts,
+        elapsed_secs: elapsed,
+        mb_per_sec,
+        percent_of_bus: match bus {
+            bus if bus > 0.0 => 100.0 * mb_per_sec / bus,
+            _ => 0.0,
+        },
+        latency_p50_us: stats.latency.p50.as_micros() as u64,
+        latency_p90_us: stats.latency.p90.as_micros() as u64,
+        latency_p99_us: stats.latency.p99.as_micros() as u64,
+    })
+}
+
+/// Runs the benchmark.
+pub fn run(args: &BenchArgs) -> CliResult<()> {
+    if args.depths.contains(&0) {
+        return Err("the queue depths must be at least 1".into());
+    }
+    if !args.duration.is_finite() || args.duration <= 0.0 {
+        return Err("the duration must be positive".into());
+    }
+
+    let mut handle = DeviceFilter::new(args.vid_pid, args.bus_dev).open()?;
+    let device = handle.device();
+    let config = device.active_config_descriptor()?;
+    let found = find_endpoints(&config, args.in_endpoint, args.out_endpoint)
+        .ok_or("the endpoints to benchmark weren't found in the active configuration")?;
+
+    if args.detach {
+        handle.set_auto_detach_kernel_driver(true)?;
+    }
+    handle.claim_interface(found.interface)?;
+    handle.enable_metrics();
+    let handle = Arc::new(handle);
+
+    let speed = device.speed();
+    if !args.json {
+        println!(
+            "Interface {}, {}, theoretical {:.1} MB/s",
+            found.interface,
+            speed_name(speed),
+            bus_mb_per_sec(speed)
+        );
+        println!("{}", BenchResult::HEADER);
+    }
+
+    let duration = Duration::from_secs_f64(args.duration);
+    let timeout = Duration::from_millis(args.timeout);
+    let mut results = vec![];
+
+    let mut sweep = || -> yusb::Result<()> {
+        for &ep in &found.endpoints {
+            for &mode in &args.modes {
+                for &size in &args.sizes {
+                    for &depth in &args.depths {
+                        let res = run_case(&handle, ep, mode, size, depth, duration, timeout)?;
+                        if !args.json {
+                            println!("{}", res);
+                        }
+                        results.push(res);
+                    }
+                }
+            }
+        }
+        Ok(())
+    };
+    let res = sweep();
+
+    // The workers are done, so this is the last reference to the handle.
+    // Releasing reattaches the kernel driver, if it was detached.
+    if let Ok(mut handle) = Arc::try_unwrap(handle) {
+        let _ = handle.release_interface(found.interface);
+    }
+
+    if args.json {
+        print_json(&results, true)?;
+    }
+    Ok(res?)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use yusb::{ConfigDescriptorBuilder, EndpointBuilder, InterfaceBuilder};
+
+    fn config() -> ConfigDescriptor {
+        let mut control = InterfaceBuilder::new(0);
+        control.endpoint(
+            EndpointBuilder::new(0x83)
+                .transfer_type(TransferType::Isochronous)
+                .max_packet_size(64),
+        );
+
+        let mut source_sink = InterfaceBuilder::new(1);
+        source_sink
+            .endpoint(
+                EndpointBuilder::new(0x81)
+                    .transfer_type(TransferType::Bulk)
+                    .max_packet_size(512),
+            )
+            .endpoint(
+                EndpointBuilder::new(0x02)
+                    .transfer_type(TransferType::Bulk)
+                    .max_packet_size(512),
+            );
+
+        let mut config = ConfigDescriptorBuilder::new(1);
+        config.interface(&control).interface(&source_sink);
+        config.build().unwrap()
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(Ok(512), parse_size("512"));
+        assert_eq!(Ok(65536), parse_size("64k"));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("k").is_err());
+
+        assert_eq!("512", format_size(512));
+        assert_eq!("64k", format_size(65536));
+        assert_eq!("1500", format_size(1500));
+    }
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(Ok(0x81), parse_endpoint("0x81"));
+        assert_eq!(Ok(0x02), parse_endpoint("02"));
+        assert!(parse_endpoint("0x100").is_err());
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(Ok(Mode::Sync), Mode::from_str("sync", false));
+        assert_eq!(Ok(Mode::Async), Mode::from_str("async", false));
+        assert!(Mode::from_str("both", false).is_err());
+        assert_eq!("async", Mode::Async.name());
+    }
+
+    #[test]
+    fn latencies() {
+        assert_eq!("125us", format_latency(125));
+        assert_eq!("12.5ms", format_latency(12_500));
+    }
+
+    #[test]
+    fn find_auto_endpoints() {
+        let found = find_endpoints(&config(), None, None).unwrap();
+        assert_eq!(1, found.interface);
+        assert_eq!(
+            vec![0x81, 0x02],
+            found
+                .endpoints
+                .iter()
+                .map(|ep| ep.address)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn find_given_endpoints() {
+        let found = find_endpoints(&config(), None, Some(0x02)).unwrap();
+        assert_eq!(1, found.interface);
+        assert_eq!(1, found.endpoints.len());
+        assert_eq!(Direction::Out, found.endpoints[0].direction());
+
+        assert!(find_endpoints(&config(), Some(0x83), None).is_none());
+        assert!(find_endpoints(&config(), Some(0x81), Some(0x05)).is_none());
+    }
+
+    #[test]
+    fn result_row() {
+        let res = BenchResult {
+            endpoint: 0x81,
+            direction: "in",
+            mode: "async",
+            size: 16384,
+            depth: 4,
+            transfers: 4688,
+            bytes: 76_808_192,
+            errors: 0,
+            timeouts: 0,
+            elapsed_secs: 2.0,
+            mb_per_sec: 38.404,
+            percent_of_bus: 64.01,
+            latency_p50_us: 650,
+            latency_p90_us: 810,
+            latency_p99_us: 12_000,
+        };
+        assert_eq!(
+            "0x81 in  async     16k      4       4688     38.40   64.0%     650us     810us    12.0ms       0",
+            res.to_string()
+        );
+        assert_eq!(BenchResult::HEADER.len(), res.to_string().len());
+    }
+}
@@ -0,0 +1,301 @@
+// yusb/src/bin/yusb/control.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The `control` command, which sends a single control request.
+
+use crate::{
+    filter::{BusDev, DeviceFilter, VidPid},
+    CliResult,
+};
+use clap::{Args, ValueEnum};
+use std::{fmt::Write, time::Duration};
+use yusb::{request_type, Direction, Recipient, RequestType};
+
+/// Send a control request to a device.
+///
+/// An IN request reads the number of bytes given by --length, and prints
+/// them as a hex dump. An OUT request writes the bytes given by --data,
+/// if any.
+#[derive(Debug, Args)]
+#[command(group(clap::ArgGroup::new("device").required(true).multiple(true)))]
+pub struct ControlArgs {
+    /// Select the device with the vendor and/or product ID, in hex.
+    #[arg(short = 'd', value_name = "[vendor]:[product]", group = "device")]
+    vid_pid: Option<VidPid>,
+
+    /// Select the device on the bus and/or with the device number, in
+    /// decimal.
+    #[arg(short = 's', value_name = "[[bus]:][devnum]", group = "device")]
+    bus_dev: Option<BusDev>,
+
+    /// The direction of the data stage.
+    #[arg(value_enum)]
+    direction: DirectionArg,
+
+    /// The type of request.
+    #[arg(value_enum)]
+    request_type: RequestTypeArg,
+
+    /// The recipient of the request.
+    #[arg(value_enum)]
+    recipient: RecipientArg,
+
+    /// The request number (bRequest).
+    #[arg(value_parser = parse_u8)]
+    request: u8,
+
+    /// The request value (wValue).
+    #[arg(value_parser = parse_u16)]
+    value: u16,
+
+    /// The request index (wIndex).
+    #[arg(value_parser = parse_u16)]
+    index: u16,
+
+    /// The number of bytes to read, for an IN request.
+    #[arg(short, long, value_parser = parse_u16, conflicts_with = "data")]
+    length: Option<u16>,
+
+    /// The bytes to write, in hex, for an OUT request, like "01 02 ff" or
+    /// "0102ff".
+    #[arg(long, value_parser = parse_hex)]
+    data: Option<HexData>,
+
+    /// Claim the interface before sending the request.
+    #[arg(long, value_name = "IFACE", value_parser = parse_u8)]
+    claim: Option<u8>,
+
+    /// Detach the kernel driver from the claimed interface, and reattach
+    /// it afterward.
+    #[arg(long, requires = "claim")]
+    detach: bool,
+
+    /// The timeout for the request, in milliseconds, or 0 to
+    /// wait forever.
+    #[arg(short, long, value_name = "MS", default_value = "1000")]
+    timeout: u64,
+}
+
+/// The direction of a control request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DirectionArg {
+    /// Device to host.
+    In,
+    /// Host to device.
+    Out,
+}
+
+/// The type of a control request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RequestTypeArg {
+    Standard,
+    Class,
+    Vendor,
+}
+
+/// The recipient of a control request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RecipientArg {
+    Device,
+    Interface,
+    Endpoint,
+    Other,
+}
+
+impl ControlArgs {
+    /// Gets the `bmRequestType` of the request.
+    fn request_type(&self) -> u8 {
+        let direction = match self.direction {
+            DirectionArg::In => Direction::In,
+            DirectionArg::Out => Direction::Out,
+        };
+        let typ = match self.request_type {
+            RequestTypeArg::Standard => RequestType::Standard,
+            RequestTypeArg::Class => RequestType::Class,
+            RequestTypeArg::Vendor => RequestType::Vendor,
+        };
+        let recipient = match self.recipient {
+            RecipientArg::Device => Recipient::Device,
+            RecipientArg::Interface => Recipient::Interface,
+            RecipientArg::Endpoint => Recipient::Endpoint,
+            RecipientArg::Other => Recipient::Other,
+        };
+        request_type(direction, typ, recipient)
+    }
+}
+
+/// The data for an OUT request.
+///
+/// This is wrapped so that clap doesn't treat a `Vec` as a list of
+/// arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexData(Vec<u8>);
+
+/// Parses an integer, in decimal or 0x-prefixed hex.
+fn parse_int(s: &str) -> std::result::Result<u64, String> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| format!("'{}' is not a valid number", s))
+}
+
+fn parse_u8(s: &str) -> std::result::Result<u8, String> {
+    u8::try_from(parse_int(s)?).map_err(|_| format!("'{}' is out of range for a byte", s))
+}
+
+fn parse_u16(s: &str) -> std::result::Result<u16, String> {
+    u16::try_from(parse_int(s)?).map_err(|_| format!("'{}' is out of range for a word", s))
+}
+
+/// Parses bytes in hex, separated by whitespace, commas, or colons, or
+/// run together.
+fn parse_hex(s: &str) -> std::result::Result<HexData, String> {
+    let mut buf = vec![];
+    for tok in s.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        let tok = tok.trim_start_matches("0x");
+        if tok.len() % 2 != 0 {
+            return Err(format!("'{}' is not a whole number of bytes", tok));
+        }
+        for i in (0..tok.len()).step_by(2) {
+            let byte = tok
+                .get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| format!("'{}' is not valid hex", tok))?;
+            buf.push(byte);
+        }
+    }
+    Ok(HexData(buf))
+}
+
+/// Formats bytes as a hex dump, with 16 bytes per line, followed by
+/// their printable ASCII characters.
+pub fn hex_dump(buf: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, chunk) in buf.chunks(16).enumerate() {
+        let _ = write!(out, "{:04x}:", 16 * i);
+        for b in chunk {
+            let _ = write!(out, " {:02x}", b);
+        }
+        let pad = 3 * (16 - chunk.len());
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7E => char::from(b),
+                _ => '.',
+            })
+            .collect();
+        let _ = writeln!(out, "{:pad$}  |{}|", "", ascii, pad = pad);
+    }
+    out
+}
+
+/// Sends the request.
+pub fn run(args: &ControlArgs) -> CliResult<()> {
+    if args.direction == DirectionArg::In && args.data.is_some() {
+        return Err("only an OUT request can have --data".into());
+    }
+    let len = match (args.direction, args.length) {
+        (DirectionArg::In, Some(len)) => len,
+        (DirectionArg::In, None) => return Err("an IN request needs a --length".into()),
+        (DirectionArg::Out, Some(_)) => return Err("only an IN request can have a --length".into()),
+        (DirectionArg::Out, None) => 0,
+    };
+
+    let mut handle = DeviceFilter::new(args.vid_pid, args.bus_dev).open()?;
+    if let Some(iface) = args.claim {
+        if args.detach {
+            handle.set_auto_detach_kernel_driver(true)?;
+        }
+        handle.claim_interface(iface)?;
+    }
+
+    let timeout = Duration::from_millis(args.timeout);
+    let res = match args.direction {
+        DirectionArg::In => {
+            let mut buf = vec![0u8; usize::from(len)];
+            handle
+                .read_control(
+                    args.request_type(),
+                    args.request,
+                    args.value,
+                    args.index,
+                    &mut buf,
+                    timeout,
+                )
+                .map(|n| {
+                    println!("Read {} bytes", n);
+                    print!("{}", hex_dump(&buf[..n]));
+                })
+        }
+        DirectionArg::Out => {
+            let data = args.data.as_ref().map_or(&[][..], |d| &d.0);
+            handle
+                .write_control(
+                    args.request_type(),
+                    args.request,
+                    args.value,
+                    args.index,
+                    data,
+                    timeout,
+                )
+                .map(|n| println!("Wrote {} bytes", n))
+        }
+    };
+
+    // Releasing reattaches the kernel driver, if it was detached.
+    if let Some(iface) = args.claim {
+        let _ = handle.release_interface(iface);
+    }
+    Ok(res?)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Ok(0x5c), parse_u8("0x5c"));
+        assert_eq!(Ok(92), parse_u8("92"));
+        assert!(parse_u8("256").is_err());
+        assert_eq!(Ok(0x0409), parse_u16("0x0409"));
+        assert!(parse_u16("0x10000").is_err());
+        assert!(parse_u16("abc").is_err());
+    }
+
+    #[test]
+    fn hex_data() {
+        let expected = HexData(vec![0x01, 0x02, 0xFF]);
+        assert_eq!(Ok(expected.clone()), parse_hex("01 02 ff"));
+        assert_eq!(Ok(expected.clone()), parse_hex("0102ff"));
+        assert_eq!(Ok(expected.clone()), parse_hex("0x01,0x02,0xff"));
+        assert_eq!(Ok(expected), parse_hex("01:02:FF"));
+        assert_eq!(Ok(HexData(vec![])), parse_hex(""));
+        assert!(parse_hex("012").is_err());
+        assert!(parse_hex("0g").is_err());
+    }
+
+    #[test]
+    fn dump() {
+        let buf: Vec<u8> = (0x30..0x42).collect();
+        assert_eq!(
+            "0000: 30 31 32 33 34 35 36 37 38 39 3a 3b 3c 3d 3e 3f  |0123456789:;<=>?|\n\
+             0010: 40 41                                            |@A|\n",
+            hex_dump(&buf)
+        );
+        assert_eq!(
+            format!("0000: 00 7f{:42}  |..|\n", ""),
+            hex_dump(&[0x00, 0x7F])
+        );
+    }
+}
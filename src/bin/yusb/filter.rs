@@ -0,0 +1,209 @@
+// yusb/src/bin/yusb/filter.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Selecting devices by their IDs or location.
+
+use crate::CliResult;
+use std::{num::ParseIntError, str::FromStr};
+use yusb::{Device, DeviceDescriptor, DeviceHandle, DeviceList, Result};
+
+/// A vendor and product ID, in the `[vendor]:[product]` hex form of the
+/// `-d` option of `lsusb`. Either can be left out to match any.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VidPid {
+    vid: Option<u16>,
+    pid: Option<u16>,
+}
+
+impl VidPid {
+    /// Determines if the IDs match.
+    pub fn matches(&self, vid: u16, pid: u16) -> bool {
+        matches(self.vid, vid) && matches(self.pid, pid)
+    }
+}
+
+impl FromStr for VidPid {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (vid, pid) = s
+            .split_once(':')
+            .ok_or_else(|| format!("'{}' is not in the form [vendor]:[product]", s))?;
+        Ok(Self {
+            vid: parse_id(vid, parse_hex)?,
+            pid: parse_id(pid, parse_hex)?,
+        })
+    }
+}
+
+/// A bus and device number, in the `[[bus]:][devnum]` decimal form of the
+/// `-s` option of `lsusb`. Either can be left out to match any.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BusDev {
+    bus: Option<u8>,
+    address: Option<u8>,
+}
+
+impl FromStr for BusDev {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (bus, address) = s.split_once(':').unwrap_or(("", s));
+        Ok(Self {
+            bus: parse_id(bus, u8::from_str)?,
+            address: parse_id(address, u8::from_str)?,
+        })
+    }
+}
+
+/// Determines if a value matches an optional filter.
+fn matches<T: PartialEq>(filter: Option<T>, val: T) -> bool {
+    filter.map_or(true, |f| f == val)
+}
+
+/// Parses a hex ID, with or without the 0x prefix.
+fn parse_hex(s: &str) -> std::result::Result<u16, ParseIntError> {
+    u16::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16)
+}
+
+/// Parses an ID that may be left empty.
+fn parse_id<T, E>(
+    s: &str,
+    parse: impl Fn(&str) -> std::result::Result<T, E>,
+) -> std::result::Result<Option<T>, String> {
+    match s {
+        "" => Ok(None),
+        _ => parse(s)
+            .map(Some)
+            .map_err(|_| format!("'{}' is not a valid ID", s)),
+    }
+}
+
+/// Selects devices by vendor and product ID, and by bus and device
+/// number.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceFilter {
+    vid_pid: VidPid,
+    bus_dev: BusDev,
+}
+
+impl DeviceFilter {
+    /// Creates a filter from the optional `-d` and `-s` arguments.
+    pub fn new(vid_pid: Option<VidPid>, bus_dev: Option<BusDev>) -> Self {
+        Self {
+            vid_pid: vid_pid.unwrap_or_default(),
+            bus_dev: bus_dev.unwrap_or_default(),
+        }
+    }
+
+    /// Determines if the filter selects all devices.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Determines if a device matches the filter.
+    pub fn matches(&self, device: &Device, desc: &DeviceDescriptor) -> bool {
+        self.matches_ids(
+            desc.vendor_id(),
+            desc.product_id(),
+            device.bus_number(),
+            device.address(),
+        )
+    }
+
+    fn matches_ids(&self, vid: u16, pid: u16, bus: u8, address: u8) -> bool {
+        self.vid_pid.matches(vid, pid)
+            && matches(self.bus_dev.bus, bus)
+            && matches(self.bus_dev.address, address)
+    }
+
+    /// Gets the devices that match the filter, with their device
+    /// descriptors, sorted by bus and device number.
+    pub fn devices(&self) -> Result<Vec<(Device, DeviceDescriptor)>> {
+        let mut devices: Vec<_> = DeviceList::new()?
+            .iter()
+            .filter_map(|device| {
+                let desc = device.device_descriptor().ok()?;
+                self.matches(&device, &desc).then(|| (device, desc))
+            })
+            .collect();
+
+        devices.sort_by_key(|(device, _)| (device.bus_number(), device.address()));
+        Ok(devices)
+    }
+
+    /// Opens the one device that matches the filter.
+    ///
+    /// It's an error if no device matches, or if more than one does.
+    pub fn open(&self) -> CliResult<DeviceHandle> {
+        let mut devices = self.devices()?;
+        match devices.len() {
+            0 => Err("no device matches the selection".into()),
+            1 => Ok(devices.remove(0).0.open()?),
+            n => Err(format!("{} devices match the selection, narrow it with -d or -s", n).into()),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_vid_pid() {
+        let ids: VidPid = "1d6b:0002".parse().unwrap();
+        assert_eq!(Some(0x1d6b), ids.vid);
+        assert_eq!(Some(0x0002), ids.pid);
+
+        let ids: VidPid = "0x1d6b:".parse().unwrap();
+        assert_eq!(Some(0x1d6b), ids.vid);
+        assert_eq!(None, ids.pid);
+
+        let ids: VidPid = ":a4a0".parse().unwrap();
+        assert_eq!(None, ids.vid);
+        assert_eq!(Some(0xa4a0), ids.pid);
+
+        assert!("1d6b".parse::<VidPid>().is_err());
+        assert!("1d6b:xyz".parse::<VidPid>().is_err());
+    }
+
+    #[test]
+    fn parse_bus_dev() {
+        let loc: BusDev = "3:12".parse().unwrap();
+        assert_eq!(Some(3), loc.bus);
+        assert_eq!(Some(12), loc.address);
+
+        let loc: BusDev = "12".parse().unwrap();
+        assert_eq!(None, loc.bus);
+        assert_eq!(Some(12), loc.address);
+
+        let loc: BusDev = "3:".parse().unwrap();
+        assert_eq!(Some(3), loc.bus);
+        assert_eq!(None, loc.address);
+
+        assert!("3:256".parse::<BusDev>().is_err());
+        assert!("0x10:3".parse::<BusDev>().is_err());
+    }
+
+    #[test]
+    fn filter_matches() {
+        let filter = DeviceFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches_ids(0x1d6b, 0x0002, 1, 1));
+
+        let filter = DeviceFilter::new(":0002".parse().ok(), "1:".parse().ok());
+        assert!(!filter.is_empty());
+        assert!(filter.matches_ids(0x1d6b, 0x0002, 1, 1));
+        assert!(!filter.matches_ids(0x1d6b, 0x0003, 1, 1));
+        assert!(!filter.matches_ids(0x1d6b, 0x0002, 2, 1));
+    }
+}
@@ -0,0 +1,493 @@
+// yusb/src/bin/yusb/list.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Listing devices, like `lsusb` and `lsusb -v`.
+
+use crate::names::Names;
+use std::{
+    fmt::{Display, Write},
+    time::Duration,
+};
+use yusb::{
+    AltSettingNode, ConfigNode, Device, DeviceDescriptor, DeviceInfo, EndpointNode, Function,
+    Result, Speed, SsEndpointCompanion, Version,
+};
+
+/// The timeout for reading each device's strings.
+const STRING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The width of the name and value columns in the verbose listing.
+const FIELD_WIDTH: usize = 25;
+
+/// Gets the one-line summary of a device, as printed by `lsusb`.
+pub fn summary(device: &Device, desc: &DeviceDescriptor, names: &Names) -> String {
+    let vid = desc.vendor_id();
+    let pid = desc.product_id();
+    let mut line = format!(
+        "Bus {:03} Device {:03}: ID {:04x}:{:04x}",
+        device.bus_number(),
+        device.address(),
+        vid,
+        pid
+    );
+    for name in [names.vendor(vid), names.product(vid, pid)]
+        .iter()
+        .flatten()
+    {
+        line.push(' ');
+        line.push_str(name);
+    }
+    line
+}
+
+/// Takes a snapshot of a device, with its strings if the device can be
+/// opened.
+pub fn device_info(device: &Device) -> Result<DeviceInfo> {
+    match device.open() {
+        Ok(handle) => Ok(DeviceInfo::from_handle(&handle, STRING_TIMEOUT)?),
+        Err(_) => {
+            eprintln!(
+                "Couldn't open device {:03}:{:03}, some information will be missing",
+                device.bus_number(),
+                device.address()
+            );
+            DeviceInfo::from_device(device)
+        }
+    }
+}
+
+/// Gets the full description of a device, as printed by `lsusb -v`.
+pub fn verbose(info: &DeviceInfo, names: &Names) -> String {
+    let mut out = String::new();
+    let dev = &info.descriptors.device;
+    let vid = dev.vendor_id;
+    let pid = dev.product_id;
+
+    let _ = writeln!(out, "Port {}", info.port());
+    let _ = writeln!(out, "Negotiated speed: {}", speed_name(info.speed));
+    let _ = writeln!(out, "Device Descriptor:");
+    field(&mut out, 2, "bLength", 18, "");
+    field(&mut out, 2, "bDescriptorType", 1, "");
+    field(&mut out, 2, "bcdUSB", bcd(dev.usb_version), "");
+    class_fields(
+        &mut out,
+        2,
+        "bDevice",
+        [dev.class_code, dev.sub_class_code, dev.protocol_code],
+        names,
+    );
+    field(&mut out, 2, "bMaxPacketSize0", dev.max_packet_size, "");
+    field(
+        &mut out,
+        2,
+        "idVendor",
+        format!("{:#06x}", vid),
+        names.vendor(vid).unwrap_or(""),
+    );
+    field(
+        &mut out,
+        2,
+        "idProduct",
+        format!("{:#06x}", pid),
+        names.product(vid, pid).unwrap_or(""),
+    );
+    field(&mut out, 2, "bcdDevice", bcd(dev.device_version), "");
+    string_field(
+        &mut out,
+        2,
+        "iManufacturer",
+        dev.manufacturer_string_index,
+        info,
+    );
+    string_field(&mut out, 2, "iProduct", dev.product_string_index, info);
+    string_field(&mut out, 2, "iSerial", dev.serial_number_string_index, info);
+    field(
+        &mut out,
+        2,
+        "bNumConfigurations",
+        dev.num_configurations,
+        "",
+    );
+
+    for config in &info.descriptors.configurations {
+        config_fields(&mut out, config, info, names);
+    }
+    out
+}
+
+fn config_fields(out: &mut String, config: &ConfigNode, info: &DeviceInfo, names: &Names) {
+    let _ = writeln!(out, "  Configuration Descriptor:");
+    field(out, 4, "bLength", 9, "");
+    field(out, 4, "bDescriptorType", 2, "");
+    field(
+        out,
+        4,
+        "wTotalLength",
+        format!("{:#06x}", config.total_length),
+        "",
+    );
+    field(out, 4, "bNumInterfaces", config.interfaces.len(), "");
+    field(out, 4, "bConfigurationValue", config.number, "");
+    string_field(
+        out,
+        4,
+        "iConfiguration",
+        config.description_string_index,
+        info,
+    );
+    field(
+        out,
+        4,
+        "bmAttributes",
+        format!("{:#04x}", config.attributes),
+        "",
+    );
+    if config.attributes & 0x80 == 0 {
+        let _ = writeln!(out, "      (Missing must-be-set bit!)");
+    }
+    if config.self_powered() {
+        let _ = writeln!(out, "      Self Powered");
+    } else {
+        let _ = writeln!(out, "      (Bus Powered)");
+    }
+    if config.remote_wakeup() {
+        let _ = writeln!(out, "      Remote Wakeup");
+    }
+    field(out, 4, "MaxPower", format!("{}mA", config.max_power), "");
+    extra_fields(out, 4, &config.extra);
+
+    for iface in &config.interfaces {
+        if let Some(function) = config
+            .functions
+            .iter()
+            .find(|f| f.first_interface() == iface.number)
+        {
+            function_fields(out, function, info, names);
+        }
+        for alt in &iface.alt_settings {
+            interface_fields(out, alt, info, names);
+        }
+    }
+}
+
+fn function_fields(out: &mut String, function: &Function, info: &DeviceInfo, names: &Names) {
+    let _ = writeln!(out, "    Interface Association:");
+    field(out, 6, "bLength", 8, "");
+    field(out, 6, "bDescriptorType", 11, "");
+    field(out, 6, "bFirstInterface", function.first_interface(), "");
+    field(out, 6, "bInterfaceCount", function.interface_count(), "");
+    class_fields(
+        out,
+        6,
+        "bFunction",
+        [
+            function.class_code(),
+            function.sub_class_code(),
+            function.protocol_code(),
+        ],
+        names,
+    );
+    string_field(
+        out,
+        6,
+        "iFunction",
+        function.description_string_index(),
+        info,
+    );
+}
+
+fn interface_fields(out: &mut String, alt: &AltSettingNode, info: &DeviceInfo, names: &Names) {
+    let _ = writeln!(out, "    Interface Descriptor:");
+    field(out, 6, "bLength", 9, "");
+    field(out, 6, "bDescriptorType", 4, "");
+    field(out, 6, "bInterfaceNumber", alt.interface_number, "");
+    field(out, 6, "bAlternateSetting", alt.setting_number, "");
+    field(out, 6, "bNumEndpoints", alt.endpoints.len(), "");
+    class_fields(
+        out,
+        6,
+        "bInterface",
+        [alt.class_code, alt.sub_class_code, alt.protocol_code],
+        names,
+    );
+    string_field(out, 6, "iInterface", alt.description_string_index, info);
+    extra_fields(out, 6, &alt.extra);
+
+    for ep in &alt.endpoints {
+        endpoint_fields(out, ep);
+    }
+}
+
+fn endpoint_fields(out: &mut String, ep: &EndpointNode) {
+    const TRANSFER_TYPES: [&str; 4] = ["Control", "Isochronous", "Bulk", "Interrupt"];
+    const SYNC_TYPES: [&str; 4] = ["None", "Asynchronous", "Adaptive", "Synchronous"];
+    const USAGE_TYPES: [&str; 4] = ["Data", "Feedback", "Implicit feedback Data", "(reserved)"];
+
+    let dir = match ep.address & 0x80 {
+        0 => "OUT",
+        _ => "IN",
+    };
+    let mps = ep.max_packet_size;
+
+    let _ = writeln!(out, "      Endpoint Descriptor:");
+    field(out, 8, "bLength", ep.length, "");
+    field(out, 8, "bDescriptorType", 5, "");
+    field(
+        out,
+        8,
+        "bEndpointAddress",
+        format!("{:#04x}", ep.address),
+        format!(" EP {} {}", ep.address & 0x0F, dir),
+    );
+    field(out, 8, "bmAttributes", ep.attributes, "");
+    let attr = usize::from(ep.attributes);
+    let _ = writeln!(
+        out,
+        "          Transfer Type            {}",
+        TRANSFER_TYPES[attr & 0x03]
+    );
+    let _ = writeln!(
+        out,
+        "          Synch Type               {}",
+        SYNC_TYPES[(attr >> 2) & 0x03]
+    );
+    let _ = writeln!(
+        out,
+        "          Usage Type               {}",
+        USAGE_TYPES[(attr >> 4) & 0x03]
+    );
+    field(
+        out,
+        8,
+        "wMaxPacketSize",
+        format!("{:#06x}", mps),
+        format!(" {}x {} bytes", ((mps >> 11) & 0x03) + 1, mps & 0x7FF),
+    );
+    field(out, 8, "bInterval", ep.interval, "");
+    if ep.length >= 9 {
+        field(out, 8, "bRefresh", ep.refresh, "");
+        field(out, 8, "bSynchAddress", ep.synch_address, "");
+    }
+
+    for desc in split_descriptors(&ep.extra) {
+        match SsEndpointCompanion::parse(desc) {
+            Some(companion) => {
+                let _ = writeln!(out, "        SuperSpeed Endpoint Companion:");
+                field(out, 10, "bLength", desc[0], "");
+                field(out, 10, "bDescriptorType", desc[1], "");
+                field(out, 10, "bMaxBurst", companion.max_burst(), "");
+                field(
+                    out,
+                    10,
+                    "wBytesPerInterval",
+                    format!("{:#06x}", companion.bytes_per_interval()),
+                    "",
+                );
+            }
+            None => extra_fields(out, 8, desc),
+        }
+    }
+}
+
+/// Writes the class, subclass, and protocol fields of a device,
+/// interface, or function, with their names.
+fn class_fields(out: &mut String, indent: usize, prefix: &str, codes: [u8; 3], names: &Names) {
+    let [class, sub_class, protocol] = codes;
+    field(
+        out,
+        indent,
+        &format!("{}Class", prefix),
+        class,
+        names.class(class).unwrap_or(""),
+    );
+    field(
+        out,
+        indent,
+        &format!("{}SubClass", prefix),
+        sub_class,
+        names.sub_class(class, sub_class).unwrap_or(""),
+    );
+    field(
+        out,
+        indent,
+        &format!("{}Protocol", prefix),
+        protocol,
+        names.protocol(class, sub_class, protocol).unwrap_or(""),
+    );
+}
+
+/// Writes a string index field, with the string, if it was read.
+fn string_field(out: &mut String, indent: usize, name: &str, index: Option<u8>, info: &DeviceInfo) {
+    field(
+        out,
+        indent,
+        name,
+        index.unwrap_or(0),
+        info.string(index).unwrap_or(""),
+    );
+}
+
+/// Writes the descriptors in an extra block, other than the interface
+/// associations, which are written with their interfaces.
+fn extra_fields(out: &mut String, indent: usize, extra: &[u8]) {
+    for desc in split_descriptors(extra) {
+        if desc[1] == 0x0B {
+            continue;
+        }
+        let _ = write!(
+            out,
+            "{:indent$}Descriptor (type {:#04x}):",
+            "",
+            desc[1],
+            indent = indent
+        );
+        for b in desc {
+            let _ = write!(out, " {:02x}", b);
+        }
+        out.push('\n');
+    }
+}
+
+/// Splits a block of extra descriptors into the individual descriptors.
+///
+/// A descriptor with a bad length ends the block.
+fn split_descriptors(mut buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || match *buf {
+        [len, _, ..] if len >= 2 && usize::from(len) <= buf.len() => {
+            let (desc, rest) = buf.split_at(usize::from(len));
+            buf = rest;
+            Some(desc)
+        }
+        _ => None,
+    })
+}
+
+/// Writes a field in the same columns as `lsusb -v`, with the name left
+/// aligned, the value right aligned, and then an optional description.
+fn field(out: &mut String, indent: usize, name: &str, value: impl Display, desc: impl Display) {
+    let value = value.to_string();
+    let width = FIELD_WIDTH.saturating_sub(name.len()).max(value.len() + 1);
+    let line = format!(
+        "{:indent$}{}{:>width$} {}",
+        "",
+        name,
+        value,
+        desc,
+        indent = indent,
+        width = width
+    );
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+/// Formats a BCD version the way `lsusb` does, like "2.00".
+fn bcd(ver: Version) -> String {
+    format!("{}.{}{}", ver.major(), ver.minor(), ver.sub_minor())
+}
+
+/// Gets the name of a speed, like "High Speed (480Mbps)".
+pub fn speed_name(speed: Speed) -> &'static str {
+    match speed {
+        Speed::Low => "Low Speed (1Mbps)",
+        Speed::Full => "Full Speed (12Mbps)",
+        Speed::High => "High Speed (480Mbps)",
+        Speed::Super => "SuperSpeed (5Gbps)",
+        Speed::SuperPlus => "SuperSpeed+ (10Gbps)",
+        _ => "Unknown",
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use yusb::{
+        ConfigDescriptorBuilder, DescriptorTree, DeviceDescriptorBuilder, EndpointBuilder,
+        InterfaceBuilder, ParseMode, TransferType,
+    };
+
+    fn device_info() -> DeviceInfo {
+        let mut dev = DeviceDescriptorBuilder::new(0x1d6b, 0x0002);
+        dev.product_string_index(2).num_configurations(1);
+
+        let mut iface = InterfaceBuilder::new(0);
+        iface.class(0x09, 0, 0).endpoint(
+            EndpointBuilder::new(0x81)
+                .transfer_type(TransferType::Interrupt)
+                .max_packet_size(4)
+                .interval(12),
+        );
+
+        let mut config = ConfigDescriptorBuilder::new(1);
+        config.interface(&iface);
+
+        let mut buf = dev.to_bytes();
+        buf.extend(config.to_bytes().unwrap());
+
+        DeviceInfo {
+            bus_number: 1,
+            address: 1,
+            port_numbers: vec![],
+            speed: Speed::High,
+            descriptors: DescriptorTree::parse(&buf, ParseMode::Strict).unwrap(),
+            strings: BTreeMap::from([(2, "xHCI Host Controller".to_string())]),
+        }
+    }
+
+    #[test]
+    fn field_columns() {
+        let mut out = String::new();
+        field(&mut out, 2, "bLength", 18, "");
+        field(&mut out, 2, "idVendor", "0x1d6b", "Linux Foundation");
+        field(&mut out, 4, "bConfigurationValue", 1, "");
+        assert_eq!(
+            "  bLength                18\n  \
+             idVendor           0x1d6b Linux Foundation\n    \
+             bConfigurationValue     1\n",
+            out
+        );
+    }
+
+    #[test]
+    fn split_extra() {
+        let extra = [0x03, 0x24, 0x01, 0x02, 0x21, 0x05];
+        let descs: Vec<_> = split_descriptors(&extra).collect();
+        assert_eq!(vec![&extra[..3], &extra[3..5]], descs);
+    }
+
+    #[test]
+    fn verbose_listing() {
+        let out = verbose(&device_info(), &Names::default());
+
+        assert!(out.starts_with("Port 1\nNegotiated speed: High Speed (480Mbps)\n"));
+        assert!(out.contains("  bDeviceClass            0 (Defined at Interface level)\n"));
+        assert!(out.contains("  idVendor           0x1d6b Linux Foundation\n"));
+        assert!(out.contains("  iProduct                2 xHCI Host Controller\n"));
+        assert!(out.contains("    wTotalLength       0x0019\n"));
+        assert!(out.contains("      bInterfaceClass         9 Hub\n"));
+        assert!(out.contains("        bEndpointAddress     0x81  EP 1 IN\n"));
+        assert!(out.contains("          Transfer Type            Interrupt\n"));
+        assert!(out.contains("        wMaxPacketSize     0x0004  1x 4 bytes\n"));
+        assert!(!out.contains("bRefresh"));
+    }
+
+    #[test]
+    fn verbose_audio_endpoint() {
+        let mut info = device_info();
+        info.descriptors = crate::test_fixtures::audio_device();
+
+        let out = verbose(&info, &Names::default());
+        assert!(out.contains("    wTotalLength       0x002b\n"));
+        assert!(out.contains("        bLength                 9\n"));
+        assert!(out.contains("        bSynchAddress         130\n"));
+    }
+}
@@ -0,0 +1,157 @@
+// yusb/src/bin/yusb/main.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! `yusb`, a command-line tool for USB devices.
+//!
+//! With no subcommand, this lists the devices like `lsusb`, with the same
+//! `-v`, `-t`, `-d`, and `-s` options. The subcommands are:
+//!
+//! - `monitor` prints the devices as they arrive and leave.
+//! - `control` sends a single control request to a device.
+//! - `bench` measures the throughput and latency of an endpoint pair.
+
+mod bench;
+mod control;
+mod filter;
+mod list;
+mod monitor;
+mod names;
+mod queue;
+mod tree;
+
+// The descriptor fixtures shared with the library tests, which take the
+// builder types from this module.
+#[cfg(test)]
+#[path = "../../test_fixtures.rs"]
+mod test_fixtures;
+#[cfg(test)]
+use yusb::{
+    ConfigDescriptorBuilder, DescriptorTree, DeviceDescriptorBuilder, EndpointBuilder,
+    InterfaceBuilder, ParseMode, TransferType,
+};
+
+use bench::BenchArgs;
+use clap::{Parser, Subcommand};
+use control::ControlArgs;
+use filter::{BusDev, DeviceFilter, VidPid};
+use monitor::MonitorArgs;
+use names::Names;
+use serde::Serialize;
+use std::{error::Error, process};
+use tree::TreeNode;
+
+/// List and work with USB devices.
+#[derive(Debug, Parser)]
+#[command(name = "yusb", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Show the full descriptors of the devices.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Show the physical device hierarchy as a tree.
+    #[arg(short, long, conflicts_with_all = ["vid_pid", "bus_dev", "json"])]
+    tree: bool,
+
+    /// Show only the devices with the vendor and/or product ID, in hex.
+    #[arg(short = 'd', value_name = "[vendor]:[product]")]
+    vid_pid: Option<VidPid>,
+
+    /// Show only the devices on the bus and/or with the device number, in
+    /// decimal.
+    #[arg(short = 's', value_name = "[[bus]:][devnum]")]
+    bus_dev: Option<BusDev>,
+
+    /// Print the devices and their descriptors as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    Monitor(MonitorArgs),
+    Control(ControlArgs),
+    Bench(BenchArgs),
+}
+
+/// The result of a command, which can fail with any error.
+pub type CliResult<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Prints a value as JSON, either pretty-printed or on a single line.
+pub fn print_json<T: Serialize>(value: &T, pretty: bool) -> CliResult<()> {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)?
+    } else {
+        serde_json::to_string(value)?
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let res = match &cli.command {
+        Some(Command::Monitor(args)) => monitor::run(args).map(|_| true),
+        Some(Command::Control(args)) => control::run(args).map(|_| true),
+        Some(Command::Bench(args)) => bench::run(args).map(|_| true),
+        None => run(&cli),
+    };
+
+    match res {
+        // Like lsusb, it's an error if the filter didn't match anything.
+        Ok(false) => process::exit(1),
+        Ok(true) => {}
+        Err(err) => {
+            eprintln!("yusb: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Lists the devices, returning whether any were found.
+fn run(cli: &Cli) -> CliResult<bool> {
+    let filter = DeviceFilter::new(cli.vid_pid, cli.bus_dev);
+    let devices = filter.devices()?;
+    let found = !devices.is_empty() || filter.is_empty();
+
+    if cli.json {
+        let infos = devices
+            .iter()
+            .map(|(device, _)| list::device_info(device))
+            .collect::<yusb::Result<Vec<_>>>()?;
+        print_json(&infos, true)?;
+        return Ok(found);
+    }
+
+    let names = Names::load();
+
+    if cli.tree {
+        let nodes = devices
+            .iter()
+            .filter_map(|(device, desc)| TreeNode::new(device, desc))
+            .collect();
+        print!("{}", tree::draw(nodes, cli.verbose, &names));
+    } else if cli.verbose {
+        for (device, desc) in &devices {
+            let info = list::device_info(device)?;
+            println!();
+            println!("{}", list::summary(device, desc, &names));
+            print!("{}", list::verbose(&info, &names));
+        }
+    } else {
+        for (device, desc) in &devices {
+            println!("{}", list::summary(device, desc, &names));
+        }
+    }
+    Ok(found)
+}
@@ -0,0 +1,459 @@
+// yusb/src/bin/yusb/monitor.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The `monitor` command, which prints devices as they arrive and leave.
+
+use crate::filter::VidPid;
+use crate::{print_json, CliResult};
+use clap::Args;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use yusb::{ClassCode, Context, Device, Hotplug, HotplugBuilder, Speed};
+
+/// The timeout for reading each device's strings.
+const STRING_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Watch for devices arriving and leaving.
+#[derive(Debug, Args)]
+pub struct MonitorArgs {
+    /// Show only the devices with the vendor and/or product ID, in hex.
+    #[arg(short = 'd', value_name = "[vendor]:[product]")]
+    vid_pid: Option<VidPid>,
+
+    /// Show only the devices with the class, or that have an interface
+    /// with the class.
+    #[arg(short, long, value_parser = parse_class)]
+    class: Option<u8>,
+
+    /// Show only the devices at or under the port, like "1-2" or "1-2.3".
+    #[arg(short, long)]
+    port: Option<String>,
+
+    /// Also show the devices that are already connected, as arrivals.
+    #[arg(short, long)]
+    enumerate: bool,
+
+    /// Print each event as a line of JSON.
+    #[arg(long)]
+    json: bool,
+
+    /// Poll the device list rather than using hotplug notifications.
+    ///
+    /// This is the default on platforms that don't support hotplug.
+    #[arg(long)]
+    poll: bool,
+
+    /// The time between polls of the device list, in milliseconds.
+    #[arg(long, value_name = "MS", default_value = "500")]
+    poll_interval: u64,
+}
+
+impl MonitorArgs {
+    /// Determines if a device, with its classes, passes the filters.
+    fn matches(&self, event: &Event, classes: &[ClassCode]) -> bool {
+        self.vid_pid
+            .map_or(true, |ids| ids.matches(event.vendor_id, event.product_id))
+            && self
+                .class
+                .map_or(true, |c| classes.contains(&ClassCode::from(c)))
+            && self
+                .port
+                .as_ref()
+                .map_or(true, |prefix| port_matches(&event.port, prefix))
+    }
+}
+
+/// Parses a class code, in decimal or 0x-prefixed hex.
+fn parse_class(s: &str) -> std::result::Result<u8, String> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| format!("'{}' is not a valid class code", s))
+}
+
+/// Whether a device arrived or left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum EventKind {
+    Arrived,
+    Left,
+}
+
+/// Sends the hotplug notifications to the monitor thread, which can do the
+/// blocking work of reading the strings.
+struct HotplugSender(Sender<(EventKind, Device)>);
+
+impl Hotplug for HotplugSender {
+    fn device_arrived(&mut self, device: Device) {
+        let _ = self.0.send((EventKind::Arrived, device));
+    }
+
+    fn device_left(&mut self, device: Device) {
+        let _ = self.0.send((EventKind::Left, device));
+    }
+}
+
+/// The strings that identify a device.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+struct DeviceStrings {
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial_number: Option<String>,
+}
+
+impl DeviceStrings {
+    /// Reads the strings from the device, if it can be opened.
+    fn read(device: &Device) -> Self {
+        let read = || -> yusb::Result<Self> {
+            let desc = device.device_descriptor()?;
+            let handle = device.open()?;
+            let timeout = yusb::Timeout::from(STRING_TIMEOUT).to_deadline();
+            let lang = match handle.read_languages(timeout)?.first() {
+                Some(&lang) => lang,
+                None => return Ok(Self::default()),
+            };
+            Ok(Self {
+                manufacturer: handle.read_manufacturer_string(lang, &desc, timeout).ok(),
+                product: handle.read_product_string(lang, &desc, timeout).ok(),
+                serial_number: handle.read_serial_number_string(lang, &desc, timeout).ok(),
+            })
+        };
+        read().unwrap_or_default()
+    }
+}
+
+/// A device arriving or leaving.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Event {
+    time: String,
+    event: EventKind,
+    bus: u8,
+    address: u8,
+    port: String,
+    vendor_id: u16,
+    product_id: u16,
+    speed: Speed,
+    classes: Vec<String>,
+    #[serde(flatten)]
+    strings: DeviceStrings,
+}
+
+impl Event {
+    /// Gets the event as a line of text.
+    fn to_line(&self) -> String {
+        let kind = match self.event {
+            EventKind::Arrived => "arrived",
+            EventKind::Left => "left   ",
+        };
+        let mut line = format!(
+            "{} {} {:04x}:{:04x} port {} {} [{}]",
+            self.time,
+            kind,
+            self.vendor_id,
+            self.product_id,
+            self.port,
+            self.speed,
+            self.classes.join(", ")
+        );
+        let strings = [
+            &self.strings.manufacturer,
+            &self.strings.product,
+            &self.strings.serial_number,
+        ];
+        for s in strings.iter().copied().flatten() {
+            line.push_str(&format!(" \"{}\"", s));
+        }
+        line
+    }
+}
+
+/// Reads the event for a device arriving, along with its classes, or
+/// `None` if its descriptor can't be read.
+///
+/// The strings aren't read here, since that means opening the device.
+fn read_event(device: &Device) -> Option<(Event, Vec<ClassCode>)> {
+    let desc = device.device_descriptor().ok()?;
+    let classes = device_classes(device, desc.class_code());
+    let event = Event {
+        time: String::new(),
+        event: EventKind::Arrived,
+        bus: device.bus_number(),
+        address: device.address(),
+        port: device.port().map(|p| p.to_string()).unwrap_or_default(),
+        vendor_id: desc.vendor_id(),
+        product_id: desc.product_id(),
+        speed: device.speed(),
+        classes: classes.iter().map(|c| c.name().to_string()).collect(),
+        strings: DeviceStrings::default(),
+    };
+    Some((event, classes))
+}
+
+/// Gets the classes of a device: its own class, or if that is defined at
+/// the interface level, the distinct classes of the interfaces in its
+/// first configuration.
+fn device_classes(device: &Device, class_code: u8) -> Vec<ClassCode> {
+    let class = ClassCode::from(class_code);
+    if class != ClassCode::PerInterface {
+        return vec![class];
+    }
+
+    let mut classes = vec![];
+    if let Ok(config) = device.config_descriptor(0) {
+        for iface in config.interfaces() {
+            for alt in iface.descriptors() {
+                if !classes.contains(&alt.class()) {
+                    classes.push(alt.class());
+                }
+            }
+        }
+    }
+    classes
+}
+
+/// Determines if a port is the same as, or downstream of, a prefix.
+///
+/// A prefix of "1-2" matches "1-2" and "1-2.3", but not "1-20".
+fn port_matches(port: &str, prefix: &str) -> bool {
+    match port.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('-') || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, with milliseconds.
+fn format_time(time: SystemTime) -> String {
+    let dur = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = dur.as_secs();
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Converts days since the epoch to a civil date, from Howard Hinnant's
+    // `civil_from_days` algorithm.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        dur.subsec_millis()
+    )
+}
+
+/// Watches for devices until the program is killed.
+pub fn run(args: &MonitorArgs) -> CliResult<()> {
+    let context = Context::new()?;
+    let (tx, rx) = mpsc::channel();
+
+    // These need to stay alive while monitoring.
+    let _hotplug = if args.poll || !yusb::has_hotplug() {
+        let context = context.clone();
+        let interval = Duration::from_millis(args.poll_interval);
+        let enumerate = args.enumerate;
+        thread::spawn(move || poll(context, interval, enumerate, tx));
+        None
+    } else {
+        let reg = HotplugBuilder::new()
+            .enumerate(args.enumerate)
+            .register(context.clone(), Box::new(HotplugSender(tx)))?;
+        Some((reg, context.spawn_event_thread()?))
+    };
+
+    // The devices that arrived, with their classes, so that they can be
+    // reported, and filtered, as they were when they leave.
+    let mut arrived = HashMap::new();
+
+    for (kind, device) in rx {
+        let key = (device.bus_number(), device.address());
+        let seen = match kind {
+            EventKind::Arrived => read_event(&device),
+            // A device that was already connected wasn't seen arriving,
+            // unless enumerating.
+            EventKind::Left => arrived.remove(&key).or_else(|| read_event(&device)),
+        };
+        let (mut event, classes) = match seen {
+            Some(seen) => seen,
+            None => continue,
+        };
+
+        let matched = args.matches(&event, &classes);
+        if kind == EventKind::Arrived {
+            // Only the devices that are shown are opened for their strings.
+            if matched {
+                event.strings = DeviceStrings::read(&device);
+            }
+            arrived.insert(key, (event.clone(), classes));
+        }
+        if !matched {
+            continue;
+        }
+
+        event.time = format_time(SystemTime::now());
+        event.event = kind;
+
+        if args.json {
+            print_json(&event, false)?;
+        } else {
+            println!("{}", event.to_line());
+        }
+    }
+    Ok(())
+}
+
+/// Polls the device list, sending an event for each device that arrives
+/// or leaves.
+///
+/// The devices are identified by bus and address, since a device gets a
+/// new address whenever it is reconnected.
+fn poll(context: Context, interval: Duration, enumerate: bool, tx: Sender<(EventKind, Device)>) {
+    let mut known: Option<HashMap<(u8, u8), Device>> = None;
+
+    loop {
+        if let Ok(list) = context.devices() {
+            let current: HashMap<_, _> = list
+                .iter()
+                .map(|device| ((device.bus_number(), device.address()), device))
+                .collect();
+
+            let mut events = vec![];
+            match &known {
+                Some(known) => {
+                    for (key, device) in known {
+                        if !current.contains_key(key) {
+                            events.push((EventKind::Left, device.clone()));
+                        }
+                    }
+                    for (key, device) in &current {
+                        if !known.contains_key(key) {
+                            events.push((EventKind::Arrived, device.clone()));
+                        }
+                    }
+                }
+                None if enumerate => {
+                    events.extend(current.values().map(|d| (EventKind::Arrived, d.clone())));
+                }
+                None => {}
+            }
+
+            for event in events {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            known = Some(current);
+        }
+        thread::sleep(interval);
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn port_prefix() {
+        assert!(port_matches("1-2", "1-2"));
+        assert!(port_matches("1-2.3", "1-2"));
+        assert!(port_matches("1-2.3", "1"));
+        assert!(!port_matches("1-20", "1-2"));
+        assert!(!port_matches("11-2", "1"));
+        assert!(!port_matches("1", "1-2"));
+    }
+
+    #[test]
+    fn class_codes() {
+        assert_eq!(Ok(0x03), parse_class("3"));
+        assert_eq!(Ok(0xFF), parse_class("0xff"));
+        assert!(parse_class("0x100").is_err());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_time(UNIX_EPOCH));
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!("2024-02-29T12:34:56.789Z", format_time(time));
+    }
+
+    fn event() -> Event {
+        Event {
+            time: "2024-02-29T12:34:56.789Z".to_string(),
+            event: EventKind::Arrived,
+            bus: 1,
+            address: 5,
+            port: "1-2.3".to_string(),
+            vendor_id: 0x0525,
+            product_id: 0xa4a0,
+            speed: Speed::High,
+            classes: vec!["Vendor Specific Class".to_string()],
+            strings: DeviceStrings {
+                manufacturer: Some("Linux".to_string()),
+                product: Some("Gadget Zero".to_string()),
+                serial_number: None,
+            },
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let mut args = MonitorArgs {
+            vid_pid: None,
+            class: Some(0xFF),
+            port: Some("1-2".to_string()),
+            enumerate: false,
+            json: false,
+            poll: false,
+            poll_interval: 500,
+        };
+        let event = event();
+        let classes = [ClassCode::from(0xFF)];
+        assert!(args.matches(&event, &classes));
+        assert!(!args.matches(&event, &[ClassCode::from(0x03)]));
+
+        args.port = Some("1-3".to_string());
+        assert!(!args.matches(&event, &classes));
+    }
+
+    #[test]
+    fn event_output() {
+        let event = event();
+        assert_eq!(
+            "2024-02-29T12:34:56.789Z arrived 0525:a4a0 port 1-2.3 480 Mbps \
+             [Vendor Specific Class] \"Linux\" \"Gadget Zero\"",
+            event.to_line()
+        );
+
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.starts_with(
+            r#"{"time":"2024-02-29T12:34:56.789Z","event":"arrived","bus":1,"address":5,"#
+        ));
+        assert!(json
+            .ends_with(r#""manufacturer":"Linux","product":"Gadget Zero","serial_number":null}"#));
+    }
+}
@@ -0,0 +1,69 @@
+// yusb/src/bin/yusb/names.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Names of vendors, products, and classes.
+
+use yusb::{ids, UsbIds};
+
+/// Looks up names in the system's copy of `usb.ids`, falling back to the
+/// embedded database for anything that it's missing.
+#[derive(Debug, Default)]
+pub struct Names {
+    system: Option<UsbIds>,
+}
+
+impl Names {
+    /// Loads the system's `usb.ids`, if there is one.
+    pub fn load() -> Self {
+        Self {
+            system: UsbIds::system(),
+        }
+    }
+
+    /// Looks up the name of a vendor.
+    pub fn vendor(&self, vendor_id: u16) -> Option<&str> {
+        self.system
+            .as_ref()
+            .and_then(|ids| ids.vendor_name(vendor_id))
+            .or_else(|| ids::vendor_name(vendor_id))
+    }
+
+    /// Looks up the name of a product.
+    pub fn product(&self, vendor_id: u16, product_id: u16) -> Option<&str> {
+        self.system
+            .as_ref()
+            .and_then(|ids| ids.product_name(vendor_id, product_id))
+            .or_else(|| ids::product_name(vendor_id, product_id))
+    }
+
+    /// Looks up the name of a class.
+    pub fn class(&self, class: u8) -> Option<&str> {
+        self.system
+            .as_ref()
+            .and_then(|ids| ids.class_name(class))
+            .or_else(|| ids::class_name(class))
+    }
+
+    /// Looks up the name of a subclass.
+    pub fn sub_class(&self, class: u8, sub_class: u8) -> Option<&str> {
+        self.system
+            .as_ref()
+            .and_then(|ids| ids.sub_class_name(class, sub_class))
+            .or_else(|| ids::sub_class_name(class, sub_class))
+    }
+
+    /// Looks up the name of a protocol.
+    pub fn protocol(&self, class: u8, sub_class: u8, protocol: u8) -> Option<&str> {
+        self.system
+            .as_ref()
+            .and_then(|ids| ids.protocol_name(class, sub_class, protocol))
+            .or_else(|| ids::protocol_name(class, sub_class, protocol))
+    }
+}
@@ -0,0 +1,380 @@
+// yusb/src/bin/yusb/queue.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A queue of asynchronous bulk or interrupt transfers, for the `bench`
+//! command.
+//!
+//! The library only has synchronous transfers, so this drives the `libusb`
+//! asynchronous API directly. It keeps a fixed number of transfers
+//! submitted on an endpoint, resubmitting each one from its callback as it
+//! completes, while the calling thread handles the events.
+
+use std::{
+    collections::HashMap,
+    mem,
+    os::raw::{c_int, c_uint, c_void},
+    ptr::NonNull,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+use yusb::{
+    constants::*, ffi, Context, DeviceHandle, Error, LatencyStats, Result, TransferStats,
+    TransferType,
+};
+
+/// The longest time to block handling events before checking the clock.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of the transfers in a queue, and their results.
+///
+/// This is shared, under a lock, by the thread running the queue and the
+/// transfer callbacks.
+#[derive(Debug, Default)]
+struct State {
+    running: bool,
+    in_flight: usize,
+    submitted: Vec<Instant>,
+    transfers: u64,
+    bytes: u64,
+    errors: HashMap<Error, u64>,
+    timeouts: u64,
+    latencies: Vec<Duration>,
+}
+
+impl State {
+    /// Records the result of a transfer that took `latency` to complete.
+    fn record(&mut self, res: &Result<usize>, latency: Duration) {
+        self.transfers += 1;
+        match res {
+            Ok(n) => self.bytes += *n as u64,
+            Err(Error::Timeout) => self.timeouts += 1,
+            Err(err) => *self.errors.entry(*err).or_default() += 1,
+        }
+        self.latencies.push(latency);
+    }
+
+    /// Gets the statistics of the transfers, in the same form as the
+    /// handle metrics of the synchronous transfers.
+    fn stats(&mut self) -> TransferStats {
+        self.latencies.sort_unstable();
+        TransferStats {
+            transfers: self.transfers,
+            bytes: self.bytes,
+            errors: self.errors.clone(),
+            timeouts: self.timeouts,
+            latency: latency_stats(&self.latencies),
+        }
+    }
+}
+
+/// Gets the statistics of a sorted set of latencies.
+fn latency_stats(sorted: &[Duration]) -> LatencyStats {
+    let n = sorted.len();
+    if n == 0 {
+        return LatencyStats::default();
+    }
+    let pct = |p: usize| sorted[(p * n).saturating_sub(1) / 100];
+    LatencyStats {
+        min: sorted[0],
+        max: sorted[n - 1],
+        mean: sorted.iter().sum::<Duration>() / n as u32,
+        p50: pct(50),
+        p90: pct(90),
+        p99: pct(99),
+    }
+}
+
+/// Converts the status of a completed transfer into the result that the
+/// equivalent synchronous transfer would have returned.
+fn transfer_result(status: c_int, actual_length: c_int) -> Result<usize> {
+    let n = actual_length.max(0) as usize;
+    match status {
+        LIBUSB_TRANSFER_COMPLETED => Ok(n),
+        // A synchronous transfer reports the data that made it before
+        // a timeout, rather than the timeout.
+        LIBUSB_TRANSFER_TIMED_OUT if n > 0 => Ok(n),
+        LIBUSB_TRANSFER_TIMED_OUT => Err(Error::Timeout),
+        LIBUSB_TRANSFER_STALL => Err(Error::Pipe),
+        LIBUSB_TRANSFER_NO_DEVICE => Err(Error::NoDevice),
+        LIBUSB_TRANSFER_OVERFLOW => Err(Error::Overflow),
+        LIBUSB_TRANSFER_CANCELLED => Err(Error::Interrupted),
+        _ => Err(Error::Io),
+    }
+}
+
+/// Converts a `libusb` return code into an error.
+fn from_libusb(rc: c_int) -> Error {
+    match rc {
+        LIBUSB_ERROR_IO => Error::Io,
+        LIBUSB_ERROR_INVALID_PARAM => Error::InvalidParam,
+        LIBUSB_ERROR_ACCESS => Error::Access,
+        LIBUSB_ERROR_NO_DEVICE => Error::NoDevice,
+        LIBUSB_ERROR_NOT_FOUND => Error::NotFound,
+        LIBUSB_ERROR_BUSY => Error::Busy,
+        LIBUSB_ERROR_PIPE => Error::Pipe,
+        LIBUSB_ERROR_NO_MEM => Error::NoMem,
+        LIBUSB_ERROR_NOT_SUPPORTED => Error::NotSupported,
+        _ => Error::Other,
+    }
+}
+
+/// Locks the state of a queue.
+///
+/// A panic can't happen with the lock held, but if it did, the counts are
+/// still usable, and the callbacks can't unwind.
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// One transfer in the queue, and the buffer that it reads or writes.
+struct Slot {
+    transfer: NonNull<ffi::libusb_transfer>,
+    buf: Vec<u8>,
+    index: usize,
+    state: *const Mutex<State>,
+}
+
+impl Slot {
+    /// Allocates and fills a transfer on the endpoint, without submitting
+    /// it.
+    ///
+    /// The slot is boxed so that its address, which the transfer holds as
+    /// its user data, stays put. The state must outlive the slot.
+    fn new(
+        handle: &DeviceHandle,
+        state: &Mutex<State>,
+        index: usize,
+        endpoint: u8,
+        transfer_type: TransferType,
+        len: c_int,
+        timeout: c_uint,
+    ) -> Result<Box<Self>> {
+        let transfer =
+            NonNull::new(unsafe { ffi::libusb_alloc_transfer(0) }).ok_or(Error::NoMem)?;
+        let mut slot = Box::new(Slot {
+            transfer,
+            buf: vec![0u8; len as usize],
+            index,
+            state,
+        });
+
+        let fill = match transfer_type {
+            TransferType::Interrupt => ffi::libusb_fill_interrupt_transfer,
+            _ => ffi::libusb_fill_bulk_transfer,
+        };
+        let user_data = &*slot as *const Slot as *mut c_void;
+        // Safety: The slot owns the transfer and the buffer, which are freed
+        // together.
+        unsafe {
+            fill(
+                transfer.as_ptr(),
+                handle.as_raw(),
+                endpoint,
+                slot.buf.as_mut_ptr(),
+                len,
+                transfer_done,
+                user_data,
+                timeout,
+            )
+        };
+        Ok(slot)
+    }
+
+    /// Gets the state of the queue.
+    fn state(&self) -> &Mutex<State> {
+        // Safety: The state outlives the slots.
+        unsafe { &*self.state }
+    }
+
+    /// Submits the transfer, counting it as in flight if that worked.
+    ///
+    /// This takes the state of the queue, which is already locked.
+    fn submit(&self, state: &mut State) -> Result<()> {
+        state.submitted[self.index] = Instant::now();
+        match unsafe { ffi::libusb_submit_transfer(self.transfer.as_ptr()) } {
+            0 => {
+                state.in_flight += 1;
+                Ok(())
+            }
+            rc => Err(from_libusb(rc)),
+        }
+    }
+
+    /// Cancels the transfer, if it's in flight.
+    fn cancel(&self) {
+        let _ = unsafe { ffi::libusb_cancel_transfer(self.transfer.as_ptr()) };
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        // The queue drains the transfers before dropping the slots.
+        unsafe { ffi::libusb_free_transfer(self.transfer.as_ptr()) };
+    }
+}
+
+/// The callback for each completed transfer, which records the result and
+/// resubmits the transfer while the queue is running.
+extern "system" fn transfer_done(transfer: *mut ffi::libusb_transfer) {
+    // Safety: The user data is the slot of the transfer, which outlives it.
+    let (slot, res) = unsafe {
+        let slot = &*((*transfer).user_data as *const Slot);
+        let res = transfer_result((*transfer).status, (*transfer).actual_length);
+        (slot, res)
+    };
+
+    let mut state = lock(slot.state());
+    state.in_flight -= 1;
+    if !state.running {
+        return;
+    }
+
+    let latency = state.submitted[slot.index].elapsed();
+    state.record(&res, latency);
+
+    // Anything but a timeout, like a halt, won't fix itself.
+    if matches!(res, Ok(_) | Err(Error::Timeout)) {
+        if let Err(err) = slot.submit(&mut state) {
+            state.record(&Err(err), Duration::ZERO);
+        }
+    }
+}
+
+/// Handles the events of the context, for up to the timeout.
+///
+/// A signal that interrupts the wait is not an error.
+fn handle_events(ctx: &Context, timeout: Duration) -> Result<()> {
+    match ctx.handle_events(Some(timeout)) {
+        Err(Error::Interrupted) => Ok(()),
+        res => res,
+    }
+}
+
+/// Keeps `depth` asynchronous transfers of `size` bytes in flight on the
+/// endpoint for the duration, and gets the statistics of the ones that
+/// completed in that time.
+///
+/// The transfers complete on the thread that handles the events of the
+/// context, which has to be this one, so that it knows when they are all
+/// done and can be freed. So this fails with `Busy` if the context has an
+/// event thread.
+///
+/// A zero timeout waits forever for each transfer.
+pub fn run(
+    handle: &DeviceHandle,
+    endpoint: u8,
+    transfer_type: TransferType,
+    size: usize,
+    depth: usize,
+    duration: Duration,
+    timeout: Duration,
+) -> Result<TransferStats> {
+    let ctx = handle.context();
+    if ctx.has_event_thread() {
+        return Err(Error::Busy);
+    }
+
+    let len = c_int::try_from(size).map_err(|_| Error::InvalidParam)?;
+    let timeout = timeout.as_millis().min(c_uint::MAX as u128) as c_uint;
+
+    let state = Box::new(Mutex::new(State {
+        running: true,
+        submitted: vec![Instant::now(); depth],
+        ..State::default()
+    }));
+    let slots = (0..depth)
+        .map(|index| Slot::new(handle, &state, index, endpoint, transfer_type, len, timeout))
+        .collect::<Result<Vec<_>>>()?;
+
+    let deadline = Instant::now() + duration;
+    let mut res = slots
+        .iter()
+        .try_for_each(|slot| slot.submit(&mut lock(&state)));
+
+    while res.is_ok() && lock(&state).in_flight > 0 {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        res = handle_events(&ctx, (deadline - now).min(POLL_INTERVAL));
+    }
+
+    // Stop, and wait for the transfers still in flight to be cancelled
+    // before they can be freed.
+    lock(&state).running = false;
+    slots.iter().for_each(|slot| slot.cancel());
+
+    while lock(&state).in_flight > 0 {
+        if let Err(err) = handle_events(&ctx, POLL_INTERVAL) {
+            // The transfers can't be freed, so leak them, and the state,
+            // rather than risk a use-after-free.
+            slots.into_iter().for_each(mem::forget);
+            Box::leak(state);
+            return Err(err);
+        }
+    }
+    drop(slots);
+
+    res.map(|_| lock(&state).stats())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn results() {
+        assert_eq!(Ok(512), transfer_result(LIBUSB_TRANSFER_COMPLETED, 512));
+        assert_eq!(Ok(100), transfer_result(LIBUSB_TRANSFER_TIMED_OUT, 100));
+        assert_eq!(
+            Err(Error::Timeout),
+            transfer_result(LIBUSB_TRANSFER_TIMED_OUT, 0)
+        );
+        assert_eq!(Err(Error::Pipe), transfer_result(LIBUSB_TRANSFER_STALL, 0));
+        assert_eq!(
+            Err(Error::NoDevice),
+            transfer_result(LIBUSB_TRANSFER_NO_DEVICE, 0)
+        );
+        assert_eq!(Err(Error::Io), transfer_result(LIBUSB_TRANSFER_ERROR, 0));
+    }
+
+    #[test]
+    fn records_results() {
+        let mut state = State::default();
+        state.record(&Ok(512), Duration::from_micros(30));
+        state.record(&Err(Error::Timeout), Duration::from_micros(10));
+        state.record(&Err(Error::Pipe), Duration::from_micros(20));
+
+        let stats = state.stats();
+        assert_eq!(3, stats.transfers);
+        assert_eq!(512, stats.bytes);
+        assert_eq!(1, stats.timeouts);
+        assert_eq!(1, stats.error_count());
+        assert_eq!(Duration::from_micros(10), stats.latency.min);
+        assert_eq!(Duration::from_micros(30), stats.latency.max);
+        assert_eq!(Duration::from_micros(20), stats.latency.mean);
+    }
+
+    #[test]
+    fn percentiles() {
+        assert_eq!(LatencyStats::default(), latency_stats(&[]));
+
+        let sorted: Vec<_> = (1..=100).map(Duration::from_micros).collect();
+        let stats = latency_stats(&sorted);
+        assert_eq!(Duration::from_micros(50), stats.p50);
+        assert_eq!(Duration::from_micros(90), stats.p90);
+        assert_eq!(Duration::from_micros(99), stats.p99);
+
+        let stats = latency_stats(&[Duration::from_micros(7)]);
+        assert_eq!(Duration::from_micros(7), stats.p99);
+    }
+}
@@ -0,0 +1,197 @@
+// yusb/src/bin/yusb/tree.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The physical device hierarchy, like `lsusb -t`.
+
+use crate::names::Names;
+use std::fmt::Write;
+use yusb::{ClassCode, Device, DeviceDescriptor, Port, Speed};
+
+/// A device in the tree, with the classes of the interfaces in its active
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    port: Port,
+    address: u8,
+    speed: Speed,
+    vendor_id: u16,
+    product_id: u16,
+    interfaces: Vec<(u8, u8)>,
+}
+
+impl TreeNode {
+    /// Creates a node for a device.
+    ///
+    /// Returns `None` if the device's port can't be read, since then
+    /// there's no telling where it goes in the tree.
+    pub fn new(device: &Device, desc: &DeviceDescriptor) -> Option<Self> {
+        let port = device.port().ok()?;
+        let interfaces = match device.active_config_descriptor() {
+            Ok(config) => config
+                .interfaces()
+                .filter_map(|iface| {
+                    let alt = iface.descriptors().next()?;
+                    Some((iface.number(), alt.class_code()))
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        Some(Self {
+            port,
+            address: device.address(),
+            speed: device.speed(),
+            vendor_id: desc.vendor_id(),
+            product_id: desc.product_id(),
+            interfaces,
+        })
+    }
+
+    /// Determines if this is a root hub, which is directly on the bus.
+    fn is_root_hub(&self) -> bool {
+        self.port.port_numbers().is_empty()
+    }
+}
+
+/// Draws the tree of devices, with a line for each interface of every
+/// device, under the hub to which it is connected.
+///
+/// In verbose mode, the vendor and product of each device are listed
+/// under it.
+pub fn draw(mut nodes: Vec<TreeNode>, verbose: bool, names: &Names) -> String {
+    // Sorting by port puts every device after its hub.
+    nodes.sort_by(|a, b| {
+        (a.port.bus(), a.port.port_numbers()).cmp(&(b.port.bus(), b.port.port_numbers()))
+    });
+
+    let mut out = String::new();
+    let mut bus = None;
+
+    for node in &nodes {
+        if bus != Some(node.port.bus()) {
+            bus = Some(node.port.bus());
+            if !node.is_root_hub() {
+                let _ = writeln!(out, "/:  Bus {:03}", node.port.bus());
+            }
+        }
+
+        let depth = node.port.port_numbers().len();
+        let indent = 4 * depth;
+        if node.is_root_hub() {
+            let _ = writeln!(
+                out,
+                "/:  Bus {:03}.Port 001: Dev {:03}, Class=root_hub, {}",
+                node.port.bus(),
+                node.address,
+                short_speed(node.speed)
+            );
+        } else if node.interfaces.is_empty() {
+            let _ = writeln!(
+                out,
+                "{:indent$}|__ Port {}: Dev {:03}, {}",
+                "",
+                node.port,
+                node.address,
+                short_speed(node.speed),
+                indent = indent
+            );
+        } else {
+            for (iface, class) in &node.interfaces {
+                let _ = writeln!(
+                    out,
+                    "{:indent$}|__ Port {}: Dev {:03}, If {}, Class={}, {}",
+                    "",
+                    node.port,
+                    node.address,
+                    iface,
+                    ClassCode::from(*class).name(),
+                    short_speed(node.speed),
+                    indent = indent
+                );
+            }
+        }
+
+        if verbose {
+            let mut line = format!(
+                "{:indent$}ID {:04x}:{:04x}",
+                "",
+                node.vendor_id,
+                node.product_id,
+                indent = indent + 4
+            );
+            let vendor = names.vendor(node.vendor_id);
+            let product = names.product(node.vendor_id, node.product_id);
+            for name in [vendor, product].iter().flatten() {
+                line.push(' ');
+                line.push_str(name);
+            }
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    out
+}
+
+/// Gets the short form of a speed, like "480M".
+fn short_speed(speed: Speed) -> String {
+    match speed {
+        Speed::Unknown => "unknown".to_string(),
+        _ => format!("{}M", speed.as_mbps()),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn node(bus: u8, ports: &[u8], address: u8, interfaces: &[(u8, u8)]) -> TreeNode {
+        TreeNode {
+            port: Port::new(bus, ports.to_vec()),
+            address,
+            speed: match ports.len() {
+                0 => Speed::High,
+                _ => Speed::Full,
+            },
+            vendor_id: 0x1d6b,
+            product_id: 0x0002,
+            interfaces: interfaces.to_vec(),
+        }
+    }
+
+    #[test]
+    fn draw_tree() {
+        let nodes = vec![
+            node(1, &[1, 2], 4, &[(0, 0x03), (1, 0x03)]),
+            node(1, &[], 1, &[(0, 0x09)]),
+            node(1, &[1], 2, &[(0, 0x09)]),
+            node(2, &[3], 3, &[]),
+        ];
+
+        assert_eq!(
+            "/:  Bus 001.Port 001: Dev 001, Class=root_hub, 480M\n    \
+             |__ Port 1-1: Dev 002, If 0, Class=Hub, 12M\n        \
+             |__ Port 1-1.2: Dev 004, If 0, Class=Human Interface Device, 12M\n        \
+             |__ Port 1-1.2: Dev 004, If 1, Class=Human Interface Device, 12M\n\
+             /:  Bus 002\n    \
+             |__ Port 2-3: Dev 003, 12M\n",
+            draw(nodes, false, &Names::default())
+        );
+    }
+
+    #[test]
+    fn draw_verbose_tree() {
+        let nodes = vec![node(1, &[], 1, &[(0, 0x09)])];
+        assert_eq!(
+            "/:  Bus 001.Port 001: Dev 001, Class=root_hub, 480M\n    \
+             ID 1d6b:0002 Linux Foundation 2.0 root hub\n",
+            draw(nodes, true, &Names::default())
+        );
+    }
+}
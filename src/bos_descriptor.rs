@@ -0,0 +1,1010 @@
+// yusb/src/bos_descriptor.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The Binary device Object Store (BOS) descriptor and the device
+//! capabilities that it contains.
+
+use crate::{
+    fields::Speed, ms_os_descriptor::MsOs20DescriptorSetInfo, webusb::WebUsbCapability, Error,
+    Result, Version,
+};
+use libusb1_sys::{self as ffi, constants::*};
+use std::{fmt, slice, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Device capability type for a USB 2.0 Extension.
+pub const USB_2_0_EXTENSION_CAPABILITY: u8 = 0x02;
+/// Device capability type for SuperSpeed USB.
+pub const SUPERSPEED_USB_CAPABILITY: u8 = 0x03;
+/// Device capability type for a Container ID.
+pub const CONTAINER_ID_CAPABILITY: u8 = 0x04;
+/// Device capability type for a Platform.
+pub const PLATFORM_CAPABILITY: u8 = 0x05;
+/// Device capability type for SuperSpeedPlus USB.
+pub const SUPERSPEED_PLUS_CAPABILITY: u8 = 0x0A;
+/// Device capability type for a Billboard.
+pub const BILLBOARD_CAPABILITY: u8 = 0x0D;
+
+/// The size of the BOS descriptor header.
+const BOS_HEADER_SIZE: usize = 5;
+
+/// The size of the common header of a device capability descriptor.
+const DEV_CAP_HEADER_SIZE: usize = 3;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A 128-bit GUID, as used in USB descriptors.
+///
+/// This is stored as the raw bytes from the descriptor, in which the first
+/// three fields are little-endian, as is usual for Microsoft GUIDs. It is
+/// displayed in the conventional form, like
+/// `D8DD60DF-4589-4CC7-9CD2-659D9E648A9F`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    /// Creates a GUID from its raw bytes, as they appear in a descriptor.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// Creates a GUID from its fields, as they appear when displayed.
+    pub const fn from_fields(d1: u32, d2: u16, d3: u16, d4: [u8; 8]) -> Self {
+        let a = d1.to_le_bytes();
+        let b = d2.to_le_bytes();
+        let c = d3.to_le_bytes();
+        Self([
+            a[0], a[1], a[2], a[3], b[0], b[1], c[0], c[1], d4[0], d4[1], d4[2], d4[3], d4[4],
+            d4[5], d4[6], d4[7],
+        ])
+    }
+
+    /// Gets the raw bytes of the GUID, as they appear in a descriptor.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Reads a GUID from the start of a byte slice, if it's long enough.
+    pub(crate) fn from_slice(buf: &[u8]) -> Option<Self> {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(buf.get(..16)?);
+        Some(Self(bytes))
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-",
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            u16::from_le_bytes([b[4], b[5]]),
+            u16::from_le_bytes([b[6], b[7]]),
+            b[8],
+            b[9]
+        )?;
+        for x in &b[10..] {
+            write!(f, "{:02X}", x)?;
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// The Binary device Object Store (BOS) descriptor of a device.
+///
+/// This is the list of device-level capabilities, as reported by USB 2.1
+/// and later devices. It is read with
+/// [`DeviceHandle::bos_descriptor`](crate::DeviceHandle::bos_descriptor).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BosDescriptor {
+    total_length: u16,
+    capabilities: Vec<DeviceCapability>,
+}
+
+impl BosDescriptor {
+    /// Parses a BOS descriptor from its raw bytes, as read from the device.
+    ///
+    /// The buffer must hold the header and all the device capabilities that
+    /// it declares. Each capability that can't be decoded is kept as an
+    /// [`DeviceCapability::Unknown`].
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < BOS_HEADER_SIZE
+            || usize::from(buf[0]) < BOS_HEADER_SIZE
+            || buf[1] != LIBUSB_DT_BOS
+        {
+            return Err(Error::BadDescriptor);
+        }
+
+        let total_length = u16::from_le_bytes([buf[2], buf[3]]);
+        let num_caps = buf[4];
+
+        let end = usize::from(total_length).min(buf.len());
+        let mut rest = &buf[usize::from(buf[0]).min(end)..end];
+        let mut capabilities = Vec::with_capacity(usize::from(num_caps));
+
+        for _ in 0..num_caps {
+            let len = match rest.first() {
+                Some(&len) if usize::from(len) >= DEV_CAP_HEADER_SIZE => usize::from(len),
+                _ => return Err(Error::BadDescriptor),
+            };
+            if rest.len() < len || rest[1] != LIBUSB_DT_DEVICE_CAPABILITY {
+                return Err(Error::BadDescriptor);
+            }
+            capabilities.push(DeviceCapability::parse(&rest[..len]));
+            rest = &rest[len..];
+        }
+
+        Ok(Self {
+            total_length,
+            capabilities,
+        })
+    }
+
+    /// Creates an owned BOS descriptor from the one allocated by `libusb`.
+    ///
+    /// # Safety
+    ///
+    /// `bos` must point to a valid descriptor from
+    /// `libusb_get_bos_descriptor()`. It is not freed.
+    pub(crate) unsafe fn from_libusb(bos: *const ffi::libusb_bos_descriptor) -> Self {
+        let bos = &*(bos as *const LibusbBosDescriptor);
+        let caps =
+            slice::from_raw_parts(bos.dev_capability.as_ptr(), usize::from(bos.bNumDeviceCaps));
+
+        let capabilities = caps
+            .iter()
+            .filter(|cap| !cap.is_null())
+            .map(|&cap| {
+                // libusb copies the whole capability descriptor, including
+                // the header, into the allocation.
+                let len = usize::from((*cap).bLength);
+                DeviceCapability::parse(slice::from_raw_parts(cap as *const u8, len))
+            })
+            .collect();
+
+        Self {
+            total_length: bos.wTotalLength,
+            capabilities,
+        }
+    }
+
+    /// Gets the total length of the descriptor, including all the device
+    /// capabilities.
+    pub fn total_length(&self) -> u16 {
+        self.total_length
+    }
+
+    /// Gets the number of device capabilities.
+    pub fn num_capabilities(&self) -> usize {
+        self.capabilities.len()
+    }
+
+    /// Gets the device capabilities.
+    pub fn capabilities(&self) -> &[DeviceCapability] {
+        &self.capabilities
+    }
+
+    /// Gets the USB 2.0 Extension capability, if present.
+    pub fn usb2_extension(&self) -> Option<&Usb2ExtensionCapability> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            DeviceCapability::Usb2Extension(cap) => Some(cap),
+            _ => None,
+        })
+    }
+
+    /// Gets the SuperSpeed USB capability, if present.
+    pub fn superspeed(&self) -> Option<&SuperSpeedCapability> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            DeviceCapability::SuperSpeed(cap) => Some(cap),
+            _ => None,
+        })
+    }
+
+    /// Gets the SuperSpeedPlus USB capability, if present.
+    pub fn superspeed_plus(&self) -> Option<&SuperSpeedPlusCapability> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            DeviceCapability::SuperSpeedPlus(cap) => Some(cap),
+            _ => None,
+        })
+    }
+
+    /// Gets the Container ID of the device, if present.
+    pub fn container_id(&self) -> Option<Guid> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            DeviceCapability::ContainerId(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Gets an iterator over the Platform capabilities.
+    pub fn platform_capabilities(&self) -> impl Iterator<Item = &PlatformCapability> {
+        self.capabilities.iter().filter_map(|cap| match cap {
+            DeviceCapability::Platform(cap) => Some(cap),
+            _ => None,
+        })
+    }
+
+    /// Gets the information about the MS OS 2.0 descriptor sets, from the
+    /// MS OS 2.0 platform capability, if the device has one.
+    pub fn ms_os_20_descriptor_sets(&self) -> Vec<MsOs20DescriptorSetInfo> {
+        self.platform_capabilities()
+            .filter_map(MsOs20DescriptorSetInfo::from_capability)
+            .flatten()
+            .collect()
+    }
+
+    /// Gets the WebUSB platform capability, if the device has one.
+    pub fn webusb(&self) -> Option<WebUsbCapability> {
+        self.platform_capabilities()
+            .find_map(WebUsbCapability::from_capability)
+    }
+}
+
+/// The layout of `struct libusb_bos_descriptor`, including the flexible
+/// array of capabilities that is missing from the `ffi` definition.
+#[allow(non_snake_case)]
+#[repr(C)]
+struct LibusbBosDescriptor {
+    bLength: u8,
+    bDescriptorType: u8,
+    wTotalLength: u16,
+    bNumDeviceCaps: u8,
+    dev_capability: [*const ffi::libusb_bos_dev_capability_descriptor; 0],
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A device capability from the BOS descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum DeviceCapability {
+    /// USB 2.0 Extension, with the Link Power Management (LPM) support.
+    Usb2Extension(Usb2ExtensionCapability),
+    /// SuperSpeed USB.
+    SuperSpeed(SuperSpeedCapability),
+    /// SuperSpeedPlus USB.
+    SuperSpeedPlus(SuperSpeedPlusCapability),
+    /// A Container ID that uniquely identifies the physical device across
+    /// all the buses that it is connected to.
+    ContainerId(Guid),
+    /// A platform or operating system specific capability.
+    Platform(PlatformCapability),
+    /// The Billboard capability of a USB Type-C device.
+    Billboard(BillboardCapability),
+    /// A capability that isn't decoded, or that is malformed.
+    Unknown(RawCapability),
+}
+
+impl DeviceCapability {
+    /// Parses a device capability from its raw descriptor bytes, including
+    /// the header.
+    ///
+    /// Any capability that is unknown, or too short to decode, is returned
+    /// as `Unknown`, with the raw bytes.
+    pub fn parse(buf: &[u8]) -> Self {
+        let cap_type = buf.get(2).copied().unwrap_or(0);
+
+        let cap = match cap_type {
+            USB_2_0_EXTENSION_CAPABILITY => {
+                Usb2ExtensionCapability::parse(buf).map(DeviceCapability::Usb2Extension)
+            }
+            SUPERSPEED_USB_CAPABILITY => {
+                SuperSpeedCapability::parse(buf).map(DeviceCapability::SuperSpeed)
+            }
+            SUPERSPEED_PLUS_CAPABILITY => {
+                SuperSpeedPlusCapability::parse(buf).map(DeviceCapability::SuperSpeedPlus)
+            }
+            CONTAINER_ID_CAPABILITY if buf.len() >= 20 => {
+                Guid::from_slice(&buf[4..]).map(DeviceCapability::ContainerId)
+            }
+            PLATFORM_CAPABILITY => PlatformCapability::parse(buf).map(DeviceCapability::Platform),
+            BILLBOARD_CAPABILITY => {
+                BillboardCapability::parse(buf).map(DeviceCapability::Billboard)
+            }
+            _ => None,
+        };
+
+        cap.unwrap_or_else(|| {
+            DeviceCapability::Unknown(RawCapability {
+                capability_type: cap_type,
+                data: buf.get(DEV_CAP_HEADER_SIZE..).unwrap_or(&[]).to_vec(),
+            })
+        })
+    }
+
+    /// Gets the `bDevCapabilityType` code of the capability.
+    pub fn capability_type(&self) -> u8 {
+        use DeviceCapability::*;
+        match self {
+            Usb2Extension(_) => USB_2_0_EXTENSION_CAPABILITY,
+            SuperSpeed(_) => SUPERSPEED_USB_CAPABILITY,
+            SuperSpeedPlus(_) => SUPERSPEED_PLUS_CAPABILITY,
+            ContainerId(_) => CONTAINER_ID_CAPABILITY,
+            Platform(_) => PLATFORM_CAPABILITY,
+            Billboard(_) => BILLBOARD_CAPABILITY,
+            Unknown(raw) => raw.capability_type,
+        }
+    }
+
+    /// Encodes the capability as a raw device capability descriptor,
+    /// including the header.
+    ///
+    /// This is the inverse of [`DeviceCapability::parse`].
+    ///
+    /// Returns `Error::Overflow` if the capability is too long for the
+    /// 8-bit `bLength`, which also limits the number of Billboard modes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let buf = self.encode();
+        match buf.len() {
+            0..=255 => Ok(buf),
+            _ => Err(Error::Overflow),
+        }
+    }
+
+    /// Encodes the capability without checking its length.
+    ///
+    /// If it's too long, the `bLength` is not valid, so the caller must
+    /// check the length of the buffer.
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        use DeviceCapability::*;
+
+        let mut buf = vec![0, LIBUSB_DT_DEVICE_CAPABILITY, self.capability_type()];
+
+        match self {
+            Usb2Extension(cap) => buf.extend_from_slice(&cap.attributes.to_le_bytes()),
+            SuperSpeed(cap) => {
+                buf.push(cap.attributes);
+                buf.extend_from_slice(&cap.speeds_supported.to_le_bytes());
+                buf.push(cap.functionality_support);
+                buf.push(cap.u1_exit_latency);
+                buf.extend_from_slice(&cap.u2_exit_latency.to_le_bytes());
+            }
+            SuperSpeedPlus(cap) => {
+                buf.push(0);
+                buf.extend_from_slice(&cap.attributes.to_le_bytes());
+                buf.extend_from_slice(&cap.functionality_support.to_le_bytes());
+                buf.extend_from_slice(&[0, 0]);
+                for speed in &cap.sublink_speeds {
+                    buf.extend_from_slice(&speed.0.to_le_bytes());
+                }
+            }
+            ContainerId(guid) => {
+                buf.push(0);
+                buf.extend_from_slice(guid.as_bytes());
+            }
+            Platform(cap) => {
+                buf.push(0);
+                buf.extend_from_slice(cap.uuid.as_bytes());
+                buf.extend_from_slice(&cap.data);
+            }
+            Billboard(cap) => {
+                let mut configured = [0u8; 32];
+                for (i, mode) in cap.modes.iter().enumerate().take(128) {
+                    configured[i / 4] |= (mode.state as u8) << (2 * (i % 4));
+                }
+                buf.push(cap.additional_info_url);
+                buf.push(cap.modes.len() as u8);
+                buf.push(cap.preferred_mode);
+                buf.extend_from_slice(&cap.vconn_power.to_le_bytes());
+                buf.extend_from_slice(&configured);
+                buf.extend_from_slice(&cap.version.to_le_bytes());
+                buf.push(cap.additional_failure_info);
+                buf.push(0);
+                for mode in &cap.modes {
+                    buf.extend_from_slice(&mode.svid.to_le_bytes());
+                    buf.push(mode.mode);
+                    buf.push(mode.string_index);
+                }
+            }
+            Unknown(raw) => buf.extend_from_slice(&raw.data),
+        }
+
+        buf[0] = buf.len() as u8;
+        buf
+    }
+}
+
+/// A device capability that isn't decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RawCapability {
+    capability_type: u8,
+    data: Vec<u8>,
+}
+
+impl RawCapability {
+    /// Gets the `bDevCapabilityType` code of the capability.
+    pub fn capability_type(&self) -> u8 {
+        self.capability_type
+    }
+
+    /// Gets the capability-dependent data that follows the header.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// The USB 2.0 Extension device capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Usb2ExtensionCapability {
+    attributes: u32,
+}
+
+impl Usb2ExtensionCapability {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let attributes = u32::from_le_bytes(buf.get(3..7)?.try_into().ok()?);
+        Some(Self { attributes })
+    }
+
+    /// Gets the raw `bmAttributes` bitmap.
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    /// Determines if the device supports Link Power Management (LPM).
+    pub fn lpm_supported(&self) -> bool {
+        self.attributes & 0x02 != 0
+    }
+
+    /// Determines if the device supports the BESL and alternate HIRD
+    /// definitions of LPM.
+    pub fn besl_supported(&self) -> bool {
+        self.attributes & 0x04 != 0
+    }
+
+    /// Gets the recommended baseline BESL value, if valid.
+    pub fn baseline_besl(&self) -> Option<u8> {
+        (self.attributes & 0x08 != 0).then(|| ((self.attributes >> 8) & 0x0F) as u8)
+    }
+
+    /// Gets the recommended deep BESL value, if valid.
+    pub fn deep_besl(&self) -> Option<u8> {
+        (self.attributes & 0x10 != 0).then(|| ((self.attributes >> 12) & 0x0F) as u8)
+    }
+}
+
+/// The SuperSpeed USB device capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SuperSpeedCapability {
+    attributes: u8,
+    speeds_supported: u16,
+    functionality_support: u8,
+    u1_exit_latency: u8,
+    u2_exit_latency: u16,
+}
+
+impl SuperSpeedCapability {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = buf.get(..10)?;
+        Some(Self {
+            attributes: buf[3],
+            speeds_supported: u16::from_le_bytes([buf[4], buf[5]]),
+            functionality_support: buf[6],
+            u1_exit_latency: buf[7],
+            u2_exit_latency: u16::from_le_bytes([buf[8], buf[9]]),
+        })
+    }
+
+    /// Gets the raw `bmAttributes` bitmap.
+    pub fn attributes(&self) -> u8 {
+        self.attributes
+    }
+
+    /// Determines if the device can generate Latency Tolerance Messages.
+    pub fn ltm_capable(&self) -> bool {
+        self.attributes & 0x02 != 0
+    }
+
+    /// Gets the raw `wSpeedsSupported` bitmap.
+    pub fn speeds_supported(&self) -> u16 {
+        self.speeds_supported
+    }
+
+    /// Gets the speeds that the device supports.
+    pub fn supported_speeds(&self) -> Vec<Speed> {
+        [Speed::Low, Speed::Full, Speed::High, Speed::Super]
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.speeds_supported & (1 << i) != 0)
+            .map(|(_, speed)| *speed)
+            .collect()
+    }
+
+    /// Gets the lowest speed at which all the functionality of the device
+    /// is available.
+    pub fn lowest_functional_speed(&self) -> Speed {
+        match self.functionality_support {
+            0 => Speed::Low,
+            1 => Speed::Full,
+            2 => Speed::High,
+            3 => Speed::Super,
+            _ => Speed::Unknown,
+        }
+    }
+
+    /// Gets the worst-case latency to exit from the U1 link state.
+    pub fn u1_exit_latency(&self) -> Duration {
+        Duration::from_micros(u64::from(self.u1_exit_latency))
+    }
+
+    /// Gets the worst-case latency to exit from the U2 link state.
+    pub fn u2_exit_latency(&self) -> Duration {
+        Duration::from_micros(u64::from(self.u2_exit_latency))
+    }
+}
+
+/// The SuperSpeedPlus USB device capability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SuperSpeedPlusCapability {
+    attributes: u32,
+    functionality_support: u16,
+    sublink_speeds: Vec<SublinkSpeed>,
+}
+
+impl SuperSpeedPlusCapability {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let attributes = u32::from_le_bytes(buf.get(4..8)?.try_into().ok()?);
+        let functionality_support = u16::from_le_bytes(buf.get(8..10)?.try_into().ok()?);
+
+        let count = (attributes & 0x1F) as usize + 1;
+        let sublink_speeds = buf
+            .get(12..12 + 4 * count)?
+            .chunks_exact(4)
+            .map(|attr| SublinkSpeed(u32::from_le_bytes([attr[0], attr[1], attr[2], attr[3]])))
+            .collect();
+
+        Some(Self {
+            attributes,
+            functionality_support,
+            sublink_speeds,
+        })
+    }
+
+    /// Gets the raw `bmAttributes` bitmap.
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    /// Gets the number of unique sublink speed IDs.
+    pub fn num_speed_ids(&self) -> u8 {
+        ((self.attributes >> 5) & 0x0F) as u8 + 1
+    }
+
+    /// Gets the sublink speed ID of the minimum speed at which the device
+    /// is fully functional.
+    pub fn min_functional_speed_id(&self) -> u8 {
+        (self.functionality_support & 0x0F) as u8
+    }
+
+    /// Gets the minimum receive lane count for full functionality.
+    pub fn min_rx_lanes(&self) -> u8 {
+        ((self.functionality_support >> 8) & 0x0F) as u8
+    }
+
+    /// Gets the minimum transmit lane count for full functionality.
+    pub fn min_tx_lanes(&self) -> u8 {
+        ((self.functionality_support >> 12) & 0x0F) as u8
+    }
+
+    /// Gets the sublink speed attributes.
+    pub fn sublink_speeds(&self) -> &[SublinkSpeed] {
+        &self.sublink_speeds
+    }
+}
+
+/// A sublink speed attribute of the SuperSpeedPlus capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SublinkSpeed(u32);
+
+impl SublinkSpeed {
+    /// Gets the raw attribute value.
+    pub fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// Gets the sublink speed attribute ID.
+    pub fn id(&self) -> u8 {
+        (self.0 & 0x0F) as u8
+    }
+
+    /// Determines if the sublink is symmetric, with the same speed in both
+    /// directions.
+    pub fn is_symmetric(&self) -> bool {
+        self.0 & 0x40 == 0
+    }
+
+    /// Determines if this attribute is for the transmit direction of an
+    /// asymmetric sublink. Otherwise it is for receive.
+    pub fn is_transmit(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// Gets the link protocol; 0 for SuperSpeed and 1 for SuperSpeedPlus.
+    pub fn protocol(&self) -> u8 {
+        ((self.0 >> 14) & 0x03) as u8
+    }
+
+    /// Gets the speed of the sublink in bits per second.
+    pub fn bits_per_second(&self) -> u64 {
+        let mantissa = u64::from(self.0 >> 16);
+        let exponent = (self.0 >> 4) & 0x03;
+        mantissa * 1000u64.pow(exponent)
+    }
+}
+
+/// A Platform device capability.
+///
+/// The format of the data depends on the platform, which is identified by
+/// the UUID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlatformCapability {
+    uuid: Guid,
+    data: Vec<u8>,
+}
+
+impl PlatformCapability {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        Some(Self {
+            uuid: Guid::from_slice(buf.get(4..)?)?,
+            data: buf.get(20..)?.to_vec(),
+        })
+    }
+
+    /// Gets the UUID that identifies the platform.
+    pub fn uuid(&self) -> Guid {
+        self.uuid
+    }
+
+    /// Gets the platform-specific capability data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// The Billboard device capability of a USB Type-C device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BillboardCapability {
+    additional_info_url: u8,
+    preferred_mode: u8,
+    vconn_power: u16,
+    version: u16,
+    additional_failure_info: u8,
+    modes: Vec<AlternateMode>,
+}
+
+impl BillboardCapability {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let hdr = buf.get(..44)?;
+        let num_modes = usize::from(hdr[4]);
+        let configured = &hdr[8..40];
+
+        let modes = buf
+            .get(44..44 + 4 * num_modes)?
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, mode)| AlternateMode {
+                svid: u16::from_le_bytes([mode[0], mode[1]]),
+                mode: mode[2],
+                string_index: mode[3],
+                state: AlternateModeState::from((configured[i / 4] >> (2 * (i % 4))) & 0x03),
+            })
+            .collect();
+
+        Some(Self {
+            additional_info_url: hdr[3],
+            preferred_mode: hdr[5],
+            vconn_power: u16::from_le_bytes([hdr[6], hdr[7]]),
+            version: u16::from_le_bytes([hdr[40], hdr[41]]),
+            additional_failure_info: hdr[42],
+            modes,
+        })
+    }
+
+    /// Gets the index of the string descriptor with a URL for more
+    /// information about the product, if any.
+    pub fn additional_info_url_index(&self) -> Option<u8> {
+        match self.additional_info_url {
+            0 => None,
+            n => Some(n),
+        }
+    }
+
+    /// Gets the index of the preferred alternate mode in the list of modes.
+    pub fn preferred_mode(&self) -> u8 {
+        self.preferred_mode
+    }
+
+    /// Gets the raw `VCONNPower` field.
+    ///
+    /// Bits 2:0 are the power needed by the adapter for full functionality,
+    /// and bit 15 set means that VCONN power is not required.
+    pub fn vconn_power(&self) -> u16 {
+        self.vconn_power
+    }
+
+    /// Gets the version of the Billboard specification.
+    pub fn version(&self) -> Version {
+        Version::from_bcd(self.version)
+    }
+
+    /// Gets the raw `bAdditionalFailureInfo` bitmap.
+    pub fn additional_failure_info(&self) -> u8 {
+        self.additional_failure_info
+    }
+
+    /// Gets the alternate modes of the device.
+    pub fn modes(&self) -> &[AlternateMode] {
+        &self.modes
+    }
+}
+
+/// A USB Type-C alternate mode from the Billboard capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlternateMode {
+    svid: u16,
+    mode: u8,
+    string_index: u8,
+    state: AlternateModeState,
+}
+
+impl AlternateMode {
+    /// Gets the Standard or Vendor ID of the mode.
+    pub fn svid(&self) -> u16 {
+        self.svid
+    }
+
+    /// Gets the index of the mode within the SVID.
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    /// Gets the index of the string descriptor that describes the mode,
+    /// if any.
+    pub fn string_index(&self) -> Option<u8> {
+        match self.string_index {
+            0 => None,
+            n => Some(n),
+        }
+    }
+
+    /// Gets the configuration state of the mode.
+    pub fn state(&self) -> AlternateModeState {
+        self.state
+    }
+}
+
+/// The configuration state of an alternate mode, from the `bmConfigured`
+/// field of the Billboard capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AlternateModeState {
+    /// Unspecified error.
+    Error,
+    /// The mode was not attempted, or was exited.
+    NotAttempted,
+    /// The mode was attempted, but was unsuccessful.
+    Unsuccessful,
+    /// The mode was configured successfully.
+    Configured,
+}
+
+impl From<u8> for AlternateModeState {
+    fn from(state: u8) -> Self {
+        use AlternateModeState::*;
+        match state & 0x03 {
+            0 => Error,
+            1 => NotAttempted,
+            2 => Unsuccessful,
+            _ => Configured,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A BOS descriptor with USB 2.0 Extension, SuperSpeed, and Container ID
+    // capabilities, as from a typical USB 3 flash drive.
+    const BOS: [u8; 42] = [
+        0x05, 0x0F, 0x2A, 0x00, 0x03, // BOS
+        0x07, 0x10, 0x02, 0x1E, 0xF4, 0x00, 0x00, // USB 2.0 Ext
+        0x0A, 0x10, 0x03, 0x00, 0x0E, 0x00, 0x01, 0x0A, 0xFF, 0x07, // SuperSpeed
+        0x14, 0x10, 0x04, 0x00, 0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x01, 0x02, 0x03,
+        0x04, 0x05, 0x06, 0x07, 0x08, // Container ID
+    ];
+
+    #[test]
+    fn guid_display() {
+        let guid = Guid::from_fields(
+            0xD8DD60DF,
+            0x4589,
+            0x4CC7,
+            [0x9C, 0xD2, 0x65, 0x9D, 0x9E, 0x64, 0x8A, 0x9F],
+        );
+        assert_eq!(0xDF, guid.as_bytes()[0]);
+        assert_eq!("D8DD60DF-4589-4CC7-9CD2-659D9E648A9F", guid.to_string());
+    }
+
+    #[test]
+    fn capabilities_round_trip() {
+        let bos = BosDescriptor::parse(&BOS).unwrap();
+        let bytes: Vec<u8> = bos
+            .capabilities()
+            .iter()
+            .flat_map(|cap| cap.to_bytes().unwrap())
+            .collect();
+        assert_eq!(&BOS[5..], &bytes[..]);
+    }
+
+    #[test]
+    fn capability_too_long() {
+        let mut buf = vec![0u8; 20];
+        buf[0] = 20;
+        buf[1] = LIBUSB_DT_DEVICE_CAPABILITY;
+        buf[2] = PLATFORM_CAPABILITY;
+
+        let cap = match DeviceCapability::parse(&buf) {
+            DeviceCapability::Platform(mut cap) => {
+                cap.data = vec![0; 235];
+                DeviceCapability::Platform(cap)
+            }
+            cap => panic!("Unexpected capability: {:?}", cap),
+        };
+        assert_eq!(255, cap.to_bytes().unwrap().len());
+
+        let cap = match cap {
+            DeviceCapability::Platform(mut cap) => {
+                cap.data.push(0);
+                DeviceCapability::Platform(cap)
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(Err(Error::Overflow), cap.to_bytes());
+    }
+
+    #[test]
+    fn parse_bos() {
+        let bos = BosDescriptor::parse(&BOS).unwrap();
+        assert_eq!(42, bos.total_length());
+        assert_eq!(3, bos.num_capabilities());
+
+        let ext = bos.usb2_extension().unwrap();
+        assert!(ext.lpm_supported());
+        assert!(ext.besl_supported());
+        assert_eq!(Some(4), ext.baseline_besl());
+        assert_eq!(Some(15), ext.deep_besl());
+
+        let ss = bos.superspeed().unwrap();
+        assert!(!ss.ltm_capable());
+        assert_eq!(
+            vec![Speed::Full, Speed::High, Speed::Super],
+            ss.supported_speeds()
+        );
+        assert_eq!(Speed::Full, ss.lowest_functional_speed());
+        assert_eq!(Duration::from_micros(10), ss.u1_exit_latency());
+        assert_eq!(Duration::from_micros(0x07FF), ss.u2_exit_latency());
+
+        assert_eq!(
+            "12345678-1234-5678-0102-030405060708",
+            bos.container_id().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn parse_bad_bos() {
+        assert_eq!(Err(Error::BadDescriptor), BosDescriptor::parse(&BOS[..3]));
+        assert_eq!(Err(Error::BadDescriptor), BosDescriptor::parse(&BOS[..20]));
+
+        let mut bad = BOS;
+        bad[1] = LIBUSB_DT_DEVICE;
+        assert_eq!(Err(Error::BadDescriptor), BosDescriptor::parse(&bad));
+    }
+
+    #[test]
+    fn parse_superspeed_plus() {
+        let buf = [
+            0x14, 0x10, 0x0A, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x11, 0x00, 0x00, 0x30, 0x40,
+            0x0A, 0x00, 0xB0, 0x40, 0x0A, 0x00,
+        ];
+        let cap = match DeviceCapability::parse(&buf) {
+            DeviceCapability::SuperSpeedPlus(cap) => cap,
+            cap => panic!("Unexpected capability: {:?}", cap),
+        };
+        assert_eq!(2, cap.num_speed_ids());
+        assert_eq!(0, cap.min_functional_speed_id());
+        assert_eq!(1, cap.min_rx_lanes());
+        assert_eq!(1, cap.min_tx_lanes());
+
+        let speeds = cap.sublink_speeds();
+        assert_eq!(2, speeds.len());
+        assert!(speeds[0].is_symmetric());
+        assert!(!speeds[0].is_transmit());
+        assert!(speeds[1].is_transmit());
+        assert_eq!(1, speeds[0].protocol());
+        assert_eq!(10_000_000_000, speeds[0].bits_per_second());
+    }
+
+    #[test]
+    fn parse_platform_and_unknown() {
+        let mut buf = vec![0x18, 0x10, 0x05, 0x00];
+        buf.extend_from_slice(Guid::from_fields(1, 2, 3, [4; 8]).as_bytes());
+        buf.extend_from_slice(&[0xAA, 0xBB, 0xCC, 0xDD]);
+
+        match DeviceCapability::parse(&buf) {
+            DeviceCapability::Platform(cap) => {
+                assert_eq!(
+                    "00000001-0002-0003-0404-040404040404",
+                    cap.uuid().to_string()
+                );
+                assert_eq!(&[0xAA, 0xBB, 0xCC, 0xDD], cap.data());
+            }
+            cap => panic!("Unexpected capability: {:?}", cap),
+        }
+
+        let cap = DeviceCapability::parse(&[0x05, 0x10, 0x06, 0x01, 0x02]);
+        assert_eq!(0x06, cap.capability_type());
+        match cap {
+            DeviceCapability::Unknown(raw) => assert_eq!(&[0x01, 0x02], raw.data()),
+            cap => panic!("Unexpected capability: {:?}", cap),
+        }
+
+        // Too short to decode
+        let cap = DeviceCapability::parse(&[0x04, 0x10, 0x03, 0x00]);
+        assert!(matches!(cap, DeviceCapability::Unknown(_)));
+        assert_eq!(SUPERSPEED_USB_CAPABILITY, cap.capability_type());
+    }
+
+    #[test]
+    fn parse_billboard() {
+        let mut buf = vec![0u8; 48];
+        buf[0] = 48;
+        buf[1] = LIBUSB_DT_DEVICE_CAPABILITY;
+        buf[2] = BILLBOARD_CAPABILITY;
+        buf[3] = 1; // iAdditionalInfoURL
+        buf[4] = 1; // bNumberOfAlternateOrUSB4Modes
+        buf[8] = 0x03; // bmConfigured
+        buf[40] = 0x10;
+        buf[41] = 0x01; // bcdVersion 1.10
+        buf[44..48].copy_from_slice(&[0x01, 0xFF, 0x01, 0x02]);
+
+        match DeviceCapability::parse(&buf) {
+            DeviceCapability::Billboard(cap) => {
+                assert_eq!(Some(1), cap.additional_info_url_index());
+                assert_eq!(Version(1, 1, 0), cap.version());
+                let mode = cap.modes()[0];
+                assert_eq!(0xFF01, mode.svid());
+                assert_eq!(1, mode.mode());
+                assert_eq!(Some(2), mode.string_index());
+                assert_eq!(AlternateModeState::Configured, mode.state());
+            }
+            cap => panic!("Unexpected capability: {:?}", cap),
+        }
+    }
+}
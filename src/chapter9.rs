@@ -0,0 +1,737 @@
+// yusb/src/chapter9.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Live conformance tests of a device's handling of the standard requests,
+//! from Chapter 9 of the USB specification.
+//!
+//! Unlike the static checks in the [`compliance`](crate::compliance)
+//! module, these send requests to the device and check its responses:
+//!
+//! - `GET_DESCRIPTOR` for the device, configuration, string, and device
+//!   qualifier descriptors, with short, exact, and oversized `wLength`.
+//! - `GET_STATUS` on the device, and on every interface and endpoint.
+//! - `SET_FEATURE` and `CLEAR_FEATURE` of `ENDPOINT_HALT` on every
+//!   non-isochronous endpoint.
+//! - `GET_INTERFACE` and `SET_INTERFACE` on every alternate setting.
+//! - `GET_CONFIGURATION` and `SET_CONFIGURATION`, cycling through all the
+//!   configurations and the unconfigured state.
+//!
+//! The tests change the state of the device, so they should only be run
+//! against a device that isn't otherwise in use. On Linux, they can be run
+//! in CI against a software gadget, like `g_zero` on the `dummy_hcd`
+//! virtual host controller. See the `chapter9` example.
+
+use crate::{
+    fields::{request_type, Direction, Recipient, RequestType, TransferType},
+    ConfigDescriptor, DeviceDescriptor, DeviceHandle, DeviceResult, Error, Speed, Timeout,
+};
+use libusb1_sys::constants::*;
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The feature selector of `ENDPOINT_HALT`.
+const ENDPOINT_HALT: u16 = 0;
+
+/// The outcome of a single test.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Outcome {
+    /// The device responded correctly.
+    Passed,
+    /// The device responded incorrectly, for the given reason.
+    Failed(String),
+    /// The test couldn't be run, for the given reason.
+    Skipped(String),
+}
+
+impl Outcome {
+    fn from_result(res: Result<(), String>) -> Self {
+        match res {
+            Ok(()) => Outcome::Passed,
+            Err(reason) => Outcome::Failed(reason),
+        }
+    }
+}
+
+/// The result of a single test.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TestResult {
+    name: String,
+    outcome: Outcome,
+}
+
+impl TestResult {
+    /// Gets the name of the test, which describes the request.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the outcome of the test.
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+
+    /// Determines if the test passed.
+    pub fn passed(&self) -> bool {
+        self.outcome == Outcome::Passed
+    }
+
+    /// Determines if the test failed.
+    pub fn failed(&self) -> bool {
+        matches!(self.outcome, Outcome::Failed(_))
+    }
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Passed => write!(f, "PASS {}", self.name),
+            Outcome::Failed(reason) => write!(f, "FAIL {}: {}", self.name, reason),
+            Outcome::Skipped(reason) => write!(f, "SKIP {}: {}", self.name, reason),
+        }
+    }
+}
+
+/// The pass/fail report of a conformance run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Report {
+    results: Vec<TestResult>,
+}
+
+impl Report {
+    /// Gets the results of the tests, in the order they were run.
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+
+    /// Gets the number of tests that passed.
+    pub fn num_passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    /// Gets the number of tests that failed.
+    pub fn num_failed(&self) -> usize {
+        self.results.iter().filter(|r| r.failed()).count()
+    }
+
+    /// Gets the number of tests that were skipped.
+    pub fn num_skipped(&self) -> usize {
+        self.results.len() - self.num_passed() - self.num_failed()
+    }
+
+    /// Determines if the run passed, meaning that no test failed.
+    pub fn is_pass(&self) -> bool {
+        self.num_failed() == 0
+    }
+
+    /// Iterates over the tests that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|r| r.failed())
+    }
+
+    fn add<S: Into<String>>(&mut self, name: S, outcome: Outcome) {
+        self.results.push(TestResult {
+            name: name.into(),
+            outcome,
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    /// Writes a line for each test, followed by a summary.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for res in &self.results {
+            writeln!(f, "{}", res)?;
+        }
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped",
+            self.num_passed(),
+            self.num_failed(),
+            self.num_skipped()
+        )
+    }
+}
+
+/// Runs the Chapter 9 conformance tests on an open device.
+///
+/// The groups of tests that change the state of the device can be turned
+/// off individually.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chapter9Runner {
+    timeout: Timeout,
+    endpoint_halt: bool,
+    alt_settings: bool,
+    configurations: bool,
+}
+
+impl Default for Chapter9Runner {
+    fn default() -> Self {
+        Self {
+            timeout: Timeout::After(Duration::from_secs(1)),
+            endpoint_halt: true,
+            alt_settings: true,
+            configurations: true,
+        }
+    }
+}
+
+impl Chapter9Runner {
+    /// Creates a runner for all of the tests, with a timeout of one second
+    /// for each request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the timeout for each request.
+    ///
+    /// Like the timeouts of the transfers, a zero duration waits forever.
+    pub fn timeout(&mut self, timeout: impl Into<Timeout>) -> &mut Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Sets whether to halt and clear each endpoint.
+    pub fn endpoint_halt(&mut self, on: bool) -> &mut Self {
+        self.endpoint_halt = on;
+        self
+    }
+
+    /// Sets whether to select every alternate setting of each interface.
+    pub fn alt_settings(&mut self, on: bool) -> &mut Self {
+        self.alt_settings = on;
+        self
+    }
+
+    /// Sets whether to cycle through the configurations.
+    pub fn configurations(&mut self, on: bool) -> &mut Self {
+        self.configurations = on;
+        self
+    }
+
+    /// Runs the tests on the device.
+    ///
+    /// The interfaces of the active configuration are claimed for the
+    /// interface and endpoint tests, detaching any kernel drivers, and
+    /// released again afterward, leaving the handle's own setting for
+    /// detaching them as it was. The original configuration is restored
+    /// at the end.
+    pub fn run(&self, handle: &mut DeviceHandle) -> Report {
+        let mut session = Session {
+            handle,
+            timeout: self.timeout,
+            report: Report::default(),
+        };
+
+        let device = session.handle.device();
+        let speed = device.speed();
+        let desc = match device.device_descriptor() {
+            Ok(desc) => desc,
+            Err(err) => {
+                session.skip("device descriptor", err.to_string());
+                return session.report;
+            }
+        };
+        let configs: Vec<_> = (0..desc.num_configurations())
+            .filter_map(|i| device.config_descriptor(i).ok())
+            .collect();
+
+        session.device_descriptor();
+        for i in 0..desc.num_configurations() {
+            session.config_descriptor(i);
+        }
+        session.string_descriptors(&desc);
+        if speed == Speed::High {
+            session.device_qualifier();
+        }
+        session.device_status(speed);
+
+        let orig_config = session.get_configuration();
+        if let Some(config) = orig_config.and_then(|n| configs.iter().find(|c| c.number() == n)) {
+            session.interfaces(self, config, speed);
+        }
+
+        if self.configurations {
+            if let Some(orig) = orig_config {
+                session.cycle_configurations(&configs, orig);
+            }
+        }
+        session.report
+    }
+}
+
+/// The state of a conformance run.
+struct Session<'a> {
+    handle: &'a mut DeviceHandle,
+    timeout: Timeout,
+    report: Report,
+}
+
+impl<'a> Session<'a> {
+    fn pass_fail<S: Into<String>>(&mut self, name: S, res: Result<(), String>) {
+        self.report.add(name, Outcome::from_result(res));
+    }
+
+    fn skip<S: Into<String>, R: Into<String>>(&mut self, name: S, reason: R) {
+        self.report.add(name, Outcome::Skipped(reason.into()));
+    }
+
+    /// Sends a standard IN request.
+    fn read(
+        &self,
+        recipient: Recipient,
+        request: u8,
+        value: u16,
+        index: u16,
+        len: u16,
+    ) -> DeviceResult<Vec<u8>> {
+        let mut buf = vec![0u8; usize::from(len)];
+        let n = self.handle.read_control(
+            request_type(Direction::In, RequestType::Standard, recipient),
+            request,
+            value,
+            index,
+            &mut buf,
+            self.timeout,
+        )?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    /// Reads a descriptor, checking that the response has the expected
+    /// length and descriptor type.
+    fn get_descriptor(
+        &self,
+        desc_type: u8,
+        desc_index: u8,
+        lang_id: u16,
+        len: u16,
+        expected_len: Option<usize>,
+    ) -> Result<Vec<u8>, String> {
+        let buf = self
+            .read(
+                Recipient::Device,
+                LIBUSB_REQUEST_GET_DESCRIPTOR,
+                u16::from(desc_type) << 8 | u16::from(desc_index),
+                lang_id,
+                len,
+            )
+            .map_err(|err| err.kind().to_string())?;
+
+        if let Some(expected) = expected_len {
+            if buf.len() != expected {
+                return Err(format!("got {} bytes, expected {}", buf.len(), expected));
+            }
+        }
+        if buf.len() >= 2 && buf[1] != desc_type {
+            return Err(format!(
+                "bDescriptorType is {:#04x}, expected {:#04x}",
+                buf[1], desc_type
+            ));
+        }
+        Ok(buf)
+    }
+
+    fn device_descriptor(&mut self) {
+        for len in [8, 18, 0xFF] {
+            let res = self
+                .get_descriptor(LIBUSB_DT_DEVICE, 0, 0, len, Some(usize::from(len.min(18))))
+                .and_then(|buf| match buf[0] {
+                    18 => Ok(()),
+                    n => Err(format!("bLength is {}, expected 18", n)),
+                });
+            self.pass_fail(format!("GET_DESCRIPTOR device, wLength {}", len), res);
+        }
+    }
+
+    fn config_descriptor(&mut self, index: u8) {
+        let name = |len| format!("GET_DESCRIPTOR config {}, wLength {}", index, len);
+
+        let total = match self.get_descriptor(LIBUSB_DT_CONFIG, index, 0, 9, Some(9)) {
+            Ok(buf) => {
+                self.pass_fail(name(9), Ok(()));
+                u16::from_le_bytes([buf[2], buf[3]])
+            }
+            Err(reason) => {
+                self.pass_fail(name(9), Err(reason));
+                return;
+            }
+        };
+
+        let mut lengths = vec![total, 0xFF];
+        if total > 10 {
+            lengths.push(total - 1);
+        }
+        for len in lengths {
+            let expected = usize::from(len.min(total));
+            let res = self
+                .get_descriptor(LIBUSB_DT_CONFIG, index, 0, len, Some(expected))
+                .map(|_| ());
+            self.pass_fail(name(len), res);
+        }
+    }
+
+    fn string_descriptors(&mut self, desc: &DeviceDescriptor) {
+        let indices: Vec<_> = [
+            desc.manufacturer_string_index(),
+            desc.product_string_index(),
+            desc.serial_number_string_index(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if indices.is_empty() {
+            self.skip("GET_DESCRIPTOR string 0", "the device has no strings");
+            return;
+        }
+
+        let res = self
+            .get_descriptor(LIBUSB_DT_STRING, 0, 0, 2, Some(2))
+            .and_then(|buf| match buf[0] {
+                n if n >= 4 && n % 2 == 0 => Ok(()),
+                n => Err(format!("bLength {} is not a list of languages", n)),
+            });
+        self.pass_fail("GET_DESCRIPTOR string 0, wLength 2", res);
+
+        let lang_id = match self.get_descriptor(LIBUSB_DT_STRING, 0, 0, 0xFF, None) {
+            Ok(buf) => {
+                let res = check_string(&buf);
+                let lang_id = match *buf {
+                    [_, _, lo, hi, ..] => Some(u16::from_le_bytes([lo, hi])),
+                    _ => None,
+                };
+                self.pass_fail("GET_DESCRIPTOR string 0, wLength 255", res);
+                lang_id
+            }
+            Err(reason) => {
+                self.pass_fail("GET_DESCRIPTOR string 0, wLength 255", Err(reason));
+                None
+            }
+        };
+
+        for index in indices {
+            let name = format!("GET_DESCRIPTOR string {}, wLength 255", index);
+            match lang_id {
+                Some(lang_id) => {
+                    let res = self
+                        .get_descriptor(LIBUSB_DT_STRING, index, lang_id, 0xFF, None)
+                        .and_then(|buf| check_string(&buf));
+                    self.pass_fail(name, res);
+                }
+                None => self.skip(name, "the device has no languages"),
+            }
+        }
+    }
+
+    fn device_qualifier(&mut self) {
+        let res = self.get_descriptor(0x06, 0, 0, 10, Some(10)).map(|_| ());
+        self.pass_fail("GET_DESCRIPTOR device qualifier", res);
+    }
+
+    /// Reads the status of a recipient, checking that it is two bytes.
+    fn get_status(&self, recipient: Recipient, index: u16) -> Result<u16, String> {
+        let buf = self
+            .read(recipient, LIBUSB_REQUEST_GET_STATUS, 0, index, 2)
+            .map_err(|err| err.kind().to_string())?;
+        match *buf {
+            [lo, hi] => Ok(u16::from_le_bytes([lo, hi])),
+            _ => Err(format!("got {} bytes, expected 2", buf.len())),
+        }
+    }
+
+    fn device_status(&mut self, speed: Speed) {
+        // Bits 2-4 are the U1, U2, and LTM enables of SuperSpeed devices.
+        let mask = if speed >= Speed::Super {
+            0xFFE0
+        } else {
+            0xFFFC
+        };
+        let res = self
+            .get_status(Recipient::Device, 0)
+            .and_then(|status| match status & mask {
+                0 => Ok(()),
+                _ => Err(format!("reserved bits are set: {:#06x}", status)),
+            });
+        self.pass_fail("GET_STATUS device", res);
+    }
+
+    fn get_configuration(&mut self) -> Option<u8> {
+        let res = self
+            .read(Recipient::Device, LIBUSB_REQUEST_GET_CONFIGURATION, 0, 0, 1)
+            .map_err(|err| err.kind().to_string())
+            .and_then(|buf| match *buf {
+                [value] => Ok(value),
+                _ => Err(format!("got {} bytes, expected 1", buf.len())),
+            });
+        match res {
+            Ok(value) => {
+                self.pass_fail("GET_CONFIGURATION", Ok(()));
+                Some(value)
+            }
+            Err(reason) => {
+                self.pass_fail("GET_CONFIGURATION", Err(reason));
+                None
+            }
+        }
+    }
+
+    fn get_interface(&self, iface: u8) -> Result<u8, String> {
+        let buf = self
+            .read(
+                Recipient::Interface,
+                LIBUSB_REQUEST_GET_INTERFACE,
+                0,
+                u16::from(iface),
+                1,
+            )
+            .map_err(|err| err.kind().to_string())?;
+        match *buf {
+            [setting] => Ok(setting),
+            _ => Err(format!("got {} bytes, expected 1", buf.len())),
+        }
+    }
+
+    /// Tests the interfaces of the configuration, detaching any kernel
+    /// drivers while they are claimed.
+    ///
+    /// The handle's own setting for detaching the drivers is restored
+    /// afterward.
+    fn interfaces(&mut self, runner: &Chapter9Runner, config: &ConfigDescriptor, speed: Speed) {
+        let detach = !self.handle.auto_detach_kernel_driver()
+            && self.handle.set_auto_detach_kernel_driver(true).is_ok();
+
+        self.each_interface(runner, config, speed);
+
+        if detach {
+            let _ = self.handle.set_auto_detach_kernel_driver(false);
+        }
+    }
+
+    fn each_interface(&mut self, runner: &Chapter9Runner, config: &ConfigDescriptor, speed: Speed) {
+        for iface in config.interfaces() {
+            let n = iface.number();
+            if let Err(err) = self.handle.claim_interface(n) {
+                self.skip(
+                    format!("interface {}", n),
+                    format!("can't claim the interface: {}", err.kind()),
+                );
+                continue;
+            }
+
+            // SuperSpeed functions report their remote wake state in bits 0-1
+            let mask = if speed >= Speed::Super {
+                0xFFFC
+            } else {
+                0xFFFF
+            };
+            let res = self
+                .get_status(Recipient::Interface, u16::from(n))
+                .and_then(|status| match status & mask {
+                    0 => Ok(()),
+                    _ => Err(format!("reserved bits are set: {:#06x}", status)),
+                });
+            self.pass_fail(format!("GET_STATUS interface {}", n), res);
+
+            for setting in iface.descriptors() {
+                let alt = setting.setting_number();
+                if alt != 0 && !runner.alt_settings {
+                    continue;
+                }
+
+                let res = self
+                    .handle
+                    .set_alternate_setting(n, alt)
+                    .map_err(|err| err.kind().to_string());
+                let selected = res.is_ok();
+                self.pass_fail(format!("SET_INTERFACE {}.{}", n, alt), res);
+                if !selected {
+                    continue;
+                }
+
+                let res = self.get_interface(n).and_then(|value| {
+                    if value == alt {
+                        Ok(())
+                    } else {
+                        Err(format!("got setting {}, expected {}", value, alt))
+                    }
+                });
+                self.pass_fail(format!("GET_INTERFACE {}.{}", n, alt), res);
+
+                for endpoint in setting.endpoint_descriptors() {
+                    let addr = endpoint.address();
+                    let res = self.endpoint_status(addr, false);
+                    self.pass_fail(format!("GET_STATUS endpoint {:#04x}", addr), res);
+
+                    if runner.endpoint_halt {
+                        let name =
+                            format!("SET_FEATURE/CLEAR_FEATURE halt, endpoint {:#04x}", addr);
+                        if endpoint.transfer_type() == TransferType::Isochronous {
+                            self.skip(name, "isochronous endpoints can't be halted");
+                        } else {
+                            let res = self.halt_endpoint(addr);
+                            self.pass_fail(name, res);
+                        }
+                    }
+                }
+            }
+
+            if iface.descriptors().count() > 1 && runner.alt_settings {
+                let _ = self.handle.set_alternate_setting(n, 0);
+            }
+            let _ = self.handle.release_interface(n);
+        }
+    }
+
+    /// Checks the halt status of an endpoint.
+    fn endpoint_status(&self, addr: u8, halted: bool) -> Result<(), String> {
+        let status = self.get_status(Recipient::Endpoint, u16::from(addr))?;
+        if status & 0xFFFE != 0 {
+            return Err(format!("reserved bits are set: {:#06x}", status));
+        }
+        match (status & 0x01 != 0, halted) {
+            (true, false) => Err("the endpoint is halted".into()),
+            (false, true) => Err("the endpoint is not halted".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Halts an endpoint, then clears the halt, checking its status after
+    /// each step.
+    fn halt_endpoint(&mut self, addr: u8) -> Result<(), String> {
+        self.handle
+            .write_control(
+                request_type(Direction::Out, RequestType::Standard, Recipient::Endpoint),
+                LIBUSB_REQUEST_SET_FEATURE,
+                ENDPOINT_HALT,
+                u16::from(addr),
+                &[],
+                self.timeout,
+            )
+            .map_err(|err| format!("SET_FEATURE: {}", err.kind()))?;
+        self.endpoint_status(addr, true)
+            .map_err(|reason| format!("after SET_FEATURE: {}", reason))?;
+
+        // This sends CLEAR_FEATURE, and resets the host side of the pipe.
+        self.handle
+            .clear_halt(addr)
+            .map_err(|err| format!("CLEAR_FEATURE: {}", err.kind()))?;
+        self.endpoint_status(addr, false)
+            .map_err(|reason| format!("after CLEAR_FEATURE: {}", reason))
+    }
+
+    fn cycle_configurations(&mut self, configs: &[ConfigDescriptor], orig: u8) {
+        let values = configs.iter().map(|c| c.number()).chain([0, orig]);
+
+        for value in values {
+            let name = format!("SET_CONFIGURATION {}", value);
+            let res = match value {
+                0 => self.handle.unconfigure(),
+                n => self.handle.set_active_configuration(n),
+            };
+            match res {
+                Err(err) if err.kind() == Error::Busy => {
+                    self.skip(name, "the interfaces are in use by a driver");
+                    return;
+                }
+                Err(err) => {
+                    self.pass_fail(name, Err(err.kind().to_string()));
+                    continue;
+                }
+                Ok(()) => {}
+            }
+
+            let res = self
+                .read(Recipient::Device, LIBUSB_REQUEST_GET_CONFIGURATION, 0, 0, 1)
+                .map_err(|err| err.kind().to_string())
+                .and_then(|buf| match *buf {
+                    [n] if n == value => Ok(()),
+                    [n] => Err(format!("GET_CONFIGURATION returned {}", n)),
+                    _ => Err(format!("GET_CONFIGURATION returned {} bytes", buf.len())),
+                });
+            self.pass_fail(name, res);
+        }
+    }
+}
+
+/// Checks the framing of a string descriptor.
+fn check_string(buf: &[u8]) -> Result<(), String> {
+    match buf {
+        [len, ..] if usize::from(*len) != buf.len() => Err(format!(
+            "bLength is {}, but {} bytes were returned",
+            len,
+            buf.len()
+        )),
+        [len, ..] if len % 2 != 0 || *len < 2 => Err(format!("bLength {} is not legal", len)),
+        [] => Err("the descriptor is empty".into()),
+        _ => Ok(()),
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_counts() {
+        let mut report = Report::default();
+        report.add("GET_STATUS device", Outcome::Passed);
+        report.add(
+            "GET_INTERFACE 0.1",
+            Outcome::Failed("got setting 0, expected 1".into()),
+        );
+        report.add(
+            "GET_DESCRIPTOR string 0",
+            Outcome::Skipped("no strings".into()),
+        );
+
+        assert_eq!(1, report.num_passed());
+        assert_eq!(1, report.num_failed());
+        assert_eq!(1, report.num_skipped());
+        assert!(!report.is_pass());
+        assert_eq!(
+            "GET_INTERFACE 0.1",
+            report.failures().next().unwrap().name()
+        );
+
+        assert_eq!(
+            "PASS GET_STATUS device\n\
+             FAIL GET_INTERFACE 0.1: got setting 0, expected 1\n\
+             SKIP GET_DESCRIPTOR string 0: no strings\n\
+             1 passed, 1 failed, 1 skipped",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn runner_timeout() {
+        let mut runner = Chapter9Runner::new();
+        assert_eq!(Timeout::After(Duration::from_secs(1)), runner.timeout);
+
+        runner.timeout(Timeout::Infinite);
+        assert_eq!(Timeout::Infinite, runner.timeout);
+        runner.timeout(Duration::from_millis(250));
+        assert_eq!(Timeout::After(Duration::from_millis(250)), runner.timeout);
+    }
+
+    #[test]
+    fn string_framing() {
+        assert_eq!(Ok(()), check_string(&[4, 3, 0x09, 0x04]));
+        assert!(check_string(&[6, 3, 0x09, 0x04]).is_err());
+        assert!(check_string(&[3, 3, 0x09]).is_err());
+        assert!(check_string(&[]).is_err());
+    }
+}
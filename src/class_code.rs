@@ -0,0 +1,378 @@
+// yusb/src/class_code.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! USB class codes, and the names of well-known subclass and protocol
+//! combinations.
+//!
+//! See the [USB-IF defined class codes](https://www.usb.org/defined-class-codes).
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A USB base class code, as defined by the USB-IF.
+///
+/// This is the `bDeviceClass` of a device, or the `bInterfaceClass` of an
+/// interface. Codes that aren't assigned by the USB-IF are kept as
+/// `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum ClassCode {
+    /// The class is defined by each interface (device only).
+    PerInterface,
+    /// Audio (interface only).
+    Audio,
+    /// Communications and CDC control.
+    Communications,
+    /// Human Interface Device (interface only).
+    Hid,
+    /// Physical (interface only).
+    Physical,
+    /// Still imaging (interface only).
+    Image,
+    /// Printer (interface only).
+    Printer,
+    /// Mass storage (interface only).
+    MassStorage,
+    /// Hub (device only).
+    Hub,
+    /// CDC data (interface only).
+    CdcData,
+    /// Smart card (interface only).
+    SmartCard,
+    /// Content security (interface only).
+    ContentSecurity,
+    /// Video (interface only).
+    Video,
+    /// Personal healthcare (interface only).
+    PersonalHealthcare,
+    /// Audio/Video devices (interface only).
+    AudioVideo,
+    /// Billboard (device only).
+    Billboard,
+    /// USB Type-C bridge (interface only).
+    TypeCBridge,
+    /// USB bulk display protocol (interface only).
+    BulkDisplay,
+    /// MCTP over USB.
+    Mctp,
+    /// I3C (interface only).
+    I3c,
+    /// Diagnostic device.
+    Diagnostic,
+    /// Wireless controller, such as Bluetooth (interface only).
+    WirelessController,
+    /// Miscellaneous.
+    Miscellaneous,
+    /// Application specific, such as DFU (interface only).
+    ApplicationSpecific,
+    /// Vendor specific.
+    VendorSpecific,
+    /// A code that isn't assigned by the USB-IF.
+    Unknown(u8),
+}
+
+impl ClassCode {
+    /// Gets the class from its raw code.
+    pub fn from_raw(raw: u8) -> Self {
+        use ClassCode::*;
+        match raw {
+            0x00 => PerInterface,
+            0x01 => Audio,
+            0x02 => Communications,
+            0x03 => Hid,
+            0x05 => Physical,
+            0x06 => Image,
+            0x07 => Printer,
+            0x08 => MassStorage,
+            0x09 => Hub,
+            0x0A => CdcData,
+            0x0B => SmartCard,
+            0x0D => ContentSecurity,
+            0x0E => Video,
+            0x0F => PersonalHealthcare,
+            0x10 => AudioVideo,
+            0x11 => Billboard,
+            0x12 => TypeCBridge,
+            0x13 => BulkDisplay,
+            0x14 => Mctp,
+            0x3C => I3c,
+            0xDC => Diagnostic,
+            0xE0 => WirelessController,
+            0xEF => Miscellaneous,
+            0xFE => ApplicationSpecific,
+            0xFF => VendorSpecific,
+            n => Unknown(n),
+        }
+    }
+
+    /// Gets the raw code of the class.
+    pub fn as_raw(&self) -> u8 {
+        use ClassCode::*;
+        match *self {
+            PerInterface => 0x00,
+            Audio => 0x01,
+            Communications => 0x02,
+            Hid => 0x03,
+            Physical => 0x05,
+            Image => 0x06,
+            Printer => 0x07,
+            MassStorage => 0x08,
+            Hub => 0x09,
+            CdcData => 0x0A,
+            SmartCard => 0x0B,
+            ContentSecurity => 0x0D,
+            Video => 0x0E,
+            PersonalHealthcare => 0x0F,
+            AudioVideo => 0x10,
+            Billboard => 0x11,
+            TypeCBridge => 0x12,
+            BulkDisplay => 0x13,
+            Mctp => 0x14,
+            I3c => 0x3C,
+            Diagnostic => 0xDC,
+            WirelessController => 0xE0,
+            Miscellaneous => 0xEF,
+            ApplicationSpecific => 0xFE,
+            VendorSpecific => 0xFF,
+            Unknown(n) => n,
+        }
+    }
+
+    /// Gets the name of the class.
+    pub fn name(&self) -> &'static str {
+        use ClassCode::*;
+        match *self {
+            PerInterface => "(Defined at Interface level)",
+            Audio => "Audio",
+            Communications => "Communications",
+            Hid => "Human Interface Device",
+            Physical => "Physical Interface Device",
+            Image => "Imaging",
+            Printer => "Printer",
+            MassStorage => "Mass Storage",
+            Hub => "Hub",
+            CdcData => "CDC Data",
+            SmartCard => "Chip/SmartCard",
+            ContentSecurity => "Content Security",
+            Video => "Video",
+            PersonalHealthcare => "Personal Healthcare",
+            AudioVideo => "Audio/Video",
+            Billboard => "Billboard",
+            TypeCBridge => "Type-C Bridge",
+            BulkDisplay => "Bulk Display",
+            Mctp => "MCTP",
+            I3c => "I3C",
+            Diagnostic => "Diagnostic",
+            WirelessController => "Wireless",
+            Miscellaneous => "Miscellaneous Device",
+            ApplicationSpecific => "Application Specific Interface",
+            VendorSpecific => "Vendor Specific Class",
+            Unknown(_) => "Unknown",
+        }
+    }
+}
+
+impl From<u8> for ClassCode {
+    fn from(raw: u8) -> Self {
+        Self::from_raw(raw)
+    }
+}
+
+impl From<ClassCode> for u8 {
+    fn from(class: ClassCode) -> Self {
+        class.as_raw()
+    }
+}
+
+impl fmt::Display for ClassCode {
+    /// Writes the name of the class.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClassCode::Unknown(n) => write!(f, "Unknown ({:#04x})", n),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// The class, subclass, and protocol codes of a device, interface, or
+/// function.
+///
+/// The meaning of the subclass and protocol depend on the class. For
+/// well-known combinations, [`function_name`](#method.function_name)
+/// gives a description, like "Boot Keyboard" for a HID interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClassTriple {
+    class: ClassCode,
+    sub_class: u8,
+    protocol: u8,
+}
+
+impl ClassTriple {
+    /// Creates a class triple from the raw codes.
+    pub fn new(class: u8, sub_class: u8, protocol: u8) -> Self {
+        Self {
+            class: ClassCode::from_raw(class),
+            sub_class,
+            protocol,
+        }
+    }
+
+    /// Gets the base class.
+    pub fn class(&self) -> ClassCode {
+        self.class
+    }
+
+    /// Gets the raw subclass code.
+    pub fn sub_class_code(&self) -> u8 {
+        self.sub_class
+    }
+
+    /// Gets the raw protocol code.
+    pub fn protocol_code(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Looks up the name of the class in the embedded `usb.ids` database.
+    #[cfg(feature = "usb-ids")]
+    pub fn class_name(&self) -> Option<&'static str> {
+        crate::ids::class_name(self.class.as_raw())
+    }
+
+    /// Looks up the name of the subclass in the embedded `usb.ids`
+    /// database.
+    #[cfg(feature = "usb-ids")]
+    pub fn sub_class_name(&self) -> Option<&'static str> {
+        crate::ids::sub_class_name(self.class.as_raw(), self.sub_class)
+    }
+
+    /// Looks up the name of the protocol in the embedded `usb.ids`
+    /// database.
+    #[cfg(feature = "usb-ids")]
+    pub fn protocol_name(&self) -> Option<&'static str> {
+        crate::ids::protocol_name(self.class.as_raw(), self.sub_class, self.protocol)
+    }
+
+    /// Gets a description of a well-known subclass and protocol of the
+    /// class, if there is one.
+    pub fn function_name(&self) -> Option<&'static str> {
+        use ClassCode::*;
+        let name = match (self.class, self.sub_class, self.protocol) {
+            (Audio, 0x01, _) => "Audio Control",
+            (Audio, 0x02, _) => "Audio Streaming",
+            (Audio, 0x03, _) => "MIDI Streaming",
+            (Communications, 0x02, 0x01..=0x06) => "CDC ACM (AT commands)",
+            (Communications, 0x02, 0xFF) => "CDC ACM (vendor protocol)",
+            (Communications, 0x02, _) => "CDC ACM",
+            (Communications, 0x06, _) => "CDC Ethernet (ECM)",
+            (Communications, 0x0B, _) => "CDC OBEX",
+            (Communications, 0x0C, _) => "CDC Ethernet Emulation (EEM)",
+            (Communications, 0x0D, _) => "CDC Network Control (NCM)",
+            (Communications, 0x0E, _) => "CDC Mobile Broadband (MBIM)",
+            (Hid, 0x00, 0x00) => "No Subclass",
+            (Hid, 0x01, 0x01) => "Boot Keyboard",
+            (Hid, 0x01, 0x02) => "Boot Mouse",
+            (Hid, 0x01, _) => "Boot Interface",
+            (Image, 0x01, 0x01) => "PTP (Picture Transfer Protocol)",
+            (Printer, 0x01, 0x01) => "Printer (Unidirectional)",
+            (Printer, 0x01, 0x02) => "Printer (Bidirectional)",
+            (Printer, 0x01, 0x03) => "Printer (IEEE 1284.4)",
+            (MassStorage, 0x04, 0x00) => "UFI (Control/Bulk/Interrupt)",
+            (MassStorage, 0x06, 0x50) => "SCSI (Bulk-Only)",
+            (MassStorage, 0x06, 0x62) => "SCSI (UAS)",
+            (MassStorage, 0x06, _) => "SCSI",
+            (Hub, 0x00, 0x00) => "Full Speed Hub",
+            (Hub, 0x00, 0x01) => "High Speed Hub (Single TT)",
+            (Hub, 0x00, 0x02) => "High Speed Hub (Multiple TTs)",
+            (Hub, 0x00, 0x03) => "SuperSpeed Hub",
+            (Video, 0x01, _) => "Video Control",
+            (Video, 0x02, _) => "Video Streaming",
+            (Video, 0x03, _) => "Video Interface Collection",
+            (Diagnostic, 0x01, 0x01) => "USB2 Compliance Device",
+            (WirelessController, 0x01, 0x01) => "Bluetooth",
+            (WirelessController, 0x01, 0x03) => "RNDIS",
+            (Miscellaneous, 0x02, 0x01) => "Interface Association",
+            (Miscellaneous, 0x04, 0x01) => "RNDIS over Ethernet",
+            (ApplicationSpecific, 0x01, 0x01) => "DFU (Runtime)",
+            (ApplicationSpecific, 0x01, 0x02) => "DFU (DFU Mode)",
+            (ApplicationSpecific, 0x02, 0x00) => "IrDA Bridge",
+            (ApplicationSpecific, 0x03, 0x00) => "Test and Measurement (USBTMC)",
+            (ApplicationSpecific, 0x03, 0x01) => "Test and Measurement (USB488)",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for ClassTriple {
+    /// Writes the class name, followed by the function name, if it's
+    /// well known.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.function_name() {
+            Some(name) => write!(f, "{}: {}", self.class, name),
+            None => write!(f, "{}", self.class),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn class_codes_round_trip() {
+        for n in 0..=255u8 {
+            assert_eq!(n, ClassCode::from_raw(n).as_raw());
+        }
+        assert_eq!(ClassCode::Hid, ClassCode::from(0x03));
+        assert_eq!(ClassCode::Unknown(0x04), ClassCode::from(0x04));
+        assert_eq!(0xFE, u8::from(ClassCode::ApplicationSpecific));
+    }
+
+    #[test]
+    fn class_codes_have_names() {
+        assert_eq!("Mass Storage", ClassCode::MassStorage.to_string());
+        assert_eq!("Unknown (0x04)", ClassCode::Unknown(4).to_string());
+    }
+
+    #[test]
+    fn triples_have_function_names() {
+        let kbd = ClassTriple::new(0x03, 0x01, 0x01);
+        assert_eq!(ClassCode::Hid, kbd.class());
+        assert_eq!(Some("Boot Keyboard"), kbd.function_name());
+        assert_eq!("Human Interface Device: Boot Keyboard", kbd.to_string());
+
+        assert_eq!(
+            Some("CDC ACM (AT commands)"),
+            ClassTriple::new(0x02, 0x02, 0x01).function_name()
+        );
+        assert_eq!(
+            Some("SCSI (Bulk-Only)"),
+            ClassTriple::new(0x08, 0x06, 0x50).function_name()
+        );
+        assert_eq!(
+            Some("DFU (Runtime)"),
+            ClassTriple::new(0xFE, 0x01, 0x01).function_name()
+        );
+        assert_eq!(
+            Some("DFU (DFU Mode)"),
+            ClassTriple::new(0xFE, 0x01, 0x02).function_name()
+        );
+
+        let vendor = ClassTriple::new(0xFF, 0x42, 0x01);
+        assert_eq!(None, vendor.function_name());
+        assert_eq!("Vendor Specific Class", vendor.to_string());
+    }
+}
@@ -0,0 +1,1195 @@
+// yusb/src/compliance.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Compliance checks of a device's descriptors.
+//!
+//! These are modeled on the descriptor tests of the USB-IF Chapter 9
+//! test suite. The checks can be run on raw descriptor bytes, as read from
+//! a device or a file, or on the descriptors of a live [`Device`].
+//!
+//! When working from raw bytes, the framing of each descriptor is checked
+//! as well as its contents. The descriptors of a live device have already
+//! been parsed by `libusb`, so only their contents can be checked.
+
+use crate::{
+    descriptor_parser::{ParseMode, DEVICE_DESCRIPTOR_SIZE},
+    fields::TransferType,
+    interface_association::DT_INTERFACE_ASSOCIATION,
+    ConfigDescriptor, Device, DeviceDescriptor, EndpointDescriptor, InterfaceDescriptor, Result,
+    Speed, Version,
+};
+use libusb1_sys::constants::*;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The severity of a compliance finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Severity {
+    /// A note about the descriptors, or a check that couldn't be done.
+    Info,
+    /// Something that is allowed, but likely to cause problems.
+    Warning,
+    /// A violation of the USB specification.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The check that produced a finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum Check {
+    /// The `bLength` of a descriptor.
+    Length,
+    /// The `bDescriptorType` of a descriptor, and where it appears.
+    DescriptorType,
+    /// The `wTotalLength` of a configuration.
+    TotalLength,
+    /// The USB version in `bcdUSB`, for the speed of the device.
+    UsbVersion,
+    /// The number of configurations.
+    ConfigurationCount,
+    /// The `bConfigurationValue` of the configurations.
+    ConfigurationValue,
+    /// The reserved bits of `bmAttributes`.
+    Attributes,
+    /// The number of interfaces, and the numbering of the interfaces and
+    /// their alternate settings.
+    InterfaceNumbering,
+    /// The number of endpoints of an interface.
+    EndpointCount,
+    /// The endpoint addresses of an interface.
+    EndpointAddress,
+    /// The maximum packet sizes, for the speed of the device.
+    MaxPacketSize,
+    /// The polling intervals of the periodic endpoints.
+    Interval,
+    /// The SuperSpeed endpoint companion descriptors.
+    EndpointCompanion,
+    /// The string descriptor indices.
+    StringIndex,
+    /// The Interface Association Descriptors.
+    InterfaceAssociation,
+}
+
+/// Where a finding was made in the descriptors.
+///
+/// Configurations are identified by their index, which is their position
+/// in the list of the device's configurations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Location {
+    /// The device descriptor.
+    Device,
+    /// A configuration descriptor.
+    Config {
+        /// The index of the configuration.
+        config: u8,
+    },
+    /// A descriptor at a byte offset within a configuration.
+    Offset {
+        /// The index of the configuration.
+        config: u8,
+        /// The offset of the descriptor from the start of the
+        /// configuration.
+        offset: usize,
+    },
+    /// An alternate setting of an interface.
+    Interface {
+        /// The index of the configuration.
+        config: u8,
+        /// The interface number.
+        interface: u8,
+        /// The alternate setting number.
+        alt_setting: u8,
+    },
+    /// An endpoint of an alternate setting.
+    Endpoint {
+        /// The index of the configuration.
+        config: u8,
+        /// The interface number.
+        interface: u8,
+        /// The alternate setting number.
+        alt_setting: u8,
+        /// The endpoint address.
+        address: u8,
+    },
+    /// An Interface Association Descriptor.
+    Function {
+        /// The index of the configuration.
+        config: u8,
+        /// The first interface of the function.
+        first_interface: u8,
+    },
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Location::*;
+        match *self {
+            Device => write!(f, "device"),
+            Config { config } => write!(f, "config {}", config),
+            Offset { config, offset } => write!(f, "config {}, offset {}", config, offset),
+            Interface {
+                config,
+                interface,
+                alt_setting,
+            } => write!(
+                f,
+                "config {}, interface {}.{}",
+                config, interface, alt_setting
+            ),
+            Endpoint {
+                config,
+                interface,
+                alt_setting,
+                address,
+            } => write!(
+                f,
+                "config {}, interface {}.{}, endpoint {:#04x}",
+                config, interface, alt_setting, address
+            ),
+            Function {
+                config,
+                first_interface,
+            } => write!(
+                f,
+                "config {}, IAD for interface {}",
+                config, first_interface
+            ),
+        }
+    }
+}
+
+/// A problem found in the descriptors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Finding {
+    severity: Severity,
+    check: Check,
+    location: Location,
+    message: String,
+}
+
+impl Finding {
+    /// Gets the severity of the finding.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Gets the check that produced the finding.
+    pub fn check(&self) -> Check {
+        self.check
+    }
+
+    /// Gets where the finding was made.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    /// Gets a description of the finding.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Finding {
+    /// Writes the finding as "severity: location: message".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.message)
+    }
+}
+
+/// The findings of a check, as they are collected.
+#[derive(Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn add(&mut self, severity: Severity, check: Check, location: Location, message: String) {
+        self.0.push(Finding {
+            severity,
+            check,
+            location,
+            message,
+        });
+    }
+
+    fn error(&mut self, check: Check, location: Location, message: String) {
+        self.add(Severity::Error, check, location, message);
+    }
+
+    fn warning(&mut self, check: Check, location: Location, message: String) {
+        self.add(Severity::Warning, check, location, message);
+    }
+}
+
+/// Checks the descriptors of a device for compliance with the USB
+/// specification.
+///
+/// The legal packet sizes and intervals depend on the speed at which the
+/// device is operating. String indices are only checked if the checker is
+/// told which strings the device has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplianceChecker {
+    speed: Speed,
+    strings: Option<BTreeSet<u8>>,
+    unreadable: BTreeSet<u8>,
+}
+
+impl ComplianceChecker {
+    /// Creates a checker for a device operating at the specified speed.
+    pub fn new(speed: Speed) -> Self {
+        Self {
+            speed,
+            strings: None,
+            unreadable: BTreeSet::new(),
+        }
+    }
+
+    /// Sets the indices of the strings that the device has, so that
+    /// references to missing strings can be found.
+    pub fn available_strings<I>(&mut self, indices: I) -> &mut Self
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        self.strings = Some(indices.into_iter().collect());
+        self
+    }
+
+    /// Sets the indices of the strings that couldn't be read, for some
+    /// reason other than the device not having them, like a timeout.
+    ///
+    /// References to these are reported as info, rather than as missing
+    /// strings.
+    pub fn unreadable_strings<I>(&mut self, indices: I) -> &mut Self
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        self.unreadable = indices.into_iter().collect();
+        self
+    }
+
+    /// Checks the raw bytes of a device descriptor and its configurations.
+    ///
+    /// The framing of every descriptor is checked, then the descriptors
+    /// are parsed leniently and their contents checked.
+    pub fn check_bytes(&self, device: &[u8], configs: &[&[u8]]) -> Vec<Finding> {
+        let mut findings = Findings::default();
+
+        check_device_framing(&mut findings, device);
+        for (i, buf) in configs.iter().enumerate() {
+            check_config_framing(&mut findings, i as u8, buf);
+        }
+
+        if let Ok(device) = DeviceDescriptor::parse_with_mode(device, ParseMode::Lenient) {
+            let configs: Vec<_> = configs
+                .iter()
+                .map(|buf| ConfigDescriptor::parse_with_mode(buf, ParseMode::Lenient).ok())
+                .collect();
+            self.check_contents(&mut findings, &device, &configs);
+        }
+        findings.0
+    }
+
+    /// Checks the contents of parsed descriptors.
+    pub fn check_descriptors(
+        &self,
+        device: &DeviceDescriptor,
+        configs: &[ConfigDescriptor],
+    ) -> Vec<Finding> {
+        let mut findings = Findings::default();
+        let configs: Vec<_> = configs.iter().map(Some).collect();
+        self.check_contents(&mut findings, device, &configs);
+        findings.0
+    }
+
+    fn check_contents<C>(
+        &self,
+        findings: &mut Findings,
+        device: &DeviceDescriptor,
+        configs: &[Option<C>],
+    ) where
+        C: std::borrow::Borrow<ConfigDescriptor>,
+    {
+        self.check_device(findings, device, configs.len());
+
+        let mut values = BTreeMap::new();
+        for (i, config) in configs.iter().enumerate() {
+            if let Some(config) = config {
+                let config = config.borrow();
+                let loc = Location::Config { config: i as u8 };
+                match config.number() {
+                    0 => findings.error(
+                        Check::ConfigurationValue,
+                        loc,
+                        "bConfigurationValue is zero, which means unconfigured".into(),
+                    ),
+                    n => {
+                        if let Some(prev) = values.insert(n, i) {
+                            findings.error(
+                                Check::ConfigurationValue,
+                                loc,
+                                format!(
+                                    "bConfigurationValue {} is also used by config {}",
+                                    n, prev
+                                ),
+                            );
+                        }
+                    }
+                }
+                self.check_config(findings, i as u8, device, config);
+            }
+        }
+
+        if let Some(available) = &self.strings {
+            let configs: Vec<&ConfigDescriptor> =
+                configs.iter().flatten().map(|c| c.borrow()).collect();
+            for (index, loc) in string_references(device, &configs) {
+                if self.unreadable.contains(&index) {
+                    findings.add(
+                        Severity::Info,
+                        Check::StringIndex,
+                        loc,
+                        format!("string index {} could not be read", index),
+                    );
+                } else if !available.contains(&index) {
+                    findings.error(
+                        Check::StringIndex,
+                        loc,
+                        format!("string index {} refers to a missing string", index),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_device(&self, findings: &mut Findings, device: &DeviceDescriptor, num_configs: usize) {
+        let loc = Location::Device;
+
+        if usize::from(device.num_configurations()) != num_configs {
+            findings.error(
+                Check::ConfigurationCount,
+                loc,
+                format!(
+                    "bNumConfigurations is {}, but {} configurations were found",
+                    device.num_configurations(),
+                    num_configs
+                ),
+            );
+        } else if num_configs == 0 {
+            findings.error(
+                Check::ConfigurationCount,
+                loc,
+                "the device has no configurations".into(),
+            );
+        }
+
+        let version = device.usb_version();
+        let min_version = match self.speed {
+            Speed::High => Some(Version(2, 0, 0)),
+            Speed::Super | Speed::SuperPlus => Some(Version(3, 0, 0)),
+            _ => None,
+        };
+        if let Some(min) = min_version {
+            if version < min {
+                findings.error(
+                    Check::UsbVersion,
+                    loc,
+                    format!(
+                        "bcdUSB is {}, but must be at least {} at {}",
+                        version, min, self.speed
+                    ),
+                );
+            }
+        }
+
+        let mps = device.max_packet_size();
+        let legal = match self.speed {
+            Speed::Low => Some(mps == 8),
+            Speed::Full => Some(matches!(mps, 8 | 16 | 32 | 64)),
+            Speed::High => Some(mps == 64),
+            Speed::Super | Speed::SuperPlus => Some(mps == 9),
+            _ => None,
+        };
+        match legal {
+            Some(false) => findings.error(
+                Check::MaxPacketSize,
+                loc,
+                format!("bMaxPacketSize0 {} is not legal at {}", mps, self.speed),
+            ),
+            Some(true) => {}
+            None => findings.add(
+                Severity::Info,
+                Check::MaxPacketSize,
+                loc,
+                "the speed is unknown, so packet sizes were not checked".into(),
+            ),
+        }
+    }
+
+    fn check_config(
+        &self,
+        findings: &mut Findings,
+        index: u8,
+        device: &DeviceDescriptor,
+        config: &ConfigDescriptor,
+    ) {
+        let loc = Location::Config { config: index };
+
+        let attrs = config.attributes();
+        if attrs & 0x80 == 0 {
+            findings.error(
+                Check::Attributes,
+                loc,
+                "bit 7 of bmAttributes must be set".into(),
+            );
+        }
+        if attrs & 0x1F != 0 {
+            findings.error(
+                Check::Attributes,
+                loc,
+                format!("reserved bits of bmAttributes are set: {:#04x}", attrs),
+            );
+        }
+
+        // The interfaces must be numbered from zero, and so must the
+        // alternate settings of each interface.
+        let mut numbers = BTreeSet::new();
+        for iface in config.interfaces() {
+            if !numbers.insert(iface.number()) {
+                findings.error(
+                    Check::InterfaceNumbering,
+                    loc,
+                    format!("interface {} is defined more than once", iface.number()),
+                );
+            }
+
+            let settings: BTreeSet<_> = iface.descriptors().map(|s| s.setting_number()).collect();
+            if settings.len() != iface.descriptors().count() {
+                findings.error(
+                    Check::InterfaceNumbering,
+                    loc,
+                    format!(
+                        "interface {} has duplicate alternate settings",
+                        iface.number()
+                    ),
+                );
+            }
+            if !is_contiguous(&settings) {
+                findings.error(
+                    Check::InterfaceNumbering,
+                    loc,
+                    format!(
+                        "the alternate settings of interface {} are not numbered from zero",
+                        iface.number()
+                    ),
+                );
+            }
+
+            for setting in iface.descriptors() {
+                if setting.interface_number() != iface.number() {
+                    findings.error(
+                        Check::InterfaceNumbering,
+                        setting_location(index, &setting),
+                        format!(
+                            "bInterfaceNumber is {}, but it is grouped with interface {}",
+                            setting.interface_number(),
+                            iface.number()
+                        ),
+                    );
+                }
+                self.check_setting(findings, index, &setting);
+            }
+        }
+        if !is_contiguous(&numbers) {
+            findings.warning(
+                Check::InterfaceNumbering,
+                loc,
+                "the interfaces are not numbered contiguously from zero".into(),
+            );
+        }
+
+        check_functions(findings, index, device, config, &numbers);
+    }
+
+    fn check_setting(&self, findings: &mut Findings, config: u8, setting: &InterfaceDescriptor) {
+        let mut addresses = BTreeSet::new();
+
+        for endpoint in setting.endpoint_descriptors() {
+            let loc = Location::Endpoint {
+                config,
+                interface: setting.interface_number(),
+                alt_setting: setting.setting_number(),
+                address: endpoint.address(),
+            };
+
+            if endpoint.number() == 0 {
+                findings.error(
+                    Check::EndpointAddress,
+                    loc,
+                    "endpoint zero can't be described by an endpoint descriptor".into(),
+                );
+            }
+            if endpoint.address() & 0x70 != 0 {
+                findings.error(
+                    Check::EndpointAddress,
+                    loc,
+                    "reserved bits of bEndpointAddress are set".into(),
+                );
+            }
+            if !addresses.insert(endpoint.address()) {
+                findings.error(
+                    Check::EndpointAddress,
+                    loc,
+                    "the endpoint address is used more than once in the setting".into(),
+                );
+            }
+
+            self.check_endpoint(findings, loc, &endpoint);
+        }
+    }
+
+    fn check_endpoint(
+        &self,
+        findings: &mut Findings,
+        loc: Location,
+        endpoint: &EndpointDescriptor,
+    ) {
+        use TransferType::*;
+
+        let xfer = endpoint.transfer_type();
+        let size = endpoint.packet_size();
+        let mult = (endpoint.max_packet_size() >> 11) & 0x03;
+        let speed = self.speed;
+
+        let problem = match (speed, xfer) {
+            (Speed::Unknown, _) => None,
+            (Speed::Low, Bulk) | (Speed::Low, Isochronous) => {
+                Some(format!("{:?} endpoints are not allowed at low speed", xfer))
+            }
+            (Speed::Low, Interrupt) if size > 8 => Some(format!("{} bytes is more than 8", size)),
+            (Speed::Full, Bulk) | (Speed::Full, Control) if !matches!(size, 8 | 16 | 32 | 64) => {
+                Some(format!("{} bytes is not 8, 16, 32, or 64", size))
+            }
+            (Speed::Full, Interrupt) if size > 64 => {
+                Some(format!("{} bytes is more than 64", size))
+            }
+            (Speed::Full, Isochronous) if size > 1023 => {
+                Some(format!("{} bytes is more than 1023", size))
+            }
+            (Speed::High, Bulk) if size != 512 => Some(format!("{} bytes is not 512", size)),
+            (Speed::High, Control) if size != 64 => Some(format!("{} bytes is not 64", size)),
+            (Speed::High, _) if size > 1024 => Some(format!("{} bytes is more than 1024", size)),
+            (Speed::High, _) if endpoint.is_periodic() && mult == 3 => {
+                Some("the reserved transactions per microframe value 3 is used".into())
+            }
+            (Speed::High, _)
+                if endpoint.is_periodic() && mult == 1 && !(513..=1024).contains(&size) =>
+            {
+                Some(format!(
+                    "{} bytes is not 513-1024, as needed for 2 transactions per microframe",
+                    size
+                ))
+            }
+            (Speed::High, _)
+                if endpoint.is_periodic() && mult == 2 && !(683..=1024).contains(&size) =>
+            {
+                Some(format!(
+                    "{} bytes is not 683-1024, as needed for 3 transactions per microframe",
+                    size
+                ))
+            }
+            (Speed::Super, Bulk) | (Speed::SuperPlus, Bulk) if size != 1024 => {
+                Some(format!("{} bytes is not 1024", size))
+            }
+            (Speed::Super, Control) | (Speed::SuperPlus, Control) if size != 512 => {
+                Some(format!("{} bytes is not 512", size))
+            }
+            (_, _) if size > 1024 => Some(format!("{} bytes is more than 1024", size)),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            findings.error(
+                Check::MaxPacketSize,
+                loc,
+                format!("wMaxPacketSize is not legal at {}: {}", speed, problem),
+            );
+        }
+
+        if mult != 0 && (speed != Speed::High || !endpoint.is_periodic()) && speed != Speed::Unknown
+        {
+            findings.error(
+                Check::MaxPacketSize,
+                loc,
+                "additional transactions per microframe are only allowed for high speed periodic endpoints".into(),
+            );
+        }
+
+        if endpoint.is_periodic() {
+            let interval = endpoint.interval();
+            let legal = match (speed, xfer) {
+                (Speed::Low, Interrupt)
+                | (Speed::Full, Interrupt)
+                | (Speed::Unknown, Interrupt) => interval >= 1,
+                _ => (1..=16).contains(&interval),
+            };
+            if !legal {
+                findings.error(
+                    Check::Interval,
+                    loc,
+                    format!(
+                        "bInterval {} is not legal for a {:?} endpoint",
+                        interval, xfer
+                    ),
+                );
+            }
+        }
+
+        if speed >= Speed::Super && endpoint.ss_companion().is_none() {
+            findings.error(
+                Check::EndpointCompanion,
+                loc,
+                "the endpoint has no SuperSpeed Endpoint Companion descriptor".into(),
+            );
+        }
+    }
+}
+
+/// Checks the live descriptors of a device, at the speed at which it's
+/// operating.
+///
+/// The descriptors have already been parsed by `libusb`, so only their
+/// contents are checked, and not the framing. String indices aren't
+/// checked, since that requires opening the device. Use
+/// [`DeviceHandle::check_compliance`](crate::DeviceHandle::check_compliance)
+/// for the full set of checks.
+pub fn check_device(device: &Device) -> Result<Vec<Finding>> {
+    let desc = device.device_descriptor()?;
+    let configs = (0..desc.num_configurations())
+        .map(|i| device.config_descriptor(i))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ComplianceChecker::new(device.speed()).check_descriptors(&desc, &configs))
+}
+
+/// Gets all the string indices that are referenced by the descriptors,
+/// along with where they are referenced.
+pub(crate) fn string_references(
+    device: &DeviceDescriptor,
+    configs: &[&ConfigDescriptor],
+) -> Vec<(u8, Location)> {
+    let mut refs: Vec<_> = [
+        device.manufacturer_string_index(),
+        device.product_string_index(),
+        device.serial_number_string_index(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|index| (index, Location::Device))
+    .collect();
+
+    for (i, config) in configs.iter().enumerate() {
+        let i = i as u8;
+        if let Some(index) = config.description_string_index() {
+            refs.push((index, Location::Config { config: i }));
+        }
+        for iface in config.interfaces() {
+            for setting in iface.descriptors() {
+                if let Some(index) = setting.description_string_index() {
+                    refs.push((index, setting_location(i, &setting)));
+                }
+            }
+        }
+        for func in config.functions() {
+            if let Some(index) = func.description_string_index() {
+                refs.push((
+                    index,
+                    Location::Function {
+                        config: i,
+                        first_interface: func.first_interface(),
+                    },
+                ));
+            }
+        }
+    }
+    refs
+}
+
+/// Checks the Interface Association Descriptors of a configuration.
+fn check_functions(
+    findings: &mut Findings,
+    index: u8,
+    device: &DeviceDescriptor,
+    config: &ConfigDescriptor,
+    interfaces: &BTreeSet<u8>,
+) {
+    let functions = config.functions();
+    let mut owner = BTreeMap::new();
+
+    for func in &functions {
+        let loc = Location::Function {
+            config: index,
+            first_interface: func.first_interface(),
+        };
+
+        if func.interface_count() == 0 {
+            findings.error(
+                Check::InterfaceAssociation,
+                loc,
+                "bInterfaceCount is zero".into(),
+            );
+        }
+        for iface in func.interfaces() {
+            if !interfaces.contains(&iface) {
+                findings.error(
+                    Check::InterfaceAssociation,
+                    loc,
+                    format!("interface {} does not exist", iface),
+                );
+            }
+            if let Some(first) = owner.insert(iface, func.first_interface()) {
+                findings.error(
+                    Check::InterfaceAssociation,
+                    loc,
+                    format!(
+                        "interface {} is also in the function for interface {}",
+                        iface, first
+                    ),
+                );
+            }
+        }
+    }
+
+    if !functions.is_empty() {
+        let triple = (
+            device.class_code(),
+            device.sub_class_code(),
+            device.protocol_code(),
+        );
+        if triple != (0xEF, 0x02, 0x01) {
+            findings.warning(
+                Check::InterfaceAssociation,
+                Location::Device,
+                "a device with IADs should use the class triple EF/02/01".into(),
+            );
+        }
+    }
+}
+
+/// Checks the framing of a raw device descriptor.
+fn check_device_framing(findings: &mut Findings, buf: &[u8]) {
+    let loc = Location::Device;
+
+    match *buf {
+        [len, desc_type, ..] => {
+            if usize::from(len) != DEVICE_DESCRIPTOR_SIZE {
+                findings.error(
+                    Check::Length,
+                    loc,
+                    format!("bLength is {}, expected {}", len, DEVICE_DESCRIPTOR_SIZE),
+                );
+            }
+            if desc_type != LIBUSB_DT_DEVICE {
+                findings.error(
+                    Check::DescriptorType,
+                    loc,
+                    format!("bDescriptorType is {:#04x}, expected 0x01", desc_type),
+                );
+            }
+            if buf.len() < DEVICE_DESCRIPTOR_SIZE {
+                findings.error(
+                    Check::Length,
+                    loc,
+                    format!("only {} bytes of the descriptor were read", buf.len()),
+                );
+            }
+        }
+        _ => findings.error(Check::Length, loc, "the descriptor is missing".into()),
+    }
+}
+
+/// Checks the framing of a raw configuration descriptor, and the
+/// descriptors that it contains.
+fn check_config_framing(findings: &mut Findings, config: u8, buf: &[u8]) {
+    let loc = Location::Config { config };
+
+    if buf.len() < 9 {
+        findings.error(
+            Check::Length,
+            loc,
+            format!("only {} bytes of the descriptor were read", buf.len()),
+        );
+        return;
+    }
+    if buf[0] != 9 {
+        findings.error(
+            Check::Length,
+            loc,
+            format!("bLength is {}, expected 9", buf[0]),
+        );
+    }
+    if buf[1] != LIBUSB_DT_CONFIG {
+        findings.error(
+            Check::DescriptorType,
+            loc,
+            format!("bDescriptorType is {:#04x}, expected 0x02", buf[1]),
+        );
+    }
+
+    let total = usize::from(u16::from_le_bytes([buf[2], buf[3]]));
+    if total != buf.len() {
+        findings.error(
+            Check::TotalLength,
+            loc,
+            format!(
+                "wTotalLength is {}, but the configuration has {} bytes",
+                total,
+                buf.len()
+            ),
+        );
+    }
+
+    let end = total.min(buf.len());
+    let mut off = usize::from(buf[0]).max(2);
+    let mut numbers = BTreeSet::new();
+    // The offset of the current setting, with the number of endpoints
+    // that it claims, and the number that were found.
+    let mut setting: Option<(usize, u8, usize)> = None;
+    let mut last_type = 0;
+
+    let close_setting = |findings: &mut Findings, setting: Option<(usize, u8, usize)>| {
+        if let Some((offset, expected, found)) = setting {
+            if usize::from(expected) != found {
+                findings.error(
+                    Check::EndpointCount,
+                    Location::Offset { config, offset },
+                    format!(
+                        "bNumEndpoints is {}, but {} endpoints follow",
+                        expected, found
+                    ),
+                );
+            }
+        }
+    };
+
+    while off + 2 <= end {
+        let loc = Location::Offset {
+            config,
+            offset: off,
+        };
+        let (len, desc_type) = (usize::from(buf[off]), buf[off + 1]);
+
+        if len < 2 {
+            findings.error(Check::Length, loc, format!("bLength is {}", len));
+            break;
+        }
+        if off + len > end {
+            findings.error(
+                Check::Length,
+                loc,
+                format!(
+                    "the descriptor runs {} bytes past the end of the configuration",
+                    off + len - end
+                ),
+            );
+            break;
+        }
+
+        let expected_len: &[usize] = match desc_type {
+            LIBUSB_DT_INTERFACE => &[9],
+            LIBUSB_DT_ENDPOINT => &[7, 9],
+            DT_INTERFACE_ASSOCIATION => &[8],
+            LIBUSB_DT_SS_ENDPOINT_COMPANION => &[6],
+            crate::endpoint_descriptor::DT_SSP_ISO_ENDPOINT_COMPANION => &[8],
+            _ => &[],
+        };
+        if !expected_len.is_empty() && !expected_len.contains(&len) {
+            findings.error(
+                Check::Length,
+                loc,
+                format!(
+                    "bLength of descriptor type {:#04x} is {}, expected {}",
+                    desc_type, len, expected_len[0]
+                ),
+            );
+        }
+
+        match desc_type {
+            LIBUSB_DT_DEVICE | LIBUSB_DT_CONFIG | LIBUSB_DT_STRING | LIBUSB_DT_BOS | 0x06
+            | 0x07 => findings.error(
+                Check::DescriptorType,
+                loc,
+                format!(
+                    "descriptor type {:#04x} is not allowed in a configuration",
+                    desc_type
+                ),
+            ),
+            LIBUSB_DT_INTERFACE if len >= 5 => {
+                close_setting(findings, setting.take());
+                numbers.insert(buf[off + 2]);
+                setting = Some((off, buf[off + 4], 0));
+            }
+            LIBUSB_DT_ENDPOINT => match setting.as_mut() {
+                Some((_, _, found)) => *found += 1,
+                None => findings.error(
+                    Check::DescriptorType,
+                    loc,
+                    "the endpoint does not follow an interface".into(),
+                ),
+            },
+            DT_INTERFACE_ASSOCIATION if len >= 3 && numbers.contains(&buf[off + 2]) => {
+                findings.error(
+                    Check::InterfaceAssociation,
+                    loc,
+                    format!(
+                        "the IAD follows its first interface, {}, instead of preceding it",
+                        buf[off + 2]
+                    ),
+                );
+            }
+            LIBUSB_DT_SS_ENDPOINT_COMPANION if last_type != LIBUSB_DT_ENDPOINT => {
+                findings.error(
+                    Check::EndpointCompanion,
+                    loc,
+                    "the companion does not follow an endpoint".into(),
+                );
+            }
+            _ => {}
+        }
+
+        last_type = desc_type;
+        off += len;
+    }
+    close_setting(findings, setting.take());
+
+    if usize::from(buf[4]) != numbers.len() {
+        findings.error(
+            Check::InterfaceNumbering,
+            loc,
+            format!(
+                "bNumInterfaces is {}, but {} interfaces were found",
+                buf[4],
+                numbers.len()
+            ),
+        );
+    }
+}
+
+/// Gets the location of an alternate setting.
+fn setting_location(config: u8, setting: &InterfaceDescriptor) -> Location {
+    Location::Interface {
+        config,
+        interface: setting.interface_number(),
+        alt_setting: setting.setting_number(),
+    }
+}
+
+/// Determines if a set of numbers runs from zero without gaps.
+fn is_contiguous(numbers: &BTreeSet<u8>) -> bool {
+    numbers
+        .iter()
+        .enumerate()
+        .all(|(i, &n)| usize::from(n) == i)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ConfigDescriptorBuilder, DeviceDescriptorBuilder, EndpointBuilder, Function,
+        InterfaceBuilder,
+    };
+
+    fn device() -> Vec<u8> {
+        DeviceDescriptorBuilder::new(0x1234, 0x5678)
+            .product_string_index(2)
+            .to_bytes()
+    }
+
+    fn config() -> ConfigDescriptorBuilder {
+        let mut config = ConfigDescriptorBuilder::new(1);
+        config.interface(
+            InterfaceBuilder::new(0)
+                .class(0xFF, 0, 0)
+                .endpoint(EndpointBuilder::new(0x81).max_packet_size(512))
+                .endpoint(EndpointBuilder::new(0x02).max_packet_size(512)),
+        );
+        config
+    }
+
+    fn checks(findings: &[Finding]) -> Vec<Check> {
+        findings.iter().map(|f| f.check()).collect()
+    }
+
+    #[test]
+    fn compliant_device() {
+        let config = config().to_bytes().unwrap();
+        let findings = ComplianceChecker::new(Speed::High)
+            .available_strings([2])
+            .check_bytes(&device(), &[&config]);
+        assert_eq!(Vec::<Finding>::new(), findings);
+    }
+
+    #[test]
+    fn framing_errors() {
+        let mut config = config().to_bytes().unwrap();
+        config[2] += 1;
+        config[13] = 3;
+        let findings = ComplianceChecker::new(Speed::High).check_bytes(&device(), &[&config]);
+        assert_eq!(
+            vec![Check::TotalLength, Check::EndpointCount],
+            checks(&findings)
+        );
+        assert_eq!(
+            Location::Offset {
+                config: 0,
+                offset: 9
+            },
+            findings[1].location()
+        );
+
+        let config = config[..config.len() - 3].to_vec();
+        let findings = ComplianceChecker::new(Speed::High).check_bytes(&device(), &[&config]);
+        assert!(checks(&findings).contains(&Check::Length));
+    }
+
+    #[test]
+    fn speed_dependent_errors() {
+        let mut config = ConfigDescriptorBuilder::new(1);
+        config.interface(
+            InterfaceBuilder::new(0)
+                .endpoint(EndpointBuilder::new(0x81).max_packet_size(64))
+                .endpoint(
+                    EndpointBuilder::new(0x82)
+                        .transfer_type(TransferType::Isochronous)
+                        .max_packet_size(0x0800 | 256)
+                        .interval(0),
+                ),
+        );
+        let config = config.to_bytes().unwrap();
+
+        let findings = ComplianceChecker::new(Speed::High).check_bytes(&device(), &[&config]);
+        assert_eq!(
+            vec![Check::MaxPacketSize, Check::MaxPacketSize, Check::Interval],
+            checks(&findings)
+        );
+
+        let findings = ComplianceChecker::new(Speed::Full).check_bytes(&device(), &[&config]);
+        assert_eq!(
+            vec![Check::MaxPacketSize, Check::Interval],
+            checks(&findings)
+        );
+
+        let findings = ComplianceChecker::new(Speed::Super).check_bytes(&device(), &[&config]);
+        assert!(checks(&findings).contains(&Check::UsbVersion));
+        assert!(checks(&findings).contains(&Check::EndpointCompanion));
+    }
+
+    #[test]
+    fn numbering_and_address_errors() {
+        let mut config = ConfigDescriptorBuilder::new(0);
+        config
+            .interface(
+                InterfaceBuilder::new(0)
+                    .endpoint(EndpointBuilder::new(0x81).max_packet_size(512))
+                    .endpoint(EndpointBuilder::new(0x81).max_packet_size(512)),
+            )
+            .interface(InterfaceBuilder::new(2).alt_setting(1));
+        let config = config.to_bytes().unwrap();
+
+        let findings = ComplianceChecker::new(Speed::High).check_bytes(&device(), &[&config]);
+        assert_eq!(
+            vec![
+                Check::ConfigurationValue,
+                Check::EndpointAddress,
+                Check::InterfaceNumbering,
+                Check::InterfaceNumbering,
+            ],
+            checks(&findings)
+        );
+        assert_eq!(
+            "error: config 0, interface 0.0, endpoint 0x81: \
+             the endpoint address is used more than once in the setting",
+            findings[1].to_string()
+        );
+    }
+
+    #[test]
+    fn string_and_iad_errors() {
+        let mut config = config();
+        config
+            .description_string_index(4)
+            .function(Function::new(0, 2, 0x0E, 0x03, 0x00, None));
+        let config = config.to_bytes().unwrap();
+
+        let findings = ComplianceChecker::new(Speed::High)
+            .available_strings([2])
+            .check_bytes(&device(), &[&config]);
+        assert_eq!(
+            vec![
+                Check::InterfaceAssociation,
+                Check::InterfaceAssociation,
+                Check::StringIndex,
+            ],
+            checks(&findings)
+        );
+        assert_eq!(Severity::Warning, findings[1].severity());
+        assert_eq!(Location::Config { config: 0 }, findings[2].location());
+    }
+
+    #[test]
+    fn unreadable_strings() {
+        let mut config = config();
+        config.description_string_index(4);
+        let config = config.to_bytes().unwrap();
+
+        let findings = ComplianceChecker::new(Speed::High)
+            .available_strings([2])
+            .unreadable_strings([4])
+            .check_bytes(&device(), &[&config]);
+        assert_eq!(vec![Check::StringIndex], checks(&findings));
+        assert_eq!(Severity::Info, findings[0].severity());
+
+        // Without the available strings, they aren't checked at all.
+        let findings = ComplianceChecker::new(Speed::High).check_bytes(&device(), &[&config]);
+        assert_eq!(Vec::<Finding>::new(), findings);
+    }
+
+    #[test]
+    fn descriptors_without_framing() {
+        let device = DeviceDescriptorBuilder::new(0x1234, 0x5678)
+            .num_configurations(2)
+            .build();
+        let configs = [config().build().unwrap()];
+        let findings = ComplianceChecker::new(Speed::Unknown).check_descriptors(&device, &configs);
+        assert_eq!(
+            vec![Check::ConfigurationCount, Check::MaxPacketSize],
+            checks(&findings)
+        );
+        assert_eq!(Severity::Info, findings[1].severity());
+    }
+}
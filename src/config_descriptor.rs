@@ -10,17 +10,30 @@
 // to those terms.
 //
 
-use crate::Interface;
+use crate::{
+    descriptor_parser::{self, OwnedConfig, ParseMode},
+    interface_association, Function, Interface, Result, Speed,
+};
 use libusb1_sys as ffi;
 use std::{fmt, slice};
 
 /// Describes a configuration.
-pub struct ConfigDescriptor(*const ffi::libusb_config_descriptor);
+///
+/// This is usually read from a device by `libusb`, but can also be parsed
+/// from raw bytes with [`ConfigDescriptor::parse`].
+pub struct ConfigDescriptor {
+    desc: *const ffi::libusb_config_descriptor,
+    // The memory of a descriptor that was parsed, rather than allocated
+    // by libusb.
+    owned: Option<Box<OwnedConfig>>,
+}
 
 impl Drop for ConfigDescriptor {
     fn drop(&mut self) {
-        unsafe {
-            ffi::libusb_free_config_descriptor(self.0);
+        if self.owned.is_none() {
+            unsafe {
+                ffi::libusb_free_config_descriptor(self.desc);
+            }
         }
     }
 }
@@ -29,30 +42,62 @@ unsafe impl Sync for ConfigDescriptor {}
 unsafe impl Send for ConfigDescriptor {}
 
 impl ConfigDescriptor {
+    /// Parses a configuration descriptor from its raw bytes, along with
+    /// all the interface, endpoint, and other descriptors that follow it.
+    ///
+    /// This is strict, failing with `Error::BadDescriptor` if any of the
+    /// descriptors are malformed or truncated. Any bytes past the
+    /// `wTotalLength` of the configuration are ignored, so that a buffer
+    /// with several configurations can be parsed one after another.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        Self::parse_with_mode(buf, ParseMode::Strict)
+    }
+
+    /// Parses a configuration descriptor from its raw bytes, with the
+    /// specified handling of malformed or truncated descriptors.
+    pub fn parse_with_mode(buf: &[u8], mode: ParseMode) -> Result<Self> {
+        let owned = descriptor_parser::parse_config(buf, mode)?;
+        Ok(Self {
+            desc: owned.as_ptr(),
+            owned: Some(owned),
+        })
+    }
+
+    /// Returns the total length of the configuration, including all the
+    /// descriptors that follow the configuration descriptor.
+    pub fn total_length(&self) -> u16 {
+        unsafe { (*self.desc).wTotalLength }
+    }
+
     /// Returns the configuration number.
     pub fn number(&self) -> u8 {
-        unsafe { (*self.0).bConfigurationValue }
+        unsafe { (*self.desc).bConfigurationValue }
     }
 
     /// Returns the device's maximum power consumption (in milliamps) in this configuration.
     pub fn max_power(&self) -> u16 {
-        unsafe { u16::from((*self.0).bMaxPower) * 2 }
+        unsafe { u16::from((*self.desc).bMaxPower) * 2 }
+    }
+
+    /// Returns the raw `bmAttributes` bitmap of the configuration.
+    pub fn attributes(&self) -> u8 {
+        unsafe { (*self.desc).bmAttributes }
     }
 
     /// Indicates if the device is self-powered in this configuration.
     pub fn self_powered(&self) -> bool {
-        unsafe { (*self.0).bmAttributes & 0x40 != 0 }
+        unsafe { (*self.desc).bmAttributes & 0x40 != 0 }
     }
 
     /// Indicates if the device has remote wakeup capability in this configuration.
     pub fn remote_wakeup(&self) -> bool {
-        unsafe { (*self.0).bmAttributes & 0x20 != 0 }
+        unsafe { (*self.desc).bmAttributes & 0x20 != 0 }
     }
 
     /// Returns the index of the string descriptor that describes the configuration.
     pub fn description_string_index(&self) -> Option<u8> {
         unsafe {
-            match (*self.0).iConfiguration {
+            match (*self.desc).iConfiguration {
                 0 => None,
                 n => Some(n),
             }
@@ -61,13 +106,13 @@ impl ConfigDescriptor {
 
     /// Returns the number of interfaces for this configuration.
     pub fn num_interfaces(&self) -> u8 {
-        unsafe { (*self.0).bNumInterfaces }
+        unsafe { (*self.desc).bNumInterfaces }
     }
 
     /// Returns a collection of the configuration's interfaces.
-    pub fn interfaces(&self) -> Interfaces {
+    pub fn interfaces(&self) -> Interfaces<'_> {
         let interfaces = unsafe {
-            slice::from_raw_parts((*self.0).interface, (*self.0).bNumInterfaces as usize)
+            slice::from_raw_parts((*self.desc).interface, (*self.desc).bNumInterfaces as usize)
         };
 
         Interfaces {
@@ -75,11 +120,40 @@ impl ConfigDescriptor {
         }
     }
 
+    /// Returns an estimate of the worst-case periodic bandwidth of the
+    /// configuration, in bytes per second, when the device is operating at
+    /// the specified speed.
+    ///
+    /// This is the sum, over all the interfaces, of the largest bandwidth
+    /// of any of the interface's alternate settings. Compare it against
+    /// [`Speed::periodic_bandwidth_limit`] to predict whether the periodic
+    /// schedule will fit on the bus.
+    pub fn periodic_bandwidth(&self, speed: Speed) -> u64 {
+        self.interfaces()
+            .map(|iface| iface.max_periodic_bandwidth(speed))
+            .sum()
+    }
+
+    /// Returns the functions of a composite device in this configuration.
+    ///
+    /// These are parsed from the Interface Association Descriptors (IADs),
+    /// which `libusb` leaves in the 'extra' bytes of the configuration, and
+    /// of the interfaces and endpoints. The functions are sorted by their
+    /// first interface. This is empty for a device that doesn't use IADs.
+    pub fn functions(&self) -> Vec<Function> {
+        interface_association::parse_functions(self)
+    }
+
+    /// Returns the function that the interface belongs to, if any.
+    pub fn function_for_interface(&self, iface: u8) -> Option<Function> {
+        self.functions().into_iter().find(|f| f.contains(iface))
+    }
+
     /// Returns the unknown 'extra' bytes that libusb does not understand.
     pub fn extra(&self) -> &[u8] {
         unsafe {
-            match (*self.0).extra_length {
-                len if len > 0 => slice::from_raw_parts((*self.0).extra, len as usize),
+            match (*self.desc).extra_length {
+                len if len > 0 => slice::from_raw_parts((*self.desc).extra, len as usize),
                 _ => &[],
             }
         }
@@ -87,10 +161,10 @@ impl ConfigDescriptor {
 }
 
 impl fmt::Debug for ConfigDescriptor {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut debug = fmt.debug_struct("ConfigDescriptor");
 
-        let descriptor: &ffi::libusb_config_descriptor = unsafe { &*self.0 };
+        let descriptor: &ffi::libusb_config_descriptor = unsafe { &*self.desc };
 
         debug.field("bLength", &descriptor.bLength);
         debug.field("bDescriptorType", &descriptor.bDescriptorType);
@@ -125,7 +199,10 @@ impl<'a> Iterator for Interfaces<'a> {
 
 impl From<*const ffi::libusb_config_descriptor> for ConfigDescriptor {
     fn from(cfg: *const ffi::libusb_config_descriptor) -> Self {
-        Self(cfg)
+        Self {
+            desc: cfg,
+            owned: None,
+        }
     }
 }
 
@@ -165,22 +242,22 @@ mod test {
     #[test]
     fn it_interprets_self_powered_bit_in_attributes() {
         with_config!(config: config_descriptor!(bmAttributes: 0b0000_0000) => {
-            assert_eq!(false, config.self_powered());
+            assert!(!config.self_powered());
         });
 
         with_config!(config: config_descriptor!(bmAttributes: 0b0100_0000) => {
-            assert_eq!(true, config.self_powered());
+            assert!(config.self_powered());
         });
     }
 
     #[test]
     fn it_interprets_remote_wakeup_bit_in_attributes() {
         with_config!(config: config_descriptor!(bmAttributes: 0b0000_0000) => {
-            assert_eq!(false, config.remote_wakeup());
+            assert!(!config.remote_wakeup());
         });
 
         with_config!(config: config_descriptor!(bmAttributes: 0b0010_0000) => {
-            assert_eq!(true, config.remote_wakeup());
+            assert!(config.remote_wakeup());
         });
     }
 
@@ -239,7 +316,62 @@ mod test {
                 let desc: crate::InterfaceDescriptor<'a> = config.interfaces().flat_map(|intf| intf.descriptors()).next().expect("There's one interface");
                 let _: crate::EndpointDescriptor<'a> = desc.endpoint_descriptors().next().expect("There's one endpoint");
             }
-            named_lifetime(&*config);
+            named_lifetime(&config);
         })
     }
+
+    #[test]
+    fn it_has_functions_from_iads() {
+        // The IAD for the first function precedes the first interface, and
+        // the one for the second function follows the first endpoint.
+        let iad1 = [0x08, 0x0B, 0x00, 0x02, 0x02, 0x02, 0x01, 0x00];
+        let iad2 = [0x08, 0x0B, 0x02, 0x02, 0x0E, 0x03, 0x00, 0x04];
+
+        let endpoint = endpoint_descriptor!(
+            bEndpointAddress: 0x81,
+            extra: iad2.as_ptr(),
+            extra_length: iad2.len() as i32
+        );
+        let interface = interface!(interface_descriptor!(endpoint));
+
+        let mut config = config_descriptor!(interface);
+        config.extra = iad1.as_ptr();
+        config.extra_length = iad1.len() as i32;
+
+        with_config!(config: config => {
+            let functions = config.functions();
+            assert_eq!(2, functions.len());
+            assert_eq!(0..=1, functions[0].interfaces());
+            assert_eq!(0x0E, functions[1].class_code());
+            assert_eq!(Some(4), functions[1].description_string_index());
+            assert_eq!(Some(functions[1]), config.function_for_interface(3));
+            assert_eq!(None, config.function_for_interface(4));
+        });
+    }
+
+    #[test]
+    fn it_has_periodic_bandwidth() {
+        let iface0 = interface!(interface_descriptor!(endpoint_descriptor!(
+            bmAttributes: 0b0000_0011,
+            wMaxPacketSize: 64,
+            bInterval: 1
+        )));
+        let iface1 = interface!(
+            interface_descriptor!(bInterfaceNumber: 1),
+            interface_descriptor!(endpoint_descriptor!(
+                bmAttributes: 0b0000_0001,
+                wMaxPacketSize: 0x0C00,
+                bInterval: 1
+            ))
+        );
+
+        with_config!(config: config_descriptor!(iface0, iface1) => {
+            // 64 bytes every 125us, plus 2 x 1024 bytes every 125us
+            assert_eq!(512_000 + 16_384_000, config.periodic_bandwidth(Speed::High));
+            assert!(
+                config.periodic_bandwidth(Speed::High)
+                    < Speed::High.periodic_bandwidth_limit()
+            );
+        });
+    }
 }
@@ -12,7 +12,16 @@
 
 use libc::timeval;
 use once_cell::sync::Lazy;
-use std::{mem, os::raw::c_int, ptr, sync::Arc, time::Duration};
+use std::{
+    mem,
+    os::raw::c_int,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 #[cfg(windows)]
 use std::os::raw::c_long;
@@ -23,8 +32,10 @@ use std::os::unix::io::RawFd;
 use crate::{
     device_handle::DeviceHandle,
     device_list::DeviceList,
+    event_thread::EventThread,
     hotplug::{Hotplug, HotplugBuilder, Registration},
-    Error, Result,
+    logging::LogCallback,
+    Error, Result, UsbOption,
 };
 use libusb1_sys::{self as ffi, constants::*};
 
@@ -44,8 +55,30 @@ pub struct Context {
     inner: Arc<ContextInner>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
-struct ContextInner(*mut ffi::libusb_context);
+#[derive(Debug)]
+struct ContextInner {
+    // The raw libusb context. This is null for the global context.
+    ptr: *mut ffi::libusb_context,
+    // Whether an `EventThread` is currently running for this context.
+    event_thread: AtomicBool,
+}
+
+impl ContextInner {
+    fn new(ptr: *mut ffi::libusb_context) -> Self {
+        Self {
+            ptr,
+            event_thread: AtomicBool::new(false),
+        }
+    }
+}
+
+impl PartialEq for ContextInner {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl Eq for ContextInner {}
 
 unsafe impl Sync for ContextInner {}
 unsafe impl Send for ContextInner {}
@@ -54,7 +87,7 @@ impl Drop for ContextInner {
     /// Closes the `libusb` context.
     fn drop(&mut self) {
         unsafe {
-            ffi::libusb_exit(self.0);
+            ffi::libusb_exit(self.ptr);
         }
     }
 }
@@ -63,7 +96,7 @@ impl Drop for ContextInner {
 pub static GLOBAL_CONTEXT: Lazy<Context> = Lazy::new(|| {
     let _ = unsafe { ffi::libusb_init(ptr::null_mut()) };
     Context {
-        inner: Arc::new(ContextInner(ptr::null_mut())),
+        inner: Arc::new(ContextInner::new(ptr::null_mut())),
     }
 });
 
@@ -78,14 +111,15 @@ impl Context {
     }
 
     /// Creates a new `libusb` context and sets runtime options.
-    pub fn with_options(opts: &[crate::UsbOption]) -> Result<Self> {
-        let mut this = Self::new()?;
-
-        for opt in opts {
-            opt.apply(&mut this)?;
-        }
-
-        Ok(this)
+    ///
+    /// When the linked `libusb` supports `libusb_init_context()` (v1.0.27
+    /// or later), the options are applied as the context is initialized,
+    /// before device discovery runs. Otherwise the context is created first,
+    /// and then the options are applied to it, and
+    /// [`UsbOption::no_device_discovery`] fails with [`Error::NotSupported`].
+    #[doc(alias = "libusb_init_context")]
+    pub fn with_options(opts: &[UsbOption]) -> Result<Self> {
+        crate::options::new_context(opts)
     }
 
     /// Gets the global context
@@ -95,7 +129,7 @@ impl Context {
 
     /// Determines if this object is a reference the global context.
     pub fn is_global(&self) -> bool {
-        self.inner.0.is_null()
+        self.inner.ptr.is_null()
     }
 
     /// Creates yusb Context from existing libusb context.
@@ -105,13 +139,19 @@ impl Context {
     /// so the caller must guarantee that libusb_context is created properly.
     pub unsafe fn from_raw(raw: *mut ffi::libusb_context) -> Self {
         Context {
-            inner: Arc::new(ContextInner(raw)),
+            inner: Arc::new(ContextInner::new(raw)),
         }
     }
 
     /// Get the raw libusb_context pointer, for advanced use in unsafe code.
     pub fn as_raw(&self) -> *mut ffi::libusb_context {
-        self.inner.0
+        self.inner.ptr
+    }
+
+    /// Gets the number of references to the underlying context.
+    #[cfg(test)]
+    pub(crate) fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
     }
 
     /// Returns a list of the current USB devices.
@@ -149,7 +189,7 @@ impl Context {
     ) -> Option<DeviceHandle> {
         let handle =
             unsafe { ffi::libusb_open_device_with_vid_pid(self.as_raw(), vendor_id, product_id) };
-        let ptr = std::ptr::NonNull::new(handle)?;
+        let ptr = ptr::NonNull::new(handle)?;
         Some(unsafe { DeviceHandle::from_libusb(self.clone(), ptr) })
     }
 
@@ -158,7 +198,9 @@ impl Context {
     /// This is UNIX-only and platform-specific. It is currently working with
     /// Linux/Android, but might work with other systems in the future.
     ///
-    /// Note: This function does not take ownership of the specified file
+    /// # Safety
+    ///
+    /// This function does not take ownership of the specified file
     /// descriptor. The caller has the responsibility of keeping it opened for
     /// as long as the device handle.
     #[cfg(unix)]
@@ -168,7 +210,7 @@ impl Context {
 
         match ffi::libusb_wrap_sys_device(self.as_raw(), fd as _, handle.as_mut_ptr()) {
             0 => {
-                let ptr = std::ptr::NonNull::new(handle.assume_init()).ok_or(Error::NoDevice)?;
+                let ptr = ptr::NonNull::new(handle.assume_init()).ok_or(Error::NoDevice)?;
 
                 Ok(DeviceHandle::from_libusb(self.clone(), ptr))
             }
@@ -183,6 +225,21 @@ impl Context {
         }
     }
 
+    /// Sets where the log messages for this context are sent.
+    ///
+    /// This can forward the `libusb` messages to the `log` crate or to
+    /// `tracing`, depending on the enabled features. The `libusb` log level
+    /// for the context is also set to match the maximum level currently
+    /// enabled in the selected logger. See the [logging](crate::logging)
+    /// module for details.
+    ///
+    /// Returns `NotSupported` if the linked `libusb` is too old to support
+    /// log callbacks.
+    #[doc(alias = "libusb_set_log_cb")]
+    pub fn set_log_callback(&mut self, cb: LogCallback) -> Result<()> {
+        cb.apply(self.as_raw(), false)
+    }
+
     /// Register a callback to be called on hotplug events. The callback's
     /// [Hotplug::device_arrived] method is called when a new device is added to
     /// the bus, and [Hotplug::device_left] is called when it is removed.
@@ -261,6 +318,51 @@ impl Context {
         unsafe { ffi::libusb_interrupt_event_handler(self.as_raw()) }
     }
 
+    /// Starts a background thread that handles events for this context.
+    ///
+    /// The thread repeatedly calls [handle_events][`Self::handle_events()`]
+    /// until the returned [EventThread] is stopped or dropped, at which point
+    /// it is woken with [interrupt_handle_events][`Self::interrupt_handle_events()`]
+    /// and joined.
+    ///
+    /// This is what is typically needed to service hotplug callbacks and
+    /// asynchronous transfers.
+    ///
+    /// ## Errors
+    ///
+    /// * `Busy` if an event thread is already running for this context, or
+    ///   if another thread is currently handling events for it.
+    /// * `Other` if the thread could not be spawned.
+    pub fn spawn_event_thread(&self) -> Result<EventThread> {
+        EventThread::spawn(self.clone())
+    }
+
+    /// Determines if an [EventThread] is currently running for this context.
+    pub fn has_event_thread(&self) -> bool {
+        self.inner.event_thread.load(Ordering::Acquire)
+    }
+
+    /// Determines if another thread is currently handling events for this
+    /// context.
+    #[doc(alias = "libusb_event_handler_active")]
+    pub fn event_handler_active(&self) -> bool {
+        unsafe { ffi::libusb_event_handler_active(self.as_raw()) != 0 }
+    }
+
+    /// Claims the right to run an [EventThread] on this context.
+    /// Returns `false` if one is already running.
+    pub(crate) fn acquire_event_thread(&self) -> bool {
+        self.inner
+            .event_thread
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Releases the right to run an [EventThread] on this context.
+    pub(crate) fn release_event_thread(&self) {
+        self.inner.event_thread.store(false, Ordering::Release);
+    }
+
     /// Determine the next internal timeout that libusb needs to handle.
     /// Returns Ok(None) if there are no pending timeouts.
     pub fn next_timeout(&self) -> Result<Option<Duration>> {
@@ -290,7 +392,7 @@ impl Default for Context {
 /////////////////////////////////////////////////////////////////////////////
 
 /// Library logging levels.
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogLevel {
     /// No messages are printed by `libusb` (default).
     None,
@@ -322,6 +424,18 @@ impl LogLevel {
     }
 }
 
+impl From<c_int> for LogLevel {
+    fn from(level: c_int) -> Self {
+        match level {
+            LIBUSB_LOG_LEVEL_ERROR => LogLevel::Error,
+            LIBUSB_LOG_LEVEL_WARNING => LogLevel::Warning,
+            LIBUSB_LOG_LEVEL_INFO => LogLevel::Info,
+            LIBUSB_LOG_LEVEL_DEBUG => LogLevel::Debug,
+            _ => LogLevel::None,
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -336,4 +450,13 @@ mod test {
         let ctx = Context::new().unwrap();
         assert!(!ctx.is_global());
     }
+
+    #[test]
+    fn log_level_from_libusb() {
+        assert_eq!(LogLevel::Error, LogLevel::from(LIBUSB_LOG_LEVEL_ERROR));
+        assert_eq!(LogLevel::Warning, LogLevel::from(LIBUSB_LOG_LEVEL_WARNING));
+        assert_eq!(LogLevel::Info, LogLevel::from(LIBUSB_LOG_LEVEL_INFO));
+        assert_eq!(LogLevel::Debug, LogLevel::from(LIBUSB_LOG_LEVEL_DEBUG));
+        assert_eq!(LogLevel::None, LogLevel::from(LIBUSB_LOG_LEVEL_NONE));
+    }
 }
@@ -0,0 +1,898 @@
+// yusb/src/descriptor_builder.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Builders that encode USB descriptors into their raw wire format.
+//!
+//! These are useful for firmware development and test fixtures. The
+//! encoded descriptors can be parsed back into the usual descriptor types,
+//! like [`ConfigDescriptor::parse`], and the builders can be created from
+//! those types, so a descriptor read from a device can be re-encoded, or
+//! modified.
+//!
+//! Computed fields, like `wTotalLength`, `bNumInterfaces`, and
+//! `bNumEndpoints`, are filled in when the descriptors are encoded.
+
+use crate::{
+    fields::{IsoSyncType, IsoUsageType, TransferType, Version},
+    interface_association::Function,
+    BosDescriptor, ConfigDescriptor, DeviceCapability, DeviceDescriptor, EndpointDescriptor, Error,
+    Guid, InterfaceDescriptor, Language, Result,
+};
+use libusb1_sys::{self as ffi, constants::*};
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Builds a device descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceDescriptorBuilder {
+    usb_version: Version,
+    class_code: u8,
+    sub_class_code: u8,
+    protocol_code: u8,
+    max_packet_size: u8,
+    vendor_id: u16,
+    product_id: u16,
+    device_version: Version,
+    manufacturer_string_index: u8,
+    product_string_index: u8,
+    serial_number_string_index: u8,
+    num_configurations: u8,
+}
+
+impl DeviceDescriptorBuilder {
+    /// Creates a builder for a USB 2.0 device with the vendor and product
+    /// IDs, one configuration, and a 64-byte control endpoint.
+    pub fn new(vendor_id: u16, product_id: u16) -> Self {
+        Self {
+            usb_version: Version(2, 0, 0),
+            class_code: 0,
+            sub_class_code: 0,
+            protocol_code: 0,
+            max_packet_size: 64,
+            vendor_id,
+            product_id,
+            device_version: Version(1, 0, 0),
+            manufacturer_string_index: 0,
+            product_string_index: 0,
+            serial_number_string_index: 0,
+            num_configurations: 1,
+        }
+    }
+
+    /// Sets the USB version that the device supports.
+    pub fn usb_version(&mut self, version: Version) -> &mut Self {
+        self.usb_version = version;
+        self
+    }
+
+    /// Sets the device's class, sub class, and protocol codes.
+    pub fn class(&mut self, class_code: u8, sub_class_code: u8, protocol_code: u8) -> &mut Self {
+        self.class_code = class_code;
+        self.sub_class_code = sub_class_code;
+        self.protocol_code = protocol_code;
+        self
+    }
+
+    /// Sets the maximum packet size of the control endpoint.
+    pub fn max_packet_size(&mut self, max_packet_size: u8) -> &mut Self {
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    /// Sets the manufacturer's version of the device.
+    pub fn device_version(&mut self, version: Version) -> &mut Self {
+        self.device_version = version;
+        self
+    }
+
+    /// Sets the index of the manufacturer string.
+    pub fn manufacturer_string_index(&mut self, index: u8) -> &mut Self {
+        self.manufacturer_string_index = index;
+        self
+    }
+
+    /// Sets the index of the product string.
+    pub fn product_string_index(&mut self, index: u8) -> &mut Self {
+        self.product_string_index = index;
+        self
+    }
+
+    /// Sets the index of the serial number string.
+    pub fn serial_number_string_index(&mut self, index: u8) -> &mut Self {
+        self.serial_number_string_index = index;
+        self
+    }
+
+    /// Sets the number of configurations.
+    pub fn num_configurations(&mut self, num_configurations: u8) -> &mut Self {
+        self.num_configurations = num_configurations;
+        self
+    }
+
+    /// Encodes the device descriptor.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![18, LIBUSB_DT_DEVICE];
+        buf.extend_from_slice(&self.usb_version.to_bcd().to_le_bytes());
+        buf.extend_from_slice(&[
+            self.class_code,
+            self.sub_class_code,
+            self.protocol_code,
+            self.max_packet_size,
+        ]);
+        buf.extend_from_slice(&self.vendor_id.to_le_bytes());
+        buf.extend_from_slice(&self.product_id.to_le_bytes());
+        buf.extend_from_slice(&self.device_version.to_bcd().to_le_bytes());
+        buf.extend_from_slice(&[
+            self.manufacturer_string_index,
+            self.product_string_index,
+            self.serial_number_string_index,
+            self.num_configurations,
+        ]);
+        buf
+    }
+
+    /// Creates the device descriptor.
+    pub fn build(&self) -> DeviceDescriptor {
+        DeviceDescriptor::from(ffi::libusb_device_descriptor {
+            bLength: 18,
+            bDescriptorType: LIBUSB_DT_DEVICE,
+            bcdUSB: self.usb_version.to_bcd(),
+            bDeviceClass: self.class_code,
+            bDeviceSubClass: self.sub_class_code,
+            bDeviceProtocol: self.protocol_code,
+            bMaxPacketSize0: self.max_packet_size,
+            idVendor: self.vendor_id,
+            idProduct: self.product_id,
+            bcdDevice: self.device_version.to_bcd(),
+            iManufacturer: self.manufacturer_string_index,
+            iProduct: self.product_string_index,
+            iSerialNumber: self.serial_number_string_index,
+            bNumConfigurations: self.num_configurations,
+        })
+    }
+}
+
+impl From<&DeviceDescriptor> for DeviceDescriptorBuilder {
+    fn from(desc: &DeviceDescriptor) -> Self {
+        Self {
+            usb_version: desc.usb_version(),
+            class_code: desc.class_code(),
+            sub_class_code: desc.sub_class_code(),
+            protocol_code: desc.protocol_code(),
+            max_packet_size: desc.max_packet_size(),
+            vendor_id: desc.vendor_id(),
+            product_id: desc.product_id(),
+            device_version: desc.device_version(),
+            manufacturer_string_index: desc.manufacturer_string_index().unwrap_or(0),
+            product_string_index: desc.product_string_index().unwrap_or(0),
+            serial_number_string_index: desc.serial_number_string_index().unwrap_or(0),
+            num_configurations: desc.num_configurations(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Builds a configuration descriptor, with all of its interfaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDescriptorBuilder {
+    number: u8,
+    description_string_index: u8,
+    attributes: u8,
+    max_power: u8,
+    extra: Vec<u8>,
+    functions: Vec<Function>,
+    interfaces: Vec<InterfaceBuilder>,
+}
+
+impl ConfigDescriptorBuilder {
+    /// Creates a builder for a bus-powered configuration, drawing 100mA.
+    pub fn new(number: u8) -> Self {
+        Self {
+            number,
+            description_string_index: 0,
+            attributes: 0x80,
+            max_power: 50,
+            extra: Vec::new(),
+            functions: Vec::new(),
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Sets the index of the string that describes the configuration.
+    pub fn description_string_index(&mut self, index: u8) -> &mut Self {
+        self.description_string_index = index;
+        self
+    }
+
+    /// Sets whether the device is self-powered in this configuration.
+    pub fn self_powered(&mut self, on: bool) -> &mut Self {
+        self.set_attribute(0x40, on)
+    }
+
+    /// Sets whether the device supports remote wakeup in this
+    /// configuration.
+    pub fn remote_wakeup(&mut self, on: bool) -> &mut Self {
+        self.set_attribute(0x20, on)
+    }
+
+    fn set_attribute(&mut self, bit: u8, on: bool) -> &mut Self {
+        if on {
+            self.attributes |= bit;
+        } else {
+            self.attributes &= !bit;
+        }
+        self
+    }
+
+    /// Sets the maximum power that the device draws from the bus in this
+    /// configuration, in milliamps. This is rounded up to the 2mA units of
+    /// the descriptor.
+    pub fn max_power(&mut self, milliamps: u16) -> &mut Self {
+        self.max_power = (milliamps / 2 + milliamps % 2).min(255) as u8;
+        self
+    }
+
+    /// Appends raw descriptors, such as class-specific ones, to follow the
+    /// configuration descriptor.
+    pub fn extra(&mut self, extra: &[u8]) -> &mut Self {
+        self.extra.extend_from_slice(extra);
+        self
+    }
+
+    /// Adds a function of a composite device.
+    ///
+    /// This is encoded as an Interface Association Descriptor (IAD), just
+    /// before the first interface of the function.
+    pub fn function(&mut self, function: Function) -> &mut Self {
+        self.functions.push(function);
+        self
+    }
+
+    /// Adds an interface, or an alternate setting of one.
+    ///
+    /// The alternate settings of an interface should be added one after
+    /// another.
+    pub fn interface(&mut self, interface: &InterfaceBuilder) -> &mut Self {
+        self.interfaces.push(interface.clone());
+        self
+    }
+
+    /// Gets the number of distinct interfaces in the configuration.
+    ///
+    /// Returns `Error::Overflow` if there are too many for the 8-bit
+    /// `bNumInterfaces`.
+    pub fn num_interfaces(&self) -> Result<u8> {
+        let mut numbers: Vec<u8> = self.interfaces.iter().map(|i| i.number).collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        u8::try_from(numbers.len()).map_err(|_| Error::Overflow)
+    }
+
+    /// Encodes the configuration descriptor, along with all of the
+    /// descriptors that follow it.
+    ///
+    /// Returns `Error::Overflow` if the configuration is too long for the
+    /// 16-bit `wTotalLength`, or has too many interfaces.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![
+            9,
+            LIBUSB_DT_CONFIG,
+            0,
+            0,
+            self.num_interfaces()?,
+            self.number,
+            self.description_string_index,
+            self.attributes,
+            self.max_power,
+        ];
+        buf.extend_from_slice(&self.extra);
+
+        // Each IAD goes just before the first interface that it covers.
+        let mut functions = self.functions.clone();
+        functions.sort_by_key(|f| f.first_interface());
+        let mut functions = functions.into_iter().peekable();
+
+        for iface in &self.interfaces {
+            if iface.alt_setting == 0 {
+                while let Some(f) = functions.next_if(|f| f.first_interface() <= iface.number) {
+                    buf.extend_from_slice(&f.to_bytes());
+                }
+            }
+            iface.encode(&mut buf)?;
+        }
+        for f in functions {
+            buf.extend_from_slice(&f.to_bytes());
+        }
+
+        let total_len = u16::try_from(buf.len()).map_err(|_| Error::Overflow)?;
+        buf[2..4].copy_from_slice(&total_len.to_le_bytes());
+        Ok(buf)
+    }
+
+    /// Creates the configuration descriptor.
+    pub fn build(&self) -> Result<ConfigDescriptor> {
+        ConfigDescriptor::parse(&self.to_bytes()?)
+    }
+}
+
+impl From<&ConfigDescriptor> for ConfigDescriptorBuilder {
+    /// Creates a builder from a configuration.
+    ///
+    /// Any IADs, and other unparsed descriptors, are kept in the 'extra'
+    /// bytes where they were found, so that the configuration is re-encoded
+    /// exactly as it was.
+    fn from(config: &ConfigDescriptor) -> Self {
+        Self {
+            number: config.number(),
+            description_string_index: config.description_string_index().unwrap_or(0),
+            attributes: config.attributes(),
+            max_power: (config.max_power() / 2) as u8,
+            extra: config.extra().to_vec(),
+            functions: Vec::new(),
+            interfaces: config
+                .interfaces()
+                .flat_map(|iface| iface.descriptors())
+                .map(|desc| InterfaceBuilder::from(&desc))
+                .collect(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Builds an interface descriptor, with its endpoints.
+///
+/// Each interface descriptor is one alternate setting of the interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceBuilder {
+    number: u8,
+    alt_setting: u8,
+    class_code: u8,
+    sub_class_code: u8,
+    protocol_code: u8,
+    description_string_index: u8,
+    extra: Vec<u8>,
+    endpoints: Vec<EndpointBuilder>,
+}
+
+impl InterfaceBuilder {
+    /// Creates a builder for the default alternate setting of the
+    /// interface.
+    pub fn new(number: u8) -> Self {
+        Self {
+            number,
+            alt_setting: 0,
+            class_code: 0,
+            sub_class_code: 0,
+            protocol_code: 0,
+            description_string_index: 0,
+            extra: Vec::new(),
+            endpoints: Vec::new(),
+        }
+    }
+
+    /// Sets the alternate setting number.
+    pub fn alt_setting(&mut self, alt_setting: u8) -> &mut Self {
+        self.alt_setting = alt_setting;
+        self
+    }
+
+    /// Sets the interface's class, sub class, and protocol codes.
+    pub fn class(&mut self, class_code: u8, sub_class_code: u8, protocol_code: u8) -> &mut Self {
+        self.class_code = class_code;
+        self.sub_class_code = sub_class_code;
+        self.protocol_code = protocol_code;
+        self
+    }
+
+    /// Sets the index of the string that describes the interface.
+    pub fn description_string_index(&mut self, index: u8) -> &mut Self {
+        self.description_string_index = index;
+        self
+    }
+
+    /// Appends raw descriptors, such as class-specific ones, to follow the
+    /// interface descriptor, before the endpoints.
+    pub fn extra(&mut self, extra: &[u8]) -> &mut Self {
+        self.extra.extend_from_slice(extra);
+        self
+    }
+
+    /// Adds an endpoint.
+    pub fn endpoint(&mut self, endpoint: &EndpointBuilder) -> &mut Self {
+        self.endpoints.push(endpoint.clone());
+        self
+    }
+
+    /// Encodes the interface descriptor, along with the descriptors that
+    /// follow it.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let num_endpoints = u8::try_from(self.endpoints.len()).map_err(|_| Error::Overflow)?;
+        buf.extend_from_slice(&[
+            9,
+            LIBUSB_DT_INTERFACE,
+            self.number,
+            self.alt_setting,
+            num_endpoints,
+            self.class_code,
+            self.sub_class_code,
+            self.protocol_code,
+            self.description_string_index,
+        ]);
+        buf.extend_from_slice(&self.extra);
+        for ep in &self.endpoints {
+            ep.encode(buf);
+        }
+        Ok(())
+    }
+}
+
+impl From<&InterfaceDescriptor<'_>> for InterfaceBuilder {
+    fn from(desc: &InterfaceDescriptor<'_>) -> Self {
+        Self {
+            number: desc.interface_number(),
+            alt_setting: desc.setting_number(),
+            class_code: desc.class_code(),
+            sub_class_code: desc.sub_class_code(),
+            protocol_code: desc.protocol_code(),
+            description_string_index: desc.description_string_index().unwrap_or(0),
+            extra: desc.extra().to_vec(),
+            endpoints: desc
+                .endpoint_descriptors()
+                .map(|ep| EndpointBuilder::from(&ep))
+                .collect(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Builds an endpoint descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointBuilder {
+    address: u8,
+    attributes: u8,
+    max_packet_size: u16,
+    interval: u8,
+    audio: Option<(u8, u8)>,
+    extra: Vec<u8>,
+}
+
+impl EndpointBuilder {
+    /// Creates a builder for a bulk endpoint with the address, which
+    /// includes the direction bit.
+    pub fn new(address: u8) -> Self {
+        Self {
+            address,
+            attributes: TransferType::Bulk as u8,
+            max_packet_size: 64,
+            interval: 0,
+            audio: None,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Sets the endpoint's transfer type.
+    pub fn transfer_type(&mut self, transfer_type: TransferType) -> &mut Self {
+        self.attributes = (self.attributes & !LIBUSB_TRANSFER_TYPE_MASK) | transfer_type as u8;
+        self
+    }
+
+    /// Sets the synchronization and usage types of an isochronous endpoint.
+    pub fn iso_types(&mut self, sync_type: IsoSyncType, usage_type: IsoUsageType) -> &mut Self {
+        self.attributes = (self.attributes & LIBUSB_TRANSFER_TYPE_MASK)
+            | (sync_type as u8) << 2
+            | (usage_type as u8) << 4;
+        self
+    }
+
+    /// Sets the raw `bmAttributes` bitmap.
+    pub fn attributes(&mut self, attributes: u8) -> &mut Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Sets the raw `wMaxPacketSize` field.
+    pub fn max_packet_size(&mut self, max_packet_size: u16) -> &mut Self {
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    /// Sets the polling interval.
+    pub fn interval(&mut self, interval: u8) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Makes this an audio endpoint, with the `bRefresh` and
+    /// `bSynchAddress` fields.
+    pub fn audio(&mut self, refresh: u8, synch_address: u8) -> &mut Self {
+        self.audio = Some((refresh, synch_address));
+        self
+    }
+
+    /// Appends a SuperSpeed Endpoint Companion descriptor.
+    pub fn ss_companion(
+        &mut self,
+        max_burst: u8,
+        attributes: u8,
+        bytes_per_interval: u16,
+    ) -> &mut Self {
+        let bpi = bytes_per_interval.to_le_bytes();
+        self.extra(&[
+            6,
+            LIBUSB_DT_SS_ENDPOINT_COMPANION,
+            max_burst,
+            attributes,
+            bpi[0],
+            bpi[1],
+        ])
+    }
+
+    /// Appends raw descriptors, such as class-specific ones, to follow the
+    /// endpoint descriptor.
+    pub fn extra(&mut self, extra: &[u8]) -> &mut Self {
+        self.extra.extend_from_slice(extra);
+        self
+    }
+
+    /// Encodes the endpoint descriptor, along with the descriptors that
+    /// follow it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let len = if self.audio.is_some() { 9 } else { 7 };
+        let mps = self.max_packet_size.to_le_bytes();
+        buf.extend_from_slice(&[
+            len,
+            LIBUSB_DT_ENDPOINT,
+            self.address,
+            self.attributes,
+            mps[0],
+            mps[1],
+            self.interval,
+        ]);
+        if let Some((refresh, synch_address)) = self.audio {
+            buf.extend_from_slice(&[refresh, synch_address]);
+        }
+        buf.extend_from_slice(&self.extra);
+    }
+}
+
+impl From<&EndpointDescriptor<'_>> for EndpointBuilder {
+    fn from(desc: &EndpointDescriptor<'_>) -> Self {
+        let audio = (usize::from(desc.descriptor_length()) >= 9)
+            .then(|| (desc.refresh(), desc.synch_address()));
+
+        let mut ep = EndpointBuilder::new(desc.address());
+        ep.attributes = desc.attributes();
+        ep.max_packet_size = desc.max_packet_size();
+        ep.interval = desc.interval();
+        ep.audio = audio;
+        ep.extra = desc.extra().unwrap_or(&[]).to_vec();
+        ep
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Builds a Binary device Object Store (BOS) descriptor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BosDescriptorBuilder {
+    capabilities: Vec<Vec<u8>>,
+    // Whether a capability was too long to encode.
+    overflow: bool,
+}
+
+impl BosDescriptorBuilder {
+    /// Creates a builder for a BOS descriptor with no capabilities.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device capability.
+    ///
+    /// A capability that is too long makes [`to_bytes`](Self::to_bytes)
+    /// fail.
+    pub fn capability(&mut self, cap: &DeviceCapability) -> &mut Self {
+        let cap = cap.encode();
+        if cap.len() > 255 {
+            self.overflow = true;
+        } else {
+            self.capabilities.push(cap);
+        }
+        self
+    }
+
+    /// Adds a device capability from its type and the raw data that
+    /// follows the header.
+    ///
+    /// A capability that is too long makes [`to_bytes`](Self::to_bytes)
+    /// fail.
+    pub fn raw_capability(&mut self, capability_type: u8, data: &[u8]) -> &mut Self {
+        match u8::try_from(data.len() + 3) {
+            Ok(len) => {
+                let mut cap = vec![len, LIBUSB_DT_DEVICE_CAPABILITY, capability_type];
+                cap.extend_from_slice(data);
+                self.capabilities.push(cap);
+            }
+            Err(_) => self.overflow = true,
+        }
+        self
+    }
+
+    /// Adds a USB 2.0 Extension capability, with the raw `bmAttributes`.
+    pub fn usb2_extension(&mut self, attributes: u32) -> &mut Self {
+        self.raw_capability(
+            crate::bos_descriptor::USB_2_0_EXTENSION_CAPABILITY,
+            &attributes.to_le_bytes(),
+        )
+    }
+
+    /// Adds a Container ID capability.
+    pub fn container_id(&mut self, id: Guid) -> &mut Self {
+        let mut data = vec![0];
+        data.extend_from_slice(id.as_bytes());
+        self.raw_capability(crate::bos_descriptor::CONTAINER_ID_CAPABILITY, &data)
+    }
+
+    /// Adds a Platform capability, such as for WebUSB or MS OS 2.0
+    /// descriptors.
+    pub fn platform(&mut self, uuid: Guid, data: &[u8]) -> &mut Self {
+        let mut cap = vec![0];
+        cap.extend_from_slice(uuid.as_bytes());
+        cap.extend_from_slice(data);
+        self.raw_capability(crate::bos_descriptor::PLATFORM_CAPABILITY, &cap)
+    }
+
+    /// Encodes the BOS descriptor, along with all of its capabilities.
+    ///
+    /// Returns `Error::Overflow` if there are too many capabilities, or
+    /// they're too long.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.overflow {
+            return Err(Error::Overflow);
+        }
+        let num_caps = u8::try_from(self.capabilities.len()).map_err(|_| Error::Overflow)?;
+        let mut buf = vec![5, LIBUSB_DT_BOS, 0, 0, num_caps];
+        for cap in &self.capabilities {
+            buf.extend_from_slice(cap);
+        }
+        let total_len = u16::try_from(buf.len()).map_err(|_| Error::Overflow)?;
+        buf[2..4].copy_from_slice(&total_len.to_le_bytes());
+        Ok(buf)
+    }
+
+    /// Creates the BOS descriptor.
+    pub fn build(&self) -> Result<BosDescriptor> {
+        BosDescriptor::parse(&self.to_bytes()?)
+    }
+}
+
+impl From<&BosDescriptor> for BosDescriptorBuilder {
+    fn from(bos: &BosDescriptor) -> Self {
+        Self {
+            capabilities: bos
+                .capabilities()
+                .iter()
+                .map(DeviceCapability::encode)
+                .collect(),
+            overflow: false,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// Encodes a string descriptor.
+///
+/// Returns `Error::Overflow` if the string is too long to fit in a
+/// descriptor, which holds up to 126 UTF-16 code units.
+pub fn string_descriptor(s: &str) -> Result<Vec<u8>> {
+    let mut buf = vec![0, LIBUSB_DT_STRING];
+    buf.extend(s.encode_utf16().flat_map(u16::to_le_bytes));
+    buf[0] = u8::try_from(buf.len()).map_err(|_| Error::Overflow)?;
+    Ok(buf)
+}
+
+/// Encodes the string descriptor zero, with the supported languages.
+///
+/// Returns `Error::Overflow` if there are too many languages to fit in a
+/// descriptor.
+pub fn languages_descriptor(languages: &[Language]) -> Result<Vec<u8>> {
+    let mut buf = vec![0, LIBUSB_DT_STRING];
+    buf.extend(
+        languages
+            .iter()
+            .flat_map(|lang| lang.lang_id().to_le_bytes()),
+    );
+    buf[0] = u8::try_from(buf.len()).map_err(|_| Error::Overflow)?;
+    Ok(buf)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Direction;
+
+    #[test]
+    fn device_round_trip() {
+        let mut builder = DeviceDescriptorBuilder::new(0x1234, 0x5678);
+        builder
+            .usb_version(Version(2, 1, 0))
+            .class(0xEF, 0x02, 0x01)
+            .product_string_index(2);
+
+        let bytes = builder.to_bytes();
+        assert_eq!(18, bytes.len());
+        assert_eq!(&[0x10, 0x02], &bytes[2..4]);
+
+        let desc = DeviceDescriptor::parse(&bytes).unwrap();
+        assert_eq!(0x5678, desc.product_id());
+        assert_eq!(Some(2), desc.product_string_index());
+        assert_eq!(None, desc.manufacturer_string_index());
+        assert_eq!(Version(2, 1, 0), desc.usb_version());
+
+        assert_eq!(bytes, DeviceDescriptorBuilder::from(&desc).to_bytes());
+        assert_eq!(0xEF, builder.build().class_code());
+    }
+
+    #[test]
+    fn max_power_rounds_up() {
+        let mut config = ConfigDescriptorBuilder::new(1);
+        assert_eq!(0, config.max_power(0).max_power);
+        assert_eq!(1, config.max_power(1).max_power);
+        assert_eq!(50, config.max_power(100).max_power);
+        assert_eq!(255, config.max_power(510).max_power);
+        assert_eq!(255, config.max_power(u16::MAX).max_power);
+    }
+
+    #[test]
+    fn config_with_function() {
+        let mut comm = InterfaceBuilder::new(0);
+        comm.class(0x02, 0x02, 0x01)
+            .extra(&[0x05, 0x24, 0x00, 0x10, 0x01])
+            .endpoint(
+                EndpointBuilder::new(0x83)
+                    .transfer_type(TransferType::Interrupt)
+                    .max_packet_size(8)
+                    .interval(16),
+            );
+
+        let mut data = InterfaceBuilder::new(1);
+        data.class(0x0A, 0, 0)
+            .endpoint(&EndpointBuilder::new(0x01))
+            .endpoint(&EndpointBuilder::new(0x82));
+
+        let mut builder = ConfigDescriptorBuilder::new(1);
+        builder
+            .max_power(100)
+            .function(Function::new(0, 2, 0x02, 0x02, 0x01, None))
+            .interface(&comm)
+            .interface(&data);
+
+        let bytes = builder.to_bytes().unwrap();
+        assert_eq!(Ok(2), builder.num_interfaces());
+        assert_eq!(
+            bytes.len(),
+            usize::from(u16::from_le_bytes([bytes[2], bytes[3]]))
+        );
+        assert_eq!(&[0x08, 0x0B, 0x00, 0x02], &bytes[9..13]);
+
+        let config = builder.build().unwrap();
+        assert_eq!(2, config.num_interfaces());
+        assert_eq!(100, config.max_power());
+        assert_eq!(1, config.functions().len());
+
+        let data = config.interfaces().nth(1).unwrap();
+        let eps: Vec<_> = data
+            .descriptors()
+            .next()
+            .unwrap()
+            .endpoint_descriptors()
+            .map(|ep| (ep.direction(), ep.transfer_type()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Direction::Out, TransferType::Bulk),
+                (Direction::In, TransferType::Bulk)
+            ],
+            eps
+        );
+
+        // Re-encoding the parsed configuration gives the same bytes.
+        let rebuilt = ConfigDescriptorBuilder::from(&config).to_bytes().unwrap();
+        assert_eq!(bytes, rebuilt);
+    }
+
+    #[test]
+    fn endpoint_encoding() {
+        let mut ep = EndpointBuilder::new(0x81);
+        ep.transfer_type(TransferType::Isochronous)
+            .iso_types(IsoSyncType::Asynchronous, IsoUsageType::Data)
+            .max_packet_size(1024)
+            .interval(1)
+            .ss_companion(0, 0, 1024);
+
+        assert_eq!(
+            vec![0x07, 0x05, 0x81, 0x05, 0x00, 0x04, 0x01, 0x06, 0x30, 0x00, 0x00, 0x00, 0x04],
+            ep.to_bytes()
+        );
+    }
+
+    #[test]
+    fn bos_round_trip() {
+        let id = Guid::from_bytes([7; 16]);
+        let mut builder = BosDescriptorBuilder::new();
+        builder.usb2_extension(0x02).container_id(id).platform(
+            crate::webusb::WEBUSB_PLATFORM_UUID,
+            &[0x00, 0x01, 0x01, 0x01],
+        );
+
+        let bos = builder.build().unwrap();
+        assert_eq!(3, bos.num_capabilities());
+        assert!(bos.usb2_extension().unwrap().lpm_supported());
+        assert_eq!(Some(id), bos.container_id());
+        assert_eq!(Some(1), bos.webusb().unwrap().landing_page_index());
+
+        assert_eq!(
+            builder.to_bytes().unwrap(),
+            BosDescriptorBuilder::from(&bos).to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn too_many_interfaces() {
+        let mut builder = ConfigDescriptorBuilder::new(1);
+        for n in 0..=255 {
+            builder.interface(&InterfaceBuilder::new(n));
+        }
+        assert_eq!(Err(Error::Overflow), builder.num_interfaces());
+        assert_eq!(Err(Error::Overflow), builder.to_bytes());
+    }
+
+    #[test]
+    fn capability_too_long() {
+        let mut builder = BosDescriptorBuilder::new();
+        builder.raw_capability(0x7F, &[0; 252]);
+        assert_eq!(5 + 255, builder.to_bytes().unwrap().len());
+
+        builder.raw_capability(0x7F, &[0; 253]);
+        assert_eq!(Err(Error::Overflow), builder.to_bytes());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            vec![0x06, 0x03, b'h', 0, b'i', 0],
+            string_descriptor("hi").unwrap()
+        );
+        assert_eq!(Err(Error::Overflow), string_descriptor(&"x".repeat(127)));
+
+        let langs = [crate::language::from_lang_id(0x0409)];
+        assert_eq!(
+            vec![0x04, 0x03, 0x09, 0x04],
+            languages_descriptor(&langs).unwrap()
+        );
+    }
+}
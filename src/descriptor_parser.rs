@@ -0,0 +1,459 @@
+// yusb/src/descriptor_parser.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! A pure-Rust parser for the standard device and configuration
+//! descriptors.
+//!
+//! This builds the same structures that `libusb` produces, but from
+//! memory that is owned by Rust, so that the usual descriptor types, like
+//! [`ConfigDescriptor`](crate::ConfigDescriptor), can be used on
+//! descriptors from a captured trace, a sysfs `descriptors` file, or a
+//! firmware image.
+
+use crate::{Error, Result};
+use libusb1_sys::{self as ffi, constants::*};
+use std::{os::raw::c_int, ptr};
+
+/// The size of a device descriptor.
+pub(crate) const DEVICE_DESCRIPTOR_SIZE: usize = 18;
+
+/// The size of the device descriptor up to, and including,
+/// `bMaxPacketSize0`, which is all that some hosts read at first.
+const DEVICE_DESCRIPTOR_MIN_SIZE: usize = 8;
+
+/// The size of a configuration descriptor, without the descriptors that
+/// follow it.
+const CONFIG_DESCRIPTOR_SIZE: usize = 9;
+
+/// The size of an interface descriptor.
+const INTERFACE_DESCRIPTOR_SIZE: usize = 9;
+
+/// The size of a standard endpoint descriptor.
+const ENDPOINT_DESCRIPTOR_SIZE: usize = 7;
+
+/// The size of an audio endpoint descriptor, with `bRefresh` and
+/// `bSynchAddress`.
+const AUDIO_ENDPOINT_DESCRIPTOR_SIZE: usize = 9;
+
+/// How strictly to treat malformed or truncated descriptors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseMode {
+    /// Any malformed or truncated descriptor is an error.
+    Strict,
+    /// Parse as much as possible, skipping malformed descriptors, and
+    /// stopping at the first one that is truncated. The counts of
+    /// interfaces and endpoints are corrected to what was actually found.
+    Lenient,
+}
+
+impl Default for ParseMode {
+    fn default() -> Self {
+        ParseMode::Strict
+    }
+}
+
+/// Reads a little-endian u16 from the buffer at the offset, or zero if the
+/// buffer is too short.
+fn le_u16(buf: &[u8], off: usize) -> u16 {
+    match buf.get(off..off + 2) {
+        Some(b) => u16::from_le_bytes([b[0], b[1]]),
+        None => 0,
+    }
+}
+
+/// Parses a device descriptor into the `libusb` structure.
+pub(crate) fn parse_device(buf: &[u8], mode: ParseMode) -> Result<ffi::libusb_device_descriptor> {
+    let min_len = match mode {
+        ParseMode::Strict => DEVICE_DESCRIPTOR_SIZE,
+        ParseMode::Lenient => DEVICE_DESCRIPTOR_MIN_SIZE,
+    };
+
+    if buf.len() < min_len || buf[1] != LIBUSB_DT_DEVICE {
+        return Err(Error::BadDescriptor);
+    }
+    if mode == ParseMode::Strict && usize::from(buf[0]) != DEVICE_DESCRIPTOR_SIZE {
+        return Err(Error::BadDescriptor);
+    }
+
+    // In lenient mode, any fields missing from a truncated descriptor are
+    // left as zero.
+    let mut desc = [0u8; DEVICE_DESCRIPTOR_SIZE];
+    let n = buf.len().min(DEVICE_DESCRIPTOR_SIZE);
+    desc[..n].copy_from_slice(&buf[..n]);
+
+    Ok(ffi::libusb_device_descriptor {
+        bLength: desc[0],
+        bDescriptorType: desc[1],
+        bcdUSB: le_u16(&desc, 2),
+        bDeviceClass: desc[4],
+        bDeviceSubClass: desc[5],
+        bDeviceProtocol: desc[6],
+        bMaxPacketSize0: desc[7],
+        idVendor: le_u16(&desc, 8),
+        idProduct: le_u16(&desc, 10),
+        bcdDevice: le_u16(&desc, 12),
+        iManufacturer: desc[14],
+        iProduct: desc[15],
+        iSerialNumber: desc[16],
+        bNumConfigurations: desc[17],
+    })
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An endpoint, as it's parsed.
+struct Endpoint<'a> {
+    desc: &'a [u8],
+    extra: Vec<u8>,
+}
+
+/// An alternate setting of an interface, as it's parsed.
+struct AltSetting<'a> {
+    desc: &'a [u8],
+    extra: Vec<u8>,
+    endpoints: Vec<Endpoint<'a>>,
+}
+
+/// A configuration descriptor that was parsed from memory, along with all
+/// the memory that the `libusb` structures point into.
+///
+/// The structures are linked with raw pointers into the heap buffers of the
+/// vectors, which don't move when this is moved, as long as the vectors
+/// aren't modified after they're built.
+pub(crate) struct OwnedConfig {
+    config: ffi::libusb_config_descriptor,
+    _interfaces: Vec<ffi::libusb_interface>,
+    _altsettings: Vec<Vec<ffi::libusb_interface_descriptor>>,
+    _endpoints: Vec<Vec<ffi::libusb_endpoint_descriptor>>,
+    _extras: Vec<Vec<u8>>,
+}
+
+impl OwnedConfig {
+    /// Gets a pointer to the `libusb` configuration descriptor.
+    ///
+    /// This is valid for as long as the `OwnedConfig` is alive, even if it
+    /// is moved, as long as it's boxed.
+    pub(crate) fn as_ptr(&self) -> *const ffi::libusb_config_descriptor {
+        &self.config
+    }
+}
+
+/// Stores the extra bytes, returning the pointer and length for `libusb`.
+fn store_extra(extras: &mut Vec<Vec<u8>>, extra: Vec<u8>) -> (*const u8, c_int) {
+    if extra.is_empty() {
+        return (ptr::null(), 0);
+    }
+    let res = (extra.as_ptr(), extra.len() as c_int);
+    extras.push(extra);
+    res
+}
+
+/// Parses a configuration descriptor, and all the interface, endpoint,
+/// and other descriptors that follow it.
+///
+/// As with `libusb`, the interfaces are grouped by number, with each
+/// interface descriptor being an alternate setting. Any descriptors that
+/// aren't standard interface or endpoint descriptors are kept in the
+/// 'extra' bytes of the configuration, interface, or endpoint that they
+/// follow.
+pub(crate) fn parse_config(buf: &[u8], mode: ParseMode) -> Result<Box<OwnedConfig>> {
+    let strict = mode == ParseMode::Strict;
+
+    if buf.len() < CONFIG_DESCRIPTOR_SIZE
+        || usize::from(buf[0]) < CONFIG_DESCRIPTOR_SIZE
+        || buf[1] != LIBUSB_DT_CONFIG
+    {
+        return Err(Error::BadDescriptor);
+    }
+
+    let total_len = usize::from(le_u16(buf, 2));
+    if total_len < usize::from(buf[0]) || (strict && total_len > buf.len()) {
+        return Err(Error::BadDescriptor);
+    }
+    let end = total_len.min(buf.len());
+    let num_interfaces = buf[4];
+
+    let mut config_extra = Vec::new();
+    let mut ifaces: Vec<Vec<AltSetting>> = Vec::new();
+    let mut rest = &buf[usize::from(buf[0])..end];
+
+    while !rest.is_empty() {
+        let len = usize::from(rest[0]);
+        if len < 2 || len > rest.len() {
+            if strict {
+                return Err(Error::BadDescriptor);
+            }
+            break;
+        }
+        let (desc, tail) = rest.split_at(len);
+        rest = tail;
+
+        let alt = ifaces.last_mut().and_then(|alts| alts.last_mut());
+
+        match desc[1] {
+            LIBUSB_DT_INTERFACE if len >= INTERFACE_DESCRIPTOR_SIZE => {
+                let alt = AltSetting {
+                    desc,
+                    extra: Vec::new(),
+                    endpoints: Vec::new(),
+                };
+                match ifaces.last_mut() {
+                    Some(alts) if alts[0].desc[2] == desc[2] => alts.push(alt),
+                    _ => ifaces.push(vec![alt]),
+                }
+            }
+            LIBUSB_DT_ENDPOINT if len >= ENDPOINT_DESCRIPTOR_SIZE && alt.is_some() => {
+                if let Some(alt) = alt {
+                    alt.endpoints.push(Endpoint {
+                        desc,
+                        extra: Vec::new(),
+                    });
+                }
+            }
+            LIBUSB_DT_INTERFACE | LIBUSB_DT_ENDPOINT if strict => {
+                return Err(Error::BadDescriptor);
+            }
+            LIBUSB_DT_DEVICE | LIBUSB_DT_CONFIG => {
+                if strict {
+                    return Err(Error::BadDescriptor);
+                }
+                break;
+            }
+            _ => {
+                let extra = match alt {
+                    Some(alt) => match alt.endpoints.last_mut() {
+                        Some(ep) => &mut ep.extra,
+                        None => &mut alt.extra,
+                    },
+                    None => &mut config_extra,
+                };
+                extra.extend_from_slice(desc);
+            }
+        }
+    }
+
+    if strict {
+        if ifaces.len() != usize::from(num_interfaces) {
+            return Err(Error::BadDescriptor);
+        }
+        let bad_endpoints = ifaces
+            .iter()
+            .flatten()
+            .any(|alt| alt.endpoints.len() != usize::from(alt.desc[4]));
+        if bad_endpoints {
+            return Err(Error::BadDescriptor);
+        }
+    }
+
+    // Now build the libusb structures from the bottom up.
+
+    let mut extras = Vec::new();
+    let mut endpoints = Vec::new();
+    let mut altsettings = Vec::new();
+    let mut interfaces = Vec::with_capacity(ifaces.len());
+
+    for alts in ifaces {
+        let mut iface_alts = Vec::with_capacity(alts.len());
+
+        for alt in alts {
+            let eps: Vec<_> = alt
+                .endpoints
+                .into_iter()
+                .map(|ep| {
+                    let d = ep.desc;
+                    let audio = d.len() >= AUDIO_ENDPOINT_DESCRIPTOR_SIZE;
+                    let (extra, extra_length) = store_extra(&mut extras, ep.extra);
+                    ffi::libusb_endpoint_descriptor {
+                        bLength: d[0],
+                        bDescriptorType: d[1],
+                        bEndpointAddress: d[2],
+                        bmAttributes: d[3],
+                        wMaxPacketSize: le_u16(d, 4),
+                        bInterval: d[6],
+                        bRefresh: if audio { d[7] } else { 0 },
+                        bSynchAddress: if audio { d[8] } else { 0 },
+                        extra,
+                        extra_length,
+                    }
+                })
+                .collect();
+
+            let d = alt.desc;
+            let (extra, extra_length) = store_extra(&mut extras, alt.extra);
+            iface_alts.push(ffi::libusb_interface_descriptor {
+                bLength: d[0],
+                bDescriptorType: d[1],
+                bInterfaceNumber: d[2],
+                bAlternateSetting: d[3],
+                bNumEndpoints: eps.len() as u8,
+                bInterfaceClass: d[5],
+                bInterfaceSubClass: d[6],
+                bInterfaceProtocol: d[7],
+                iInterface: d[8],
+                endpoint: eps.as_ptr(),
+                extra,
+                extra_length,
+            });
+            endpoints.push(eps);
+        }
+
+        interfaces.push(ffi::libusb_interface {
+            altsetting: iface_alts.as_ptr(),
+            num_altsetting: iface_alts.len() as c_int,
+        });
+        altsettings.push(iface_alts);
+    }
+
+    let (extra, extra_length) = store_extra(&mut extras, config_extra);
+    let config = ffi::libusb_config_descriptor {
+        bLength: buf[0],
+        bDescriptorType: buf[1],
+        wTotalLength: total_len as u16,
+        bNumInterfaces: interfaces.len() as u8,
+        bConfigurationValue: buf[5],
+        iConfiguration: buf[6],
+        bmAttributes: buf[7],
+        bMaxPower: buf[8],
+        interface: interfaces.as_ptr(),
+        extra,
+        extra_length,
+    };
+
+    Ok(Box::new(OwnedConfig {
+        config,
+        _interfaces: interfaces,
+        _altsettings: altsettings,
+        _endpoints: endpoints,
+        _extras: extras,
+    }))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ConfigDescriptor, DeviceDescriptor, Direction, TransferType};
+
+    // A CDC-ACM configuration, with an IAD and class-specific descriptors.
+    const CONFIG: [u8; 65] = [
+        0x09, 0x02, 0x41, 0x00, 0x02, 0x01, 0x00, 0x80, 0x32, // config
+        0x08, 0x0B, 0x00, 0x02, 0x02, 0x02, 0x01, 0x00, // IAD
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x02, 0x02, 0x01, 0x00, // interface 0
+        0x05, 0x24, 0x00, 0x10, 0x01, // CDC header
+        0x04, 0x24, 0x02, 0x02, // CDC ACM
+        0x07, 0x05, 0x83, 0x03, 0x08, 0x00, 0x10, // EP 0x83
+        0x09, 0x04, 0x01, 0x00, 0x02, 0x0A, 0x00, 0x00, 0x00, // interface 1
+        0x07, 0x05, 0x01, 0x02, 0x40, 0x00, 0x00, // EP 0x01
+        0x07, 0x05, 0x82, 0x02, 0x40, 0x00, 0x00, // EP 0x82
+    ];
+
+    const DEVICE: [u8; 18] = [
+        0x12, 0x01, 0x00, 0x02, 0xEF, 0x02, 0x01, 0x40, 0x34, 0x12, 0x78, 0x56, 0x00, 0x01, 0x01,
+        0x02, 0x03, 0x01,
+    ];
+
+    #[test]
+    fn parse_device_descriptor() {
+        let desc = DeviceDescriptor::parse(&DEVICE).unwrap();
+        assert_eq!(0x1234, desc.vendor_id());
+        assert_eq!(0x5678, desc.product_id());
+        assert_eq!(0xEF, desc.class_code());
+        assert_eq!(64, desc.max_packet_size());
+        assert_eq!(Some(3), desc.serial_number_string_index());
+        assert_eq!(1, desc.num_configurations());
+
+        assert!(DeviceDescriptor::parse(&DEVICE[..8]).is_err());
+
+        let desc = DeviceDescriptor::parse_with_mode(&DEVICE[..8], ParseMode::Lenient).unwrap();
+        assert_eq!(64, desc.max_packet_size());
+        assert_eq!(0, desc.vendor_id());
+
+        assert!(DeviceDescriptor::parse_with_mode(&CONFIG, ParseMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn parse_config_descriptor() {
+        let config = ConfigDescriptor::parse(&CONFIG).unwrap();
+        assert_eq!(65, config.total_length());
+        assert_eq!(1, config.number());
+        assert_eq!(100, config.max_power());
+        assert_eq!(2, config.num_interfaces());
+        assert_eq!(&CONFIG[9..17], config.extra());
+        assert_eq!(1, config.functions().len());
+
+        let ifaces: Vec<_> = config.interfaces().collect();
+        assert_eq!(2, ifaces.len());
+
+        let comm = ifaces[0].descriptors().next().unwrap();
+        assert_eq!(0x02, comm.class_code());
+        assert_eq!(&CONFIG[26..35], comm.extra());
+
+        let ep = comm.endpoint_descriptors().next().unwrap();
+        assert_eq!(0x83, ep.address());
+        assert_eq!(TransferType::Interrupt, ep.transfer_type());
+        assert_eq!(None, ep.extra());
+
+        let data = ifaces[1].descriptors().next().unwrap();
+        assert_eq!(1, data.interface_number());
+        let eps: Vec<_> = data
+            .endpoint_descriptors()
+            .map(|ep| (ep.address(), ep.direction(), ep.max_packet_size()))
+            .collect();
+        assert_eq!(
+            vec![(0x01, Direction::Out, 64), (0x82, Direction::In, 64)],
+            eps
+        );
+    }
+
+    #[test]
+    fn parse_config_with_trailing_bytes() {
+        let mut buf = CONFIG.to_vec();
+        buf.extend_from_slice(&CONFIG);
+        let config = ConfigDescriptor::parse(&buf).unwrap();
+        assert_eq!(2, config.num_interfaces());
+    }
+
+    #[test]
+    fn parse_truncated_config() {
+        let buf = &CONFIG[..58];
+        assert!(ConfigDescriptor::parse(buf).is_err());
+
+        let config = ConfigDescriptor::parse_with_mode(buf, ParseMode::Lenient).unwrap();
+        assert_eq!(2, config.num_interfaces());
+        let data = config
+            .interfaces()
+            .nth(1)
+            .unwrap()
+            .descriptors()
+            .next()
+            .unwrap();
+        assert_eq!(1, data.num_endpoints());
+    }
+
+    #[test]
+    fn parse_malformed_config() {
+        // Claims three interfaces
+        let mut buf = CONFIG;
+        buf[4] = 3;
+        assert!(ConfigDescriptor::parse(&buf).is_err());
+        let config = ConfigDescriptor::parse_with_mode(&buf, ParseMode::Lenient).unwrap();
+        assert_eq!(2, config.num_interfaces());
+
+        // A zero-length descriptor in the middle
+        let mut buf = CONFIG;
+        buf[26] = 0;
+        assert!(ConfigDescriptor::parse(&buf).is_err());
+        let config = ConfigDescriptor::parse_with_mode(&buf, ParseMode::Lenient).unwrap();
+        assert_eq!(1, config.num_interfaces());
+
+        assert!(ConfigDescriptor::parse(&DEVICE).is_err());
+    }
+}
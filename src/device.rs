@@ -11,8 +11,8 @@
 //
 
 use crate::{
-    error::usb_result, ConfigDescriptor, Context, DeviceDescriptor, DeviceHandle, Error, Port,
-    Result, Speed,
+    error::usb_result, ConfigDescriptor, Context, DeviceDescriptor, DeviceHandle, Error, Function,
+    Port, Result, Speed,
 };
 use libusb1_sys::*;
 use std::{
@@ -86,6 +86,57 @@ impl Device {
         Ok(unsafe { ConfigDescriptor::from(config.assume_init()) })
     }
 
+    /// Reads the functions of a composite device in a configuration.
+    ///
+    /// These come from the Interface Association Descriptors (IADs) of the
+    /// configuration. When the linked `libusb` supports it (v1.0.27 or
+    /// later), they are retrieved with
+    /// `libusb_get_interface_association_descriptors()`. Otherwise they are
+    /// parsed from the configuration descriptor, like
+    /// [`ConfigDescriptor::functions`].
+    #[cfg(libusb_iad)]
+    pub fn functions(&self, config_index: u8) -> Result<Vec<Function>> {
+        use crate::interface_association::ffi::*;
+
+        let mut iads =
+            mem::MaybeUninit::<*mut libusb_interface_association_descriptor_array>::uninit();
+
+        try_unsafe!(libusb_get_interface_association_descriptors(
+            self.device.as_ptr(),
+            config_index,
+            iads.as_mut_ptr()
+        ));
+
+        unsafe {
+            let iads = iads.assume_init();
+            let mut functions: Vec<Function> = match (*iads).length {
+                n if n > 0 && !(*iads).iad.is_null() => {
+                    std::slice::from_raw_parts((*iads).iad, n as usize)
+                        .iter()
+                        .map(Function::from)
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            libusb_free_interface_association_descriptors(iads);
+            functions.sort_by_key(|f| f.first_interface());
+            Ok(functions)
+        }
+    }
+
+    /// Reads the functions of a composite device in a configuration.
+    ///
+    /// These come from the Interface Association Descriptors (IADs) of the
+    /// configuration. When the linked `libusb` supports it (v1.0.27 or
+    /// later), they are retrieved with
+    /// `libusb_get_interface_association_descriptors()`. Otherwise they are
+    /// parsed from the configuration descriptor, like
+    /// [`ConfigDescriptor::functions`].
+    #[cfg(not(libusb_iad))]
+    pub fn functions(&self, config_index: u8) -> Result<Vec<Function>> {
+        Ok(self.config_descriptor(config_index)?.functions())
+    }
+
     /// Returns the number of the bus that the device is connected to.
     pub fn bus_number(&self) -> u8 {
         unsafe { libusb_get_bus_number(self.device.as_ptr()) }
@@ -10,7 +10,12 @@
 // to those terms.
 //
 
-use crate::fields::Version;
+use crate::{
+    class_code::{ClassCode, ClassTriple},
+    descriptor_parser::{self, ParseMode},
+    fields::Version,
+    Result,
+};
 use libusb1_sys as ffi;
 use std::fmt;
 
@@ -18,6 +23,24 @@ use std::fmt;
 pub struct DeviceDescriptor(ffi::libusb_device_descriptor);
 
 impl DeviceDescriptor {
+    /// Parses a device descriptor from its raw bytes.
+    ///
+    /// This is strict, failing with `Error::BadDescriptor` unless the
+    /// buffer starts with a complete, 18-byte, device descriptor. Any
+    /// bytes after it are ignored.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        Self::parse_with_mode(buf, ParseMode::Strict)
+    }
+
+    /// Parses a device descriptor from its raw bytes, with the specified
+    /// handling of a malformed or truncated descriptor.
+    ///
+    /// In lenient mode, a descriptor truncated to as few as the first 8
+    /// bytes is accepted, with the missing fields set to zero.
+    pub fn parse_with_mode(buf: &[u8], mode: ParseMode) -> Result<Self> {
+        descriptor_parser::parse_device(buf, mode).map(Self)
+    }
+
     /// Returns the device's maximum supported USB version.
     pub fn usb_version(&self) -> Version {
         Version::from_bcd(self.0.bcdUSB)
@@ -67,6 +90,20 @@ impl DeviceDescriptor {
         self.0.bDeviceProtocol
     }
 
+    /// Returns the device's class.
+    pub fn class(&self) -> ClassCode {
+        ClassCode::from_raw(self.0.bDeviceClass)
+    }
+
+    /// Returns the device's class, subclass, and protocol codes.
+    pub fn class_triple(&self) -> ClassTriple {
+        ClassTriple::new(
+            self.0.bDeviceClass,
+            self.0.bDeviceSubClass,
+            self.0.bDeviceProtocol,
+        )
+    }
+
     /// Returns the device's vendor ID.
     pub fn vendor_id(&self) -> u16 {
         self.0.idVendor
@@ -77,6 +114,20 @@ impl DeviceDescriptor {
         self.0.idProduct
     }
 
+    /// Looks up the name of the device's vendor in the embedded `usb.ids`
+    /// database.
+    #[cfg(feature = "usb-ids")]
+    pub fn vendor_name(&self) -> Option<&'static str> {
+        crate::ids::vendor_name(self.0.idVendor)
+    }
+
+    /// Looks up the name of the product in the embedded `usb.ids`
+    /// database.
+    #[cfg(feature = "usb-ids")]
+    pub fn product_name(&self) -> Option<&'static str> {
+        crate::ids::product_name(self.0.idVendor, self.0.idProduct)
+    }
+
     /// Returns the maximum packet size of the device's first endpoint.
     pub fn max_packet_size(&self) -> u8 {
         self.0.bMaxPacketSize0
@@ -89,7 +140,7 @@ impl DeviceDescriptor {
 }
 
 impl fmt::Debug for DeviceDescriptor {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut debug = fmt.debug_struct("DeviceDescriptor");
 
         debug.field("bLength", &self.0.bLength);
@@ -214,6 +265,28 @@ mod test {
         );
     }
 
+    #[cfg(feature = "usb-ids")]
+    #[test]
+    fn it_has_vendor_and_product_names() {
+        let desc = DeviceDescriptor::from(device_descriptor!(idVendor: 0x1d6b, idProduct: 0x0003));
+        assert_eq!(Some("Linux Foundation"), desc.vendor_name());
+        assert_eq!(Some("3.0 root hub"), desc.product_name());
+    }
+
+    #[test]
+    fn it_has_class_triple() {
+        let desc = DeviceDescriptor::from(device_descriptor!(
+            bDeviceClass: 0x09,
+            bDeviceSubClass: 0x00,
+            bDeviceProtocol: 0x02
+        ));
+        assert_eq!(ClassCode::Hub, desc.class());
+        assert_eq!(
+            Some("High Speed Hub (Multiple TTs)"),
+            desc.class_triple().function_name()
+        );
+    }
+
     #[test]
     fn it_has_vendor_id() {
         assert_eq!(
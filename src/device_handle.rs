@@ -11,18 +11,30 @@
 //
 
 use crate::{
-    fields::{request_type, Direction, Recipient, RequestType},
+    bos_descriptor::BosDescriptor,
+    compliance::{self, ComplianceChecker, Finding},
+    descriptor_parser::ParseMode,
+    error::{usb_result, DeviceError, DeviceResult, ErrorTarget},
+    fields::{request_type, Direction, Port, Recipient, RequestType, SetupPacket},
+    instrument::{self, Transfer},
     language::Language,
-    ConfigDescriptor, Context, Device, DeviceDescriptor, Error, InterfaceDescriptor, Result,
+    metrics::{Recorder, TransferMetrics},
+    ms_os_descriptor::{
+        self, ExtendedCompatId, ExtendedProperties, MsOs20DescriptorSet, MsOs20DescriptorSetInfo,
+        MsOsStringDescriptor,
+    },
+    timeout::Timeout,
+    webusb::{self, WebUsbUrl},
+    ConfigDescriptor, Context, Device, DeviceDescriptor, EndpointDescriptor, EndpointRequirements,
+    Error, Function, Interface, InterfaceDescriptor,
 };
 use libusb1_sys::{constants::*, *};
 use std::{
     ffi::CStr,
     fmt::{self, Debug},
     mem,
-    os::raw::{c_char, c_int, c_uchar, c_uint},
-    ptr::NonNull,
-    time::Duration,
+    os::raw::{c_char, c_int, c_uchar},
+    ptr::{self, NonNull},
 };
 
 /// Bit set representing claimed USB interfaces.
@@ -65,7 +77,7 @@ impl ClaimedInterfaces {
     }
 
     /// Returns an iterator over the interfaces in this set.
-    fn iter(&self) -> ClaimedInterfacesIter {
+    fn iter(&self) -> ClaimedInterfacesIter<'_> {
         ClaimedInterfacesIter::new(self)
     }
 }
@@ -84,7 +96,7 @@ struct ClaimedInterfacesIter<'a> {
 
 impl ClaimedInterfacesIter<'_> {
     /// Create a new iterator over the interfaces in `source`.
-    fn new(source: &ClaimedInterfaces) -> ClaimedInterfacesIter {
+    fn new(source: &ClaimedInterfaces) -> ClaimedInterfacesIter<'_> {
         ClaimedInterfacesIter {
             index: 0,
             remaining: source.size(),
@@ -115,11 +127,17 @@ impl<'a> Iterator for ClaimedInterfacesIter<'a> {
 }
 
 /// A handle to an open USB device.
-#[derive(Eq, PartialEq)]
 pub struct DeviceHandle {
     ctx: Context,
     handle: Option<NonNull<libusb_device_handle>>,
     interfaces: ClaimedInterfaces,
+    metrics: Option<Recorder>,
+    auto_detach: bool,
+    // The port and VID:PID of the device, read once when it's opened.
+    port: Option<Port>,
+    id: Option<(u16, u16)>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl DeviceHandle {
@@ -159,7 +177,7 @@ impl DeviceHandle {
         unsafe {
             Device::from_libusb(
                 self.context(),
-                std::ptr::NonNull::new_unchecked(libusb_get_device(self.as_raw())),
+                NonNull::new_unchecked(libusb_get_device(self.as_raw())),
             )
         }
     }
@@ -169,76 +187,172 @@ impl DeviceHandle {
     /// Converts an existing `libusb_device_handle` pointer into a `DeviceHandle`.
     /// `handle` must be a pointer to a valid `libusb_device_handle`. yusb assumes ownership of the handle, and will close it on `drop`.
     pub unsafe fn from_libusb(ctx: Context, handle: NonNull<libusb_device_handle>) -> Self {
-        Self {
+        let device = Device::from_libusb(
+            ctx.clone(),
+            NonNull::new_unchecked(libusb_get_device(handle.as_ptr())),
+        );
+        let id = device
+            .device_descriptor()
+            .ok()
+            .map(|desc| (desc.vendor_id(), desc.product_id()));
+
+        #[allow(unused_mut)]
+        let mut this = Self {
             ctx,
             handle: Some(handle),
             interfaces: ClaimedInterfaces::new(),
+            metrics: None,
+            auto_detach: false,
+            port: device.port().ok(),
+            id,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+        };
+        #[cfg(feature = "tracing")]
+        {
+            this.span = instrument::device_span(&this);
+        }
+        instrument::operation(&this, "open", 0, &Ok(()));
+        this
+    }
+
+    /// Gets the `tracing` span that identifies the device.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Starts collecting statistics of the transfers on this handle.
+    ///
+    /// Once enabled, every control, bulk, and interrupt transfer is counted
+    /// per endpoint, with the number of bytes, errors, timeouts, and the
+    /// latency. This has no effect if the metrics are already enabled.
+    pub fn enable_metrics(&mut self) {
+        if self.metrics.is_none() {
+            self.metrics = Some(Recorder::new());
+        }
+    }
+
+    /// Stops collecting transfer statistics, and discards the values.
+    pub fn disable_metrics(&mut self) {
+        self.metrics = None;
+    }
+
+    /// Gets a snapshot of the transfer statistics.
+    ///
+    /// Returns `None` if the metrics are not enabled.
+    pub fn metrics(&self) -> Option<TransferMetrics> {
+        self.metrics.as_ref().map(Recorder::snapshot)
+    }
+
+    /// Clears the transfer statistics, if enabled, and starts a new
+    /// collection period.
+    pub fn reset_metrics(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.reset();
         }
     }
 
+    /// Gets the transfer statistics recorder, if the metrics are enabled.
+    pub(crate) fn recorder(&self) -> Option<&Recorder> {
+        self.metrics.as_ref()
+    }
+
+    /// Adds the context of the operation and the device to an error.
+    pub(crate) fn error(
+        &self,
+        operation: &'static str,
+        target: ErrorTarget,
+        err: Error,
+    ) -> DeviceError {
+        DeviceError::new(err, operation, target).with_device(self.port.clone(), self.id)
+    }
+
+    /// Gets the port that the device was on when it was opened, if known.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn port(&self) -> Option<&Port> {
+        self.port.as_ref()
+    }
+
+    /// Gets the vendor and product ID of the device, if known.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn id(&self) -> Option<(u16, u16)> {
+        self.id
+    }
+
     /// Returns the active configuration number.
-    pub fn active_configuration(&self) -> Result<u8> {
+    pub fn active_configuration(&self) -> DeviceResult<u8> {
         let mut config = mem::MaybeUninit::<c_int>::uninit();
 
-        try_unsafe!(libusb_get_configuration(self.as_raw(), config.as_mut_ptr()));
+        usb_result(unsafe { libusb_get_configuration(self.as_raw(), config.as_mut_ptr()) })
+            .map_err(|err| self.error("get_configuration", ErrorTarget::Device, err))?;
         Ok(unsafe { config.assume_init() } as u8)
     }
 
     /// Sets the device's active configuration.
-    pub fn set_active_configuration(&mut self, config: u8) -> Result<()> {
-        try_unsafe!(libusb_set_configuration(self.as_raw(), c_int::from(config)));
-        Ok(())
+    pub fn set_active_configuration(&mut self, config: u8) -> DeviceResult<()> {
+        let res =
+            usb_result(unsafe { libusb_set_configuration(self.as_raw(), c_int::from(config)) });
+        instrument::operation(self, "set_configuration", i32::from(config), &res);
+        res.map(|_| ())
+            .map_err(|err| self.error("set_configuration", ErrorTarget::Configuration(config), err))
     }
 
     /// Puts the device in an unconfigured state.
-    pub fn unconfigure(&mut self) -> Result<()> {
-        try_unsafe!(libusb_set_configuration(self.as_raw(), -1));
-        Ok(())
+    pub fn unconfigure(&mut self) -> DeviceResult<()> {
+        let res = usb_result(unsafe { libusb_set_configuration(self.as_raw(), -1) });
+        instrument::operation(self, "set_configuration", -1, &res);
+        res.map(|_| ())
+            .map_err(|err| self.error("unconfigure", ErrorTarget::Device, err))
     }
 
     /// Resets the device.
-    pub fn reset(&mut self) -> Result<()> {
-        try_unsafe!(libusb_reset_device(self.as_raw()));
-        Ok(())
+    pub fn reset(&mut self) -> DeviceResult<()> {
+        let res = usb_result(unsafe { libusb_reset_device(self.as_raw()) });
+        instrument::operation(self, "reset", 0, &res);
+        res.map(|_| ())
+            .map_err(|err| self.error("reset", ErrorTarget::Device, err))
     }
 
     /// Clear the halt/stall condition for an endpoint.
-    pub fn clear_halt(&mut self, endpoint: u8) -> Result<()> {
-        try_unsafe!(libusb_clear_halt(self.as_raw(), endpoint));
-        Ok(())
+    pub fn clear_halt(&mut self, endpoint: u8) -> DeviceResult<()> {
+        let res = usb_result(unsafe { libusb_clear_halt(self.as_raw(), endpoint) });
+        instrument::operation(self, "clear_halt", i32::from(endpoint), &res);
+        res.map(|_| ())
+            .map_err(|err| self.error("clear_halt", ErrorTarget::Endpoint(endpoint), err))
     }
 
     /// Indicates whether the device has an attached kernel driver.
     ///
     /// This method is not supported on all platforms.
-    pub fn kernel_driver_active(&self, iface: u8) -> Result<bool> {
+    pub fn kernel_driver_active(&self, iface: u8) -> DeviceResult<bool> {
         match unsafe { libusb_kernel_driver_active(self.as_raw(), c_int::from(iface)) } {
             0 => Ok(false),
             1 => Ok(true),
-            err => Err(Error::from(err)),
+            err => Err(self.error(
+                "kernel_driver_active",
+                ErrorTarget::Interface(iface),
+                Error::from(err),
+            )),
         }
     }
 
     /// Detaches an attached kernel driver from the device.
     ///
     /// This method is not supported on all platforms.
-    pub fn detach_kernel_driver(&mut self, iface: u8) -> Result<()> {
-        try_unsafe!(libusb_detach_kernel_driver(
-            self.as_raw(),
-            c_int::from(iface)
-        ));
-        Ok(())
+    pub fn detach_kernel_driver(&mut self, iface: u8) -> DeviceResult<()> {
+        usb_result(unsafe { libusb_detach_kernel_driver(self.as_raw(), c_int::from(iface)) })
+            .map(|_| ())
+            .map_err(|err| self.error("detach_kernel_driver", ErrorTarget::Interface(iface), err))
     }
 
     /// Attaches a kernel driver to the device.
     ///
     /// This method is not supported on all platforms.
-    pub fn attach_kernel_driver(&mut self, iface: u8) -> Result<()> {
-        try_unsafe!(libusb_attach_kernel_driver(
-            self.as_raw(),
-            c_int::from(iface)
-        ));
-        Ok(())
+    pub fn attach_kernel_driver(&mut self, iface: u8) -> DeviceResult<()> {
+        usb_result(unsafe { libusb_attach_kernel_driver(self.as_raw(), c_int::from(iface)) })
+            .map(|_| ())
+            .map_err(|err| self.error("attach_kernel_driver", ErrorTarget::Interface(iface), err))
     }
 
     /// Enable/disable automatic kernel driver detachment.
@@ -250,47 +364,138 @@ impl DeviceHandle {
     /// On platforms which do not have support, this function will
     /// return `Error::NotSupported`, and yusb will continue as if
     /// this function was never called.
-    pub fn set_auto_detach_kernel_driver(&mut self, auto_detach: bool) -> Result<()> {
-        try_unsafe!(libusb_set_auto_detach_kernel_driver(
-            self.as_raw(),
-            auto_detach.into()
-        ));
+    pub fn set_auto_detach_kernel_driver(&mut self, auto_detach: bool) -> DeviceResult<()> {
+        usb_result(unsafe {
+            libusb_set_auto_detach_kernel_driver(self.as_raw(), auto_detach.into())
+        })
+        .map_err(|err| self.error("set_auto_detach_kernel_driver", ErrorTarget::Device, err))?;
+        self.auto_detach = auto_detach;
         Ok(())
     }
 
+    /// Determines if automatic kernel driver detachment is enabled, as
+    /// last set with
+    /// [`set_auto_detach_kernel_driver`](Self::set_auto_detach_kernel_driver).
+    pub fn auto_detach_kernel_driver(&self) -> bool {
+        self.auto_detach
+    }
+
     /// Claims one of the device's interfaces.
     ///
     /// An interface must be claimed before operating on it. All claimed interfaces are released
     /// when the device handle goes out of scope.
-    pub fn claim_interface(&mut self, iface: u8) -> Result<()> {
-        try_unsafe!(libusb_claim_interface(self.as_raw(), c_int::from(iface)));
+    pub fn claim_interface(&mut self, iface: u8) -> DeviceResult<()> {
+        let res = usb_result(unsafe { libusb_claim_interface(self.as_raw(), c_int::from(iface)) });
+        instrument::operation(self, "claim_interface", i32::from(iface), &res);
+        res.map_err(|err| self.error("claim_interface", ErrorTarget::Interface(iface), err))?;
         self.interfaces.insert(iface);
         Ok(())
     }
 
     /// Releases a claimed interface.
-    pub fn release_interface(&mut self, iface: u8) -> Result<()> {
-        try_unsafe!(libusb_release_interface(self.as_raw(), c_int::from(iface)));
+    pub fn release_interface(&mut self, iface: u8) -> DeviceResult<()> {
+        let res =
+            usb_result(unsafe { libusb_release_interface(self.as_raw(), c_int::from(iface)) });
+        instrument::operation(self, "release_interface", i32::from(iface), &res);
+        res.map_err(|err| self.error("release_interface", ErrorTarget::Interface(iface), err))?;
         self.interfaces.remove(iface);
         Ok(())
     }
 
+    /// Claims all the interfaces of a function of a composite device.
+    ///
+    /// Interfaces that are already claimed are left as they are. If any of
+    /// the others can't be claimed, the ones that were claimed by this call
+    /// are released again, and the error is returned.
+    pub fn claim_function(&mut self, function: &Function) -> DeviceResult<()> {
+        claim_each(
+            self,
+            function.interfaces(),
+            |handle, iface| handle.interfaces.contains(iface),
+            Self::claim_interface,
+            |handle, iface| {
+                let _ = handle.release_interface(iface);
+            },
+        )
+    }
+
+    /// Releases all the interfaces of a function of a composite device.
+    ///
+    /// This attempts to release every interface in the function, returning
+    /// the first error, if any.
+    pub fn release_function(&mut self, function: &Function) -> DeviceResult<()> {
+        let mut res = Ok(());
+        for iface in function.interfaces() {
+            if let Err(err) = self.release_interface(iface) {
+                if res.is_ok() {
+                    res = Err(err);
+                }
+            }
+        }
+        res
+    }
+
     /// Sets an interface's active setting.
-    pub fn set_alternate_setting(&mut self, iface: u8, setting: u8) -> Result<()> {
-        try_unsafe!(libusb_set_interface_alt_setting(
-            self.as_raw(),
-            c_int::from(iface),
-            c_int::from(setting)
-        ));
-        Ok(())
+    pub fn set_alternate_setting(&mut self, iface: u8, setting: u8) -> DeviceResult<()> {
+        let res = usb_result(unsafe {
+            libusb_set_interface_alt_setting(
+                self.as_raw(),
+                c_int::from(iface),
+                c_int::from(setting),
+            )
+        });
+        instrument::operation(
+            self,
+            "set_alternate_setting",
+            i32::from(iface) << 8 | i32::from(setting),
+            &res,
+        );
+        res.map(|_| ()).map_err(|err| {
+            self.error(
+                "set_alternate_setting",
+                ErrorTarget::AltSetting(iface, setting),
+                err,
+            )
+        })
+    }
+
+    /// Selects the alternate setting of an interface that best meets the
+    /// endpoint requirements, and returns the chosen endpoint.
+    ///
+    /// The setting is picked with [`Interface::best_alt_setting`], which
+    /// prefers the one that uses the least periodic bandwidth. The
+    /// interface should already be claimed. If no setting has a suitable
+    /// endpoint, this returns [`Error::NotFound`].
+    pub fn set_best_alt_setting<'a>(
+        &mut self,
+        iface: &Interface<'a>,
+        requirements: &EndpointRequirements,
+    ) -> DeviceResult<EndpointDescriptor<'a>> {
+        let (setting, endpoint) = iface
+            .best_alt_setting(requirements)
+            .and_then(|setting| {
+                Some((
+                    setting.setting_number(),
+                    setting.find_endpoint(requirements)?,
+                ))
+            })
+            .ok_or_else(|| {
+                self.error(
+                    "set_best_alt_setting",
+                    ErrorTarget::Interface(iface.number()),
+                    Error::NotFound,
+                )
+            })?;
+        self.set_alternate_setting(iface.number(), setting)?;
+        Ok(endpoint)
     }
 
     /// Reads from an interrupt endpoint.
     ///
     /// This function attempts to read from the interrupt endpoint with the address given by the
     /// `endpoint` parameter and fills `buf` with any data received from the endpoint. The function
-    /// blocks up to the amount of time specified by `timeout`. Minimal `timeout` is 1 milliseconds,
-    /// anything smaller will result in an infinite block.
+    /// blocks up to the amount of time specified by `timeout`. It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// If the return value is `Ok(n)`, then `buf` is populated with `n` bytes of data received
     /// from the endpoint.
@@ -308,19 +513,33 @@ impl DeviceHandle {
     /// * `Overflow` if the device offered more data.
     /// * `NoDevice` if the device has been disconnected.
     /// * `Io` if the transfer encountered an I/O error.
-    pub fn read_interrupt(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+    pub fn read_interrupt(
+        &self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
         if endpoint & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_IN {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "read_interrupt",
+                ErrorTarget::Endpoint(endpoint),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("read_interrupt", ErrorTarget::Endpoint(endpoint), err))?;
         let mut transferred = mem::MaybeUninit::<c_int>::uninit();
-        unsafe {
+        let tx = Transfer::begin(self, "read_interrupt", endpoint, buf.len());
+        let res = unsafe {
             match libusb_interrupt_transfer(
                 self.as_raw(),
                 endpoint,
                 buf.as_mut_ptr() as *mut c_uchar,
                 buf.len() as c_int,
                 transferred.as_mut_ptr(),
-                timeout.as_millis() as c_uint,
+                timeout,
             ) {
                 0 => Ok(transferred.assume_init() as usize),
                 err if err == LIBUSB_ERROR_INTERRUPTED => {
@@ -333,15 +552,17 @@ impl DeviceHandle {
                 }
                 err => Err(Error::from(err)),
             }
-        }
+        };
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("read_interrupt", ErrorTarget::Endpoint(endpoint), err))
     }
 
     /// Writes to an interrupt endpoint.
     ///
     /// This function attempts to write the contents of `buf` to the interrupt endpoint with the
     /// address given by the `endpoint` parameter. The function blocks up to the amount of time
-    /// specified by `timeout`. Minimal `timeout` is 1 milliseconds, anything smaller will
-    /// result in an infinite block.
+    /// specified by `timeout`. It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// If the return value is `Ok(n)`, then `n` bytes of `buf` were written to the endpoint.
     ///
@@ -357,19 +578,33 @@ impl DeviceHandle {
     /// * `Pipe` if the endpoint halted.
     /// * `NoDevice` if the device has been disconnected.
     /// * `Io` if the transfer encountered an I/O error.
-    pub fn write_interrupt(&self, endpoint: u8, buf: &[u8], timeout: Duration) -> Result<usize> {
+    pub fn write_interrupt(
+        &self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
         if endpoint & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_OUT {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "write_interrupt",
+                ErrorTarget::Endpoint(endpoint),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("write_interrupt", ErrorTarget::Endpoint(endpoint), err))?;
         let mut transferred = mem::MaybeUninit::<c_int>::uninit();
-        unsafe {
+        let tx = Transfer::begin(self, "write_interrupt", endpoint, buf.len());
+        let res = unsafe {
             match libusb_interrupt_transfer(
                 self.as_raw(),
                 endpoint,
                 buf.as_ptr() as *mut c_uchar,
                 buf.len() as c_int,
                 transferred.as_mut_ptr(),
-                timeout.as_millis() as c_uint,
+                timeout,
             ) {
                 0 => Ok(transferred.assume_init() as usize),
                 err if err == LIBUSB_ERROR_INTERRUPTED => {
@@ -382,15 +617,17 @@ impl DeviceHandle {
                 }
                 err => Err(Error::from(err)),
             }
-        }
+        };
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("write_interrupt", ErrorTarget::Endpoint(endpoint), err))
     }
 
     /// Reads from a bulk endpoint.
     ///
     /// This function attempts to read from the bulk endpoint with the address given by the
     /// `endpoint` parameter and fills `buf` with any data received from the endpoint. The function
-    /// blocks up to the amount of time specified by `timeout`. Minimal `timeout` is 1 milliseconds,
-    /// anything smaller will result in an infinite block.
+    /// blocks up to the amount of time specified by `timeout`. It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// If the return value is `Ok(n)`, then `buf` is populated with `n` bytes of data received
     /// from the endpoint.
@@ -408,19 +645,33 @@ impl DeviceHandle {
     /// * `Overflow` if the device offered more data.
     /// * `NoDevice` if the device has been disconnected.
     /// * `Io` if the transfer encountered an I/O error.
-    pub fn read_bulk(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+    pub fn read_bulk(
+        &self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
         if endpoint & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_IN {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "read_bulk",
+                ErrorTarget::Endpoint(endpoint),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("read_bulk", ErrorTarget::Endpoint(endpoint), err))?;
         let mut transferred = mem::MaybeUninit::<c_int>::uninit();
-        unsafe {
+        let tx = Transfer::begin(self, "read_bulk", endpoint, buf.len());
+        let res = unsafe {
             match libusb_bulk_transfer(
                 self.as_raw(),
                 endpoint,
                 buf.as_mut_ptr() as *mut c_uchar,
                 buf.len() as c_int,
                 transferred.as_mut_ptr(),
-                timeout.as_millis() as c_uint,
+                timeout,
             ) {
                 0 => Ok(transferred.assume_init() as usize),
                 err if err == LIBUSB_ERROR_INTERRUPTED || err == LIBUSB_ERROR_TIMEOUT => {
@@ -433,15 +684,17 @@ impl DeviceHandle {
                 }
                 err => Err(Error::from(err)),
             }
-        }
+        };
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("read_bulk", ErrorTarget::Endpoint(endpoint), err))
     }
 
     /// Writes to a bulk endpoint.
     ///
     /// This function attempts to write the contents of `buf` to the bulk endpoint with the address
     /// given by the `endpoint` parameter. The function blocks up to the amount of time specified
-    /// by `timeout`. Minimal `timeout` is 1 milliseconds, anything smaller will result in an
-    /// infinite block.
+    /// by `timeout`. It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// If the return value is `Ok(n)`, then `n` bytes of `buf` were written to the endpoint.
     ///
@@ -457,19 +710,33 @@ impl DeviceHandle {
     /// * `Pipe` if the endpoint halted.
     /// * `NoDevice` if the device has been disconnected.
     /// * `Io` if the transfer encountered an I/O error.
-    pub fn write_bulk(&self, endpoint: u8, buf: &[u8], timeout: Duration) -> Result<usize> {
+    pub fn write_bulk(
+        &self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
         if endpoint & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_OUT {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "write_bulk",
+                ErrorTarget::Endpoint(endpoint),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("write_bulk", ErrorTarget::Endpoint(endpoint), err))?;
         let mut transferred = mem::MaybeUninit::<c_int>::uninit();
-        unsafe {
+        let tx = Transfer::begin(self, "write_bulk", endpoint, buf.len());
+        let res = unsafe {
             match libusb_bulk_transfer(
                 self.as_raw(),
                 endpoint,
                 buf.as_ptr() as *mut c_uchar,
                 buf.len() as c_int,
                 transferred.as_mut_ptr(),
-                timeout.as_millis() as c_uint,
+                timeout,
             ) {
                 0 => Ok(transferred.assume_init() as usize),
                 err if err == LIBUSB_ERROR_INTERRUPTED || err == LIBUSB_ERROR_TIMEOUT => {
@@ -482,15 +749,89 @@ impl DeviceHandle {
                 }
                 err => Err(Error::from(err)),
             }
+        };
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("write_bulk", ErrorTarget::Endpoint(endpoint), err))
+    }
+
+    /// Reads from a bulk endpoint until the buffer is full.
+    ///
+    /// This makes as many calls to [`read_bulk`](#method.read_bulk) as
+    /// needed to fill `buf`, with the `timeout` applying to the whole
+    /// operation, rather than to each of the individual transfers.
+    ///
+    /// A short packet from the device ends the transfer early, so the
+    /// return value is the total number of bytes read, which may be less
+    /// than the size of the buffer.
+    ///
+    /// If an error occurs, the number of bytes that were read into `buf`
+    /// before it is available from [`DeviceError::transferred`].
+    pub fn read_bulk_exact(
+        &self,
+        endpoint: u8,
+        buf: &mut [u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
+        let timeout = timeout.into().to_deadline();
+        let max_packet_size = self.max_packet_size(endpoint);
+        let mut n = 0;
+
+        while n < buf.len() {
+            let len = self
+                .read_bulk(endpoint, &mut buf[n..], timeout)
+                .map_err(|err| err.with_transferred(n))?;
+            n += len;
+            if len == 0 || max_packet_size.map_or(false, |sz| len % sz != 0) {
+                break;
+            }
+        }
+        Ok(n)
+    }
+
+    /// Writes the whole buffer to a bulk endpoint.
+    ///
+    /// This makes as many calls to [`write_bulk`](#method.write_bulk) as
+    /// needed to send all of `buf`, with the `timeout` applying to the
+    /// whole operation, rather than to each of the individual transfers.
+    ///
+    /// If an error occurs, the number of bytes that were written before it
+    /// is available from [`DeviceError::transferred`].
+    pub fn write_bulk_all(
+        &self,
+        endpoint: u8,
+        buf: &[u8],
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<()> {
+        let timeout = timeout.into().to_deadline();
+        let mut n = 0;
+
+        while n < buf.len() {
+            n += self
+                .write_bulk(endpoint, &buf[n..], timeout)
+                .map_err(|err| err.with_transferred(n))?;
         }
+        Ok(())
+    }
+
+    /// Gets the maximum packet size of an endpoint in the active
+    /// configuration, if it can be found.
+    fn max_packet_size(&self, endpoint: u8) -> Option<usize> {
+        let config = self.device().active_config_descriptor().ok()?;
+        config
+            .interfaces()
+            .flat_map(|iface| iface.descriptors())
+            .flat_map(|desc| desc.endpoint_descriptors())
+            .find(|ep| ep.address() == endpoint)
+            .map(|ep| usize::from(ep.max_packet_size() & 0x07FF))
+            .filter(|&sz| sz != 0)
     }
 
     /// Reads data using a control transfer.
     ///
     /// This function attempts to read data from the device using a control transfer and fills
     /// `buf` with any data received during the transfer. The function blocks up to the amount of
-    /// time specified by `timeout`. Minimal `timeout` is 1 milliseconds, anything smaller will
-    /// result in an infinite block.
+    /// time specified by `timeout`. It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// The parameters `request_type`, `request`, `value`, and `index` specify the fields of the
     /// control transfer setup packet (`bmRequestType`, `bRequest`, `wValue`, and `wIndex`
@@ -520,11 +861,35 @@ impl DeviceHandle {
         value: u16,
         index: u16,
         buf: &mut [u8],
-        timeout: Duration,
-    ) -> Result<usize> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
+        let setup = SetupPacket {
+            request_type,
+            request,
+            value,
+            index,
+            length: buf.len() as u16,
+        };
         if request_type & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_IN {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "read_control",
+                ErrorTarget::Control(setup),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("read_control", ErrorTarget::Control(setup), err))?;
+        let tx = Transfer::control(
+            self,
+            "read_control",
+            request_type,
+            request,
+            value,
+            index,
+            buf.len(),
+        );
         let res = unsafe {
             libusb_control_transfer(
                 self.as_raw(),
@@ -534,22 +899,21 @@ impl DeviceHandle {
                 index,
                 buf.as_mut_ptr() as *mut c_uchar,
                 buf.len() as u16,
-                timeout.as_millis() as c_uint,
+                timeout,
             )
         };
 
-        if res < 0 {
-            Err(Error::from(res))
-        } else {
-            Ok(res as usize)
-        }
+        let res = usb_result(res);
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("read_control", ErrorTarget::Control(setup), err))
     }
 
     /// Writes data using a control transfer.
     ///
     /// This function attempts to write the contents of `buf` to the device using a control
     /// transfer. The function blocks up to the amount of time specified by `timeout`.
-    /// Minimal `timeout` is 1 milliseconds, anything smaller will result in an infinite block.
+    /// It can be given as a
+    /// `Duration`, a deadline `Instant`, or [`Timeout::Infinite`](crate::Timeout::Infinite).
     ///
     /// The parameters `request_type`, `request`, `value`, and `index` specify the fields of the
     /// control transfer setup packet (`bmRequestType`, `bRequest`, `wValue`, and `wIndex`
@@ -579,11 +943,35 @@ impl DeviceHandle {
         value: u16,
         index: u16,
         buf: &[u8],
-        timeout: Duration,
-    ) -> Result<usize> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<usize> {
+        let setup = SetupPacket {
+            request_type,
+            request,
+            value,
+            index,
+            length: buf.len() as u16,
+        };
         if request_type & LIBUSB_ENDPOINT_DIR_MASK != LIBUSB_ENDPOINT_OUT {
-            return Err(Error::InvalidParam);
+            return Err(self.error(
+                "write_control",
+                ErrorTarget::Control(setup),
+                Error::InvalidParam,
+            ));
         }
+        let timeout = timeout
+            .into()
+            .as_libusb_millis()
+            .map_err(|err| self.error("write_control", ErrorTarget::Control(setup), err))?;
+        let tx = Transfer::control(
+            self,
+            "write_control",
+            request_type,
+            request,
+            value,
+            index,
+            buf.len(),
+        );
         let res = unsafe {
             libusb_control_transfer(
                 self.as_raw(),
@@ -593,35 +981,369 @@ impl DeviceHandle {
                 index,
                 buf.as_ptr() as *mut c_uchar,
                 buf.len() as u16,
-                timeout.as_millis() as c_uint,
+                timeout,
             )
         };
 
-        if res < 0 {
-            Err(Error::from(res))
-        } else {
-            Ok(res as usize)
+        let res = usb_result(res);
+        tx.end(&res, buf);
+        res.map_err(|err| self.error("write_control", ErrorTarget::Control(setup), err))
+    }
+
+    /// Reads the Binary device Object Store (BOS) descriptor of the device.
+    ///
+    /// This is available from devices with a USB version of 2.01 or later,
+    /// and describes the capabilities of the device. It returns `Pipe` or
+    /// `NotFound` if the device doesn't have one.
+    pub fn bos_descriptor(&self) -> DeviceResult<BosDescriptor> {
+        let mut bos = ptr::null();
+        let res = usb_result(unsafe { libusb_get_bos_descriptor(self.as_raw(), &mut bos) });
+        instrument::operation(self, "get_bos_descriptor", 0, &res);
+        res.map_err(|err| self.error("get_bos_descriptor", ErrorTarget::Device, err))?;
+
+        unsafe {
+            let desc = BosDescriptor::from_libusb(bos);
+            libusb_free_bos_descriptor(bos as *mut _);
+            Ok(desc)
         }
     }
 
+    /// Reads the raw descriptors of the device, and checks them for
+    /// compliance with the USB specification.
+    ///
+    /// This reads the device descriptor and every configuration with
+    /// standard `GET_DESCRIPTOR` requests, so that their framing can be
+    /// checked, then tries to read every string that they refer to. The
+    /// `timeout` applies to reading all of the descriptors, and separately
+    /// to each of the strings.
+    ///
+    /// A string is only reported as missing if the device stalls the
+    /// request for it, or returns something that isn't a string. If the
+    /// languages can't be read for any other reason, the strings aren't
+    /// checked, and a string that can't be read is reported as info.
+    ///
+    /// See the [`compliance`](crate::compliance) module for the checks.
+    pub fn check_compliance(&self, timeout: impl Into<Timeout>) -> DeviceResult<Vec<Finding>> {
+        let timeout = timeout.into();
+        let deadline = timeout.to_deadline();
+
+        let device = self.read_raw_descriptor(LIBUSB_DT_DEVICE, 0, 18, deadline)?;
+        let num_configs = match device.get(17) {
+            Some(&n) => n,
+            None => self
+                .device()
+                .device_descriptor()
+                .map_err(|err| self.error("check_compliance", ErrorTarget::Device, err))?
+                .num_configurations(),
+        };
+
+        let mut configs = Vec::new();
+        for i in 0..num_configs {
+            let header = self.read_raw_descriptor(LIBUSB_DT_CONFIG, i, 9, deadline)?;
+            let total = match *header {
+                [_, _, lo, hi, ..] => u16::from_le_bytes([lo, hi]).max(9),
+                _ => 9,
+            };
+            configs.push(self.read_raw_descriptor(LIBUSB_DT_CONFIG, i, total, deadline)?);
+        }
+
+        let is_missing =
+            |err: &DeviceError| matches!(err.kind(), Error::Pipe | Error::BadDescriptor);
+        let mut checker = ComplianceChecker::new(self.device().speed());
+
+        // A device without any strings has no languages.
+        let language = match self.read_languages(timeout) {
+            Ok(langs) => Ok(langs.first().copied()),
+            Err(err) if is_missing(&err) => Ok(None),
+            Err(err) => Err(err),
+        };
+
+        if let (Ok(language), Ok(desc)) = (
+            language,
+            DeviceDescriptor::parse_with_mode(&device, ParseMode::Lenient),
+        ) {
+            let parsed: Vec<_> = configs
+                .iter()
+                .filter_map(|buf| ConfigDescriptor::parse_with_mode(buf, ParseMode::Lenient).ok())
+                .collect();
+            let parsed: Vec<_> = parsed.iter().collect();
+
+            // A string is available if it can be read in the first language.
+            let mut available = Vec::new();
+            let mut unreadable = Vec::new();
+            if let Some(language) = language {
+                for (index, _) in compliance::string_references(&desc, &parsed) {
+                    if available.contains(&index) || unreadable.contains(&index) {
+                        continue;
+                    }
+                    match self.read_string_descriptor(language, index, timeout) {
+                        Ok(_) => available.push(index),
+                        Err(err) if is_missing(&err) => {}
+                        Err(_) => unreadable.push(index),
+                    }
+                }
+            }
+            checker
+                .available_strings(available)
+                .unreadable_strings(unreadable);
+        }
+
+        let configs: Vec<_> = configs.iter().map(Vec::as_slice).collect();
+        Ok(checker.check_bytes(&device, &configs))
+    }
+
+    /// Reads up to `len` bytes of a raw standard descriptor.
+    fn read_raw_descriptor(
+        &self,
+        desc_type: u8,
+        index: u8,
+        len: u16,
+        timeout: Timeout,
+    ) -> DeviceResult<Vec<u8>> {
+        let mut buf = vec![0u8; usize::from(len)];
+        let n = self.read_control(
+            request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            LIBUSB_REQUEST_GET_DESCRIPTOR,
+            u16::from(desc_type) << 8 | u16::from(index),
+            0,
+            &mut buf,
+            timeout,
+        )?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    /// Reads the Microsoft OS 1.0 string descriptor, at string index 0xEE.
+    ///
+    /// This gives the vendor code to read the other MS OS 1.0 descriptors.
+    /// Returns `Error::NotFound` if the device has a string at that index
+    /// which isn't the MS OS descriptor. Many devices without one will
+    /// stall the request, giving `Error::Pipe`.
+    pub fn read_ms_os_string(
+        &self,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<MsOsStringDescriptor> {
+        let mut buf = [0u8; 18];
+
+        let len = self.read_control(
+            request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            LIBUSB_REQUEST_GET_DESCRIPTOR,
+            u16::from(LIBUSB_DT_STRING) << 8 | u16::from(ms_os_descriptor::MS_OS_STRING_INDEX),
+            0,
+            &mut buf,
+            timeout,
+        )?;
+
+        MsOsStringDescriptor::parse(&buf[..len])
+            .map_err(|err| self.error("read_ms_os_string", ErrorTarget::Device, err))
+    }
+
+    /// Reads the Microsoft OS 1.0 Extended Compat ID descriptor, using the
+    /// vendor code from the MS OS string descriptor.
+    ///
+    /// The `timeout` applies to the whole operation, which takes two
+    /// requests.
+    pub fn read_ms_extended_compat_id(
+        &self,
+        vendor_code: u8,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<ExtendedCompatId> {
+        let buf = self.read_ms_os_10_descriptor(
+            "read_ms_extended_compat_id",
+            Recipient::Device,
+            vendor_code,
+            0,
+            ms_os_descriptor::EXTENDED_COMPAT_ID_INDEX,
+            ms_os_descriptor::EXTENDED_COMPAT_ID_HEADER_SIZE,
+            timeout,
+        )?;
+        ExtendedCompatId::parse(&buf)
+            .map_err(|err| self.error("read_ms_extended_compat_id", ErrorTarget::Device, err))
+    }
+
+    /// Reads the Microsoft OS 1.0 Extended Properties descriptor of an
+    /// interface, using the vendor code from the MS OS string descriptor.
+    ///
+    /// The `timeout` applies to the whole operation, which takes two
+    /// requests.
+    pub fn read_ms_extended_properties(
+        &self,
+        vendor_code: u8,
+        iface: u8,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<ExtendedProperties> {
+        let buf = self.read_ms_os_10_descriptor(
+            "read_ms_extended_properties",
+            Recipient::Interface,
+            vendor_code,
+            u16::from(iface),
+            ms_os_descriptor::EXTENDED_PROPERTIES_INDEX,
+            ms_os_descriptor::EXTENDED_PROPERTIES_HEADER_SIZE,
+            timeout,
+        )?;
+        ExtendedProperties::parse(&buf).map_err(|err| {
+            self.error(
+                "read_ms_extended_properties",
+                ErrorTarget::Interface(iface),
+                err,
+            )
+        })
+    }
+
+    /// Reads an MS OS 1.0 feature descriptor with a vendor request.
+    ///
+    /// This reads the header first, to get the total length from its
+    /// `dwLength` field, and then the whole descriptor.
+    #[allow(clippy::too_many_arguments)]
+    fn read_ms_os_10_descriptor(
+        &self,
+        operation: &'static str,
+        recipient: Recipient,
+        vendor_code: u8,
+        value: u16,
+        index: u16,
+        header_size: usize,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<Vec<u8>> {
+        let timeout = timeout.into().to_deadline();
+        let req_type = request_type(Direction::In, RequestType::Vendor, recipient);
+        let setup = SetupPacket {
+            request_type: req_type,
+            request: vendor_code,
+            value,
+            index,
+            length: header_size as u16,
+        };
+        let bad_descriptor =
+            || self.error(operation, ErrorTarget::Control(setup), Error::BadDescriptor);
+
+        let mut buf = vec![0u8; header_size];
+        let len = self.read_control(req_type, vendor_code, value, index, &mut buf, timeout)?;
+        if len < header_size {
+            return Err(bad_descriptor());
+        }
+
+        let total_len = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        if total_len < header_size || total_len > usize::from(u16::MAX) {
+            return Err(bad_descriptor());
+        }
+
+        buf.resize(total_len, 0);
+        let len = self.read_control(req_type, vendor_code, value, index, &mut buf, timeout)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Reads the Microsoft OS 2.0 descriptor set.
+    ///
+    /// This finds the MS OS 2.0 platform capability in the BOS descriptor,
+    /// and reads the first descriptor set that it describes. Returns
+    /// `Error::NotFound` if the device doesn't have the capability.
+    pub fn read_ms_os_20_descriptors(
+        &self,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<MsOs20DescriptorSet> {
+        let info = match self.bos_descriptor()?.ms_os_20_descriptor_sets().first() {
+            Some(info) => *info,
+            None => {
+                return Err(self.error(
+                    "read_ms_os_20_descriptors",
+                    ErrorTarget::Device,
+                    Error::NotFound,
+                ))
+            }
+        };
+        self.read_ms_os_20_descriptor_set(&info, timeout)
+    }
+
+    /// Reads a Microsoft OS 2.0 descriptor set, as described by the MS OS
+    /// 2.0 platform capability in the BOS descriptor.
+    pub fn read_ms_os_20_descriptor_set(
+        &self,
+        info: &MsOs20DescriptorSetInfo,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<MsOs20DescriptorSet> {
+        let mut buf = vec![0u8; usize::from(info.total_length())];
+
+        let len = self.read_control(
+            request_type(Direction::In, RequestType::Vendor, Recipient::Device),
+            info.vendor_code(),
+            0,
+            ms_os_descriptor::MS_OS_20_DESCRIPTOR_INDEX,
+            &mut buf,
+            timeout,
+        )?;
+
+        MsOs20DescriptorSet::parse(&buf[..len])
+            .map_err(|err| self.error("read_ms_os_20_descriptor_set", ErrorTarget::Device, err))
+    }
+
+    /// Reads the landing page URL of a WebUSB device.
+    ///
+    /// This finds the WebUSB platform capability in the BOS descriptor, and
+    /// then reads the landing page URL descriptor with the vendor `GET_URL`
+    /// request. Returns `Error::NotFound` if the device doesn't have the
+    /// capability, or doesn't have a landing page.
+    pub fn read_webusb_url(&self, timeout: impl Into<Timeout>) -> DeviceResult<WebUsbUrl> {
+        let not_found = || self.error("read_webusb_url", ErrorTarget::Device, Error::NotFound);
+
+        let cap = self.bos_descriptor()?.webusb().ok_or_else(not_found)?;
+        let index = cap.landing_page_index().ok_or_else(not_found)?;
+        self.read_webusb_url_descriptor(cap.vendor_code(), index, timeout)
+    }
+
+    /// Reads a WebUSB URL descriptor with the vendor `GET_URL` request,
+    /// using the vendor code from the WebUSB platform capability.
+    pub fn read_webusb_url_descriptor(
+        &self,
+        vendor_code: u8,
+        index: u8,
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<WebUsbUrl> {
+        let mut buf = [0u8; 255];
+
+        let len = self.read_control(
+            request_type(Direction::In, RequestType::Vendor, Recipient::Device),
+            vendor_code,
+            u16::from(index),
+            webusb::WEBUSB_GET_URL,
+            &mut buf,
+            timeout,
+        )?;
+
+        WebUsbUrl::parse(&buf[..len])
+            .map_err(|err| self.error("read_webusb_url", ErrorTarget::Device, err))
+    }
+
     /// Reads the languages supported by the device's string descriptors.
     ///
     /// This function returns a list of languages that can be used to read the device's string
     /// descriptors.
-    pub fn read_languages(&self, timeout: Duration) -> Result<Vec<Language>> {
+    pub fn read_languages(&self, timeout: impl Into<Timeout>) -> DeviceResult<Vec<Language>> {
         let mut buf = [0u8; 255];
+        let setup = SetupPacket {
+            request_type: request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            request: LIBUSB_REQUEST_GET_DESCRIPTOR,
+            value: u16::from(LIBUSB_DT_STRING) << 8,
+            index: 0,
+            length: buf.len() as u16,
+        };
 
         let len = self.read_control(
-            request_type(Direction::In, RequestType::Standard, Recipient::Device),
-            LIBUSB_REQUEST_GET_DESCRIPTOR,
-            u16::from(LIBUSB_DT_STRING) << 8,
-            0,
+            setup.request_type,
+            setup.request,
+            setup.value,
+            setup.index,
             &mut buf,
             timeout,
         )?;
 
         if len < 2 || buf[0] != len as u8 || len & 0x01 != 0 {
-            return Err(Error::BadDescriptor);
+            return Err(self.error(
+                "read_languages",
+                ErrorTarget::Control(setup),
+                Error::BadDescriptor,
+            ));
         }
 
         if len == 2 {
@@ -639,18 +1361,44 @@ impl DeviceHandle {
     }
 
     /// Reads an ASCII string descriptor from the device.
-    pub fn read_string_descriptor_ascii(&self, index: u8) -> Result<String> {
+    pub fn read_string_descriptor_ascii(&self, index: u8) -> DeviceResult<String> {
         let mut buf = [0u8; 128];
 
         let ptr = buf.as_mut_ptr().cast::<c_uchar>();
         let len = buf.len() as i32;
 
-        unsafe {
-            match libusb_get_string_descriptor_ascii(self.as_raw(), index, ptr, len) {
-                res if res < 0 => Err(Error::from(res)),
-                _ => Ok(CStr::from_ptr(ptr.cast::<c_char>()).to_str()?.to_string()),
-            }
-        }
+        let setup = SetupPacket {
+            request_type: request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            request: LIBUSB_REQUEST_GET_DESCRIPTOR,
+            value: u16::from(LIBUSB_DT_STRING) << 8 | u16::from(index),
+            index: 0,
+            length: buf.len() as u16,
+        };
+        let err = |err| {
+            self.error(
+                "read_string_descriptor_ascii",
+                ErrorTarget::Control(setup),
+                err,
+            )
+        };
+
+        let tx = Transfer::control(
+            self,
+            "read_string_descriptor_ascii",
+            setup.request_type,
+            setup.request,
+            setup.value,
+            setup.index,
+            buf.len(),
+        );
+        let res = usb_result(unsafe {
+            libusb_get_string_descriptor_ascii(self.as_raw(), index, ptr, len)
+        });
+        tx.end(&res, &buf);
+        res.map_err(err)?;
+
+        let s = unsafe { CStr::from_ptr(ptr.cast::<c_char>()) };
+        Ok(s.to_str().map_err(|e| err(e.into()))?.to_string())
     }
 
     /// Reads a string descriptor from the device.
@@ -660,9 +1408,16 @@ impl DeviceHandle {
         &self,
         language: Language,
         index: u8,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         let mut buf = [0u16; 128];
+        let setup = SetupPacket {
+            request_type: request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            request: LIBUSB_REQUEST_GET_DESCRIPTOR,
+            value: u16::from(LIBUSB_DT_STRING) << 8 | u16::from(index),
+            index: language.lang_id(),
+            length: 255,
+        };
 
         let len = {
             // SAFETY: since we create slice from existing slice pointer valid
@@ -676,16 +1431,20 @@ impl DeviceHandle {
             };
 
             let len = self.read_control(
-                request_type(Direction::In, RequestType::Standard, Recipient::Device),
-                LIBUSB_REQUEST_GET_DESCRIPTOR,
-                u16::from(LIBUSB_DT_STRING) << 8 | u16::from(index),
-                language.lang_id(),
+                setup.request_type,
+                setup.request,
+                setup.value,
+                setup.index,
                 buf,
                 timeout,
             )?;
 
             if len < 2 || buf[0] != len as u8 || len & 0x01 != 0 {
-                return Err(Error::BadDescriptor);
+                return Err(self.error(
+                    "read_string_descriptor",
+                    ErrorTarget::Control(setup),
+                    Error::BadDescriptor,
+                ));
             }
 
             // Length in # chars
@@ -696,15 +1455,44 @@ impl DeviceHandle {
         if len == 1 {
             Ok(String::new())
         } else {
-            let sbuf = String::from_utf16(&buf[1..len])?;
+            let sbuf = String::from_utf16(&buf[1..len]).map_err(|err| {
+                self.error(
+                    "read_string_descriptor",
+                    ErrorTarget::Control(setup),
+                    err.into(),
+                )
+            })?;
             Ok(sbuf.trim_end_matches('\0').to_string())
         }
     }
 
+    /// Reads a string descriptor in the first language supported by the
+    /// device.
+    ///
+    /// This reads the supported languages and then the string, with the
+    /// `timeout` applying to the whole operation.
+    pub fn read_string(&self, index: u8, timeout: impl Into<Timeout>) -> DeviceResult<String> {
+        let timeout = timeout.into().to_deadline();
+        let language = match self.read_languages(timeout)?.first() {
+            Some(lang) => *lang,
+            None => {
+                return Err(self.error("read_string", ErrorTarget::Device, Error::NotFound));
+            }
+        };
+        self.read_string_descriptor(language, index, timeout)
+    }
+
     /// Reads the device's manufacturer string descriptor (ascii).
-    pub fn read_manufacturer_string_ascii(&self, device: &DeviceDescriptor) -> Result<String> {
+    pub fn read_manufacturer_string_ascii(
+        &self,
+        device: &DeviceDescriptor,
+    ) -> DeviceResult<String> {
         match device.manufacturer_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_manufacturer_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor_ascii(n),
         }
     }
@@ -714,18 +1502,26 @@ impl DeviceHandle {
         &self,
         language: Language,
         device: &DeviceDescriptor,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         match device.manufacturer_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_manufacturer_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor(language, n, timeout),
         }
     }
 
     /// Reads the device's product string descriptor (ascii).
-    pub fn read_product_string_ascii(&self, device: &DeviceDescriptor) -> Result<String> {
+    pub fn read_product_string_ascii(&self, device: &DeviceDescriptor) -> DeviceResult<String> {
         match device.product_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_product_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor_ascii(n),
         }
     }
@@ -735,18 +1531,29 @@ impl DeviceHandle {
         &self,
         language: Language,
         device: &DeviceDescriptor,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         match device.product_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_product_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor(language, n, timeout),
         }
     }
 
     /// Reads the device's serial number string descriptor (ascii).
-    pub fn read_serial_number_string_ascii(&self, device: &DeviceDescriptor) -> Result<String> {
+    pub fn read_serial_number_string_ascii(
+        &self,
+        device: &DeviceDescriptor,
+    ) -> DeviceResult<String> {
         match device.serial_number_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_serial_number_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor_ascii(n),
         }
     }
@@ -756,10 +1563,14 @@ impl DeviceHandle {
         &self,
         language: Language,
         device: &DeviceDescriptor,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         match device.serial_number_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_serial_number_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor(language, n, timeout),
         }
     }
@@ -769,10 +1580,14 @@ impl DeviceHandle {
         &self,
         language: Language,
         configuration: &ConfigDescriptor,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         match configuration.description_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_configuration_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor(language, n, timeout),
         }
     }
@@ -782,10 +1597,14 @@ impl DeviceHandle {
         &self,
         language: Language,
         interface: &InterfaceDescriptor,
-        timeout: Duration,
-    ) -> Result<String> {
+        timeout: impl Into<Timeout>,
+    ) -> DeviceResult<String> {
         match interface.description_string_index() {
-            None => Err(Error::InvalidParam),
+            None => Err(self.error(
+                "read_interface_string",
+                ErrorTarget::Device,
+                Error::InvalidParam,
+            )),
             Some(n) => self.read_string_descriptor(language, n, timeout),
         }
     }
@@ -795,11 +1614,13 @@ impl Drop for DeviceHandle {
     /// Closes the device.
     fn drop(&mut self) {
         unsafe {
-            for iface in self.interfaces.iter() {
-                libusb_release_interface(self.as_raw(), iface as c_int);
-            }
-
             if let Some(handle) = self.handle {
+                for iface in self.interfaces.iter() {
+                    let res = usb_result(libusb_release_interface(handle.as_ptr(), iface as c_int));
+                    instrument::operation(self, "release_interface", i32::from(iface), &res);
+                }
+
+                instrument::operation(self, "close", 0, &Ok(()));
                 libusb_close(handle.as_ptr());
             }
         }
@@ -809,6 +1630,14 @@ impl Drop for DeviceHandle {
 unsafe impl Send for DeviceHandle {}
 unsafe impl Sync for DeviceHandle {}
 
+impl PartialEq for DeviceHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.ctx == other.ctx && self.handle == other.handle && self.interfaces == other.interfaces
+    }
+}
+
+impl Eq for DeviceHandle {}
+
 impl Debug for DeviceHandle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("DeviceHandle")
@@ -819,12 +1648,38 @@ impl Debug for DeviceHandle {
     }
 }
 
+/// Claims each of the interfaces that isn't claimed yet.
+///
+/// If one can't be claimed, the ones that were claimed by this call are
+/// released, and the error is returned.
+fn claim_each<T, E>(
+    target: &mut T,
+    ifaces: impl IntoIterator<Item = u8>,
+    is_claimed: impl Fn(&T, u8) -> bool,
+    claim: impl Fn(&mut T, u8) -> Result<(), E>,
+    release: impl Fn(&mut T, u8),
+) -> Result<(), E> {
+    let mut claimed = ClaimedInterfaces::new();
+    for iface in ifaces {
+        if is_claimed(target, iface) {
+            continue;
+        }
+        if let Err(err) = claim(target, iface) {
+            for iface in claimed.iter() {
+                release(target, iface);
+            }
+            return Err(err);
+        }
+        claimed.insert(iface);
+    }
+    Ok(())
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod tests {
-    use super::ClaimedInterfaces;
-    use std::u8;
+    use super::{claim_each, ClaimedInterfaces};
 
     #[test]
     fn claimed_interfaces_empty() {
@@ -883,8 +1738,11 @@ mod tests {
         }
 
         // Validate iter().
-        let contents = interfaces.iter().collect::<Vec<_>>().sort();
-        assert_eq!(contents, elements.clone().sort());
+        let mut contents = interfaces.iter().collect::<Vec<_>>();
+        contents.sort();
+        let mut sorted = elements.clone();
+        sorted.sort();
+        assert_eq!(contents, sorted);
 
         // Validate size_hint().
         let mut iter = interfaces.iter();
@@ -904,4 +1762,52 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn claim_each_rolls_back_only_new_claims() {
+        // The interfaces that are claimed, and the one that can't be.
+        struct Mock {
+            claimed: ClaimedInterfaces,
+            busy: u8,
+        }
+
+        let mut mock = Mock {
+            claimed: ClaimedInterfaces::new(),
+            busy: 4,
+        };
+        mock.claimed.insert(2);
+
+        let res = claim_each(
+            &mut mock,
+            1..=4,
+            |m, iface| m.claimed.contains(iface),
+            |m, iface| {
+                if iface == m.busy {
+                    return Err(iface);
+                }
+                m.claimed.insert(iface);
+                Ok(())
+            },
+            |m, iface| m.claimed.remove(iface),
+        );
+
+        assert_eq!(Err(4), res);
+        assert_eq!(vec![2], mock.claimed.iter().collect::<Vec<_>>());
+
+        mock.busy = 0;
+        assert_eq!(
+            Ok(()),
+            claim_each(
+                &mut mock,
+                1..=4,
+                |m, iface| m.claimed.contains(iface),
+                |m, iface| {
+                    m.claimed.insert(iface);
+                    Ok::<_, u8>(())
+                },
+                |m, iface| m.claimed.remove(iface),
+            )
+        );
+        assert_eq!(4, mock.claimed.size());
+    }
 }
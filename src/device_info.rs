@@ -0,0 +1,504 @@
+// yusb/src/device_info.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Owned snapshots of a device and its full descriptor tree.
+//!
+//! The usual descriptor types borrow memory from `libusb`, which makes
+//! them awkward to keep around or to export. These types copy everything
+//! out into plain data, which can be stored, compared, and, with the
+//! `serde` feature, serialized.
+
+use crate::{
+    descriptor_parser::{self, ParseMode},
+    ConfigDescriptor, Device, DeviceDescriptor, DeviceHandle, DeviceResult, EndpointDescriptor,
+    ErrorTarget, Function, InterfaceDescriptor, Port, Result, Speed, Timeout, Version,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An owned snapshot of a device, with its descriptor tree, and
+/// optionally, its strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceInfo {
+    /// The number of the bus that the device is connected to.
+    pub bus_number: u8,
+    /// The device's address on the bus.
+    pub address: u8,
+    /// The hub ports through which the device is connected.
+    pub port_numbers: Vec<u8>,
+    /// The speed at which the device is operating.
+    pub speed: Speed,
+    /// The device's descriptors.
+    pub descriptors: DescriptorTree,
+    /// The string descriptors, by index, in the device's first language.
+    /// This is empty unless the strings were read from an open device.
+    pub strings: BTreeMap<u8, String>,
+}
+
+impl DeviceInfo {
+    /// Takes a snapshot of a device, with all of its configurations.
+    ///
+    /// This doesn't need to open the device, so it doesn't read any
+    /// strings.
+    pub fn from_device(device: &Device) -> Result<Self> {
+        let desc = device.device_descriptor()?;
+        let configs = (0..desc.num_configurations())
+            .map(|i| device.config_descriptor(i))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            bus_number: device.bus_number(),
+            address: device.address(),
+            port_numbers: device.port_numbers()?,
+            speed: device.speed(),
+            descriptors: DescriptorTree::new(&desc, &configs),
+            strings: BTreeMap::new(),
+        })
+    }
+
+    /// Takes a snapshot of an open device, including all of the strings
+    /// that its descriptors refer to.
+    ///
+    /// Any strings that can't be read are left out. The `timeout` applies
+    /// to reading all the strings.
+    pub fn from_handle(handle: &DeviceHandle, timeout: impl Into<Timeout>) -> DeviceResult<Self> {
+        let mut info = Self::from_device(&handle.device())
+            .map_err(|err| handle.error("device_info", ErrorTarget::Device, err))?;
+
+        let indices = info.descriptors.string_indices();
+        if indices.is_empty() {
+            return Ok(info);
+        }
+
+        // Many devices without strings stall the request for the languages,
+        // which just means that there are no strings to read.
+        let timeout = timeout.into().to_deadline();
+        let languages = handle.read_languages(timeout).unwrap_or_default();
+
+        if let Some(&language) = languages.first() {
+            for index in indices {
+                if let Ok(s) = handle.read_string_descriptor(language, index, timeout) {
+                    info.strings.insert(index, s);
+                }
+            }
+        }
+        Ok(info)
+    }
+
+    /// Gets the port to which the device is connected.
+    pub fn port(&self) -> Port {
+        Port::new(self.bus_number, self.port_numbers.clone())
+    }
+
+    /// Gets a string by index, if it was read.
+    pub fn string(&self, index: Option<u8>) -> Option<&str> {
+        self.strings.get(&index?).map(String::as_str)
+    }
+
+    /// Gets the manufacturer string, if it was read.
+    pub fn manufacturer(&self) -> Option<&str> {
+        self.string(self.descriptors.device.manufacturer_string_index)
+    }
+
+    /// Gets the product string, if it was read.
+    pub fn product(&self) -> Option<&str> {
+        self.string(self.descriptors.device.product_string_index)
+    }
+
+    /// Gets the serial number string, if it was read.
+    pub fn serial_number(&self) -> Option<&str> {
+        self.string(self.descriptors.device.serial_number_string_index)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// An owned copy of a device descriptor, and all of its configurations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DescriptorTree {
+    /// The device descriptor.
+    pub device: DeviceNode,
+    /// The configurations.
+    pub configurations: Vec<ConfigNode>,
+}
+
+impl DescriptorTree {
+    /// Creates a descriptor tree from the device descriptor and its
+    /// configurations.
+    pub fn new(device: &DeviceDescriptor, configs: &[ConfigDescriptor]) -> Self {
+        Self {
+            device: DeviceNode::from(device),
+            configurations: configs.iter().map(ConfigNode::from).collect(),
+        }
+    }
+
+    /// Parses a descriptor tree from raw bytes, in which the device
+    /// descriptor is followed by all of its configurations, like the
+    /// `descriptors` file of a USB device in Linux sysfs.
+    ///
+    /// Any trailing bytes after the configurations are ignored.
+    pub fn parse(buf: &[u8], mode: ParseMode) -> Result<Self> {
+        let device = DeviceDescriptor::parse_with_mode(buf, mode)?;
+
+        let mut rest = buf
+            .get(descriptor_parser::DEVICE_DESCRIPTOR_SIZE..)
+            .unwrap_or(&[]);
+        let mut configs = Vec::new();
+
+        for _ in 0..device.num_configurations() {
+            match ConfigDescriptor::parse_with_mode(rest, mode) {
+                Ok(config) => {
+                    let len = usize::from(config.total_length()).min(rest.len());
+                    rest = &rest[len..];
+                    configs.push(config);
+                }
+                Err(_) if mode == ParseMode::Lenient => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Self::new(&device, &configs))
+    }
+
+    /// Gets the indexes of all the strings that the descriptors refer to.
+    pub fn string_indices(&self) -> BTreeSet<u8> {
+        let dev = &self.device;
+        let mut indices: BTreeSet<u8> = [
+            dev.manufacturer_string_index,
+            dev.product_string_index,
+            dev.serial_number_string_index,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        for config in &self.configurations {
+            indices.extend(config.description_string_index);
+            indices.extend(
+                config
+                    .functions
+                    .iter()
+                    .filter_map(Function::description_string_index),
+            );
+            for iface in &config.interfaces {
+                indices.extend(
+                    iface
+                        .alt_settings
+                        .iter()
+                        .filter_map(|alt| alt.description_string_index),
+                );
+            }
+        }
+        indices
+    }
+}
+
+/// An owned copy of a device descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceNode {
+    /// The USB version that the device supports.
+    pub usb_version: Version,
+    /// The device's class code.
+    pub class_code: u8,
+    /// The device's sub class code.
+    pub sub_class_code: u8,
+    /// The device's protocol code.
+    pub protocol_code: u8,
+    /// The maximum packet size of the control endpoint.
+    pub max_packet_size: u8,
+    /// The vendor ID.
+    pub vendor_id: u16,
+    /// The product ID.
+    pub product_id: u16,
+    /// The manufacturer's version of the device.
+    pub device_version: Version,
+    /// The index of the manufacturer string.
+    pub manufacturer_string_index: Option<u8>,
+    /// The index of the product string.
+    pub product_string_index: Option<u8>,
+    /// The index of the serial number string.
+    pub serial_number_string_index: Option<u8>,
+    /// The number of configurations.
+    pub num_configurations: u8,
+}
+
+impl From<&DeviceDescriptor> for DeviceNode {
+    fn from(desc: &DeviceDescriptor) -> Self {
+        Self {
+            usb_version: desc.usb_version(),
+            class_code: desc.class_code(),
+            sub_class_code: desc.sub_class_code(),
+            protocol_code: desc.protocol_code(),
+            max_packet_size: desc.max_packet_size(),
+            vendor_id: desc.vendor_id(),
+            product_id: desc.product_id(),
+            device_version: desc.device_version(),
+            manufacturer_string_index: desc.manufacturer_string_index(),
+            product_string_index: desc.product_string_index(),
+            serial_number_string_index: desc.serial_number_string_index(),
+            num_configurations: desc.num_configurations(),
+        }
+    }
+}
+
+/// An owned copy of a configuration descriptor, and all of its interfaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConfigNode {
+    /// The configuration number.
+    pub number: u8,
+    /// The `wTotalLength` of the configuration, including all of its
+    /// interfaces, endpoints, and extra descriptors.
+    pub total_length: u16,
+    /// The index of the string that describes the configuration.
+    pub description_string_index: Option<u8>,
+    /// The raw `bmAttributes` bitmap.
+    pub attributes: u8,
+    /// The maximum power, in milliamps.
+    pub max_power: u16,
+    /// The functions of a composite device, from the IADs.
+    pub functions: Vec<Function>,
+    /// The interfaces.
+    pub interfaces: Vec<InterfaceNode>,
+    /// The descriptors that follow the configuration descriptor which
+    /// aren't parsed.
+    pub extra: Vec<u8>,
+}
+
+impl ConfigNode {
+    /// Determines if the device is self-powered in this configuration.
+    pub fn self_powered(&self) -> bool {
+        self.attributes & 0x40 != 0
+    }
+
+    /// Determines if the device supports remote wakeup in this
+    /// configuration.
+    pub fn remote_wakeup(&self) -> bool {
+        self.attributes & 0x20 != 0
+    }
+}
+
+impl From<&ConfigDescriptor> for ConfigNode {
+    fn from(config: &ConfigDescriptor) -> Self {
+        Self {
+            number: config.number(),
+            total_length: config.total_length(),
+            description_string_index: config.description_string_index(),
+            attributes: config.attributes(),
+            max_power: config.max_power(),
+            functions: config.functions(),
+            interfaces: config
+                .interfaces()
+                .map(|iface| InterfaceNode {
+                    number: iface.number(),
+                    alt_settings: iface
+                        .descriptors()
+                        .map(|desc| AltSettingNode::from(&desc))
+                        .collect(),
+                })
+                .collect(),
+            extra: config.extra().to_vec(),
+        }
+    }
+}
+
+/// An owned copy of an interface, with all of its alternate settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InterfaceNode {
+    /// The interface number.
+    pub number: u8,
+    /// The alternate settings.
+    pub alt_settings: Vec<AltSettingNode>,
+}
+
+/// An owned copy of an interface descriptor, which is one alternate
+/// setting of the interface, with all of its endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AltSettingNode {
+    /// The interface number.
+    pub interface_number: u8,
+    /// The alternate setting number.
+    pub setting_number: u8,
+    /// The interface's class code.
+    pub class_code: u8,
+    /// The interface's sub class code.
+    pub sub_class_code: u8,
+    /// The interface's protocol code.
+    pub protocol_code: u8,
+    /// The index of the string that describes the interface.
+    pub description_string_index: Option<u8>,
+    /// The endpoints.
+    pub endpoints: Vec<EndpointNode>,
+    /// The descriptors that follow the interface descriptor which aren't
+    /// parsed, such as class-specific ones.
+    pub extra: Vec<u8>,
+}
+
+impl From<&InterfaceDescriptor<'_>> for AltSettingNode {
+    fn from(desc: &InterfaceDescriptor<'_>) -> Self {
+        Self {
+            interface_number: desc.interface_number(),
+            setting_number: desc.setting_number(),
+            class_code: desc.class_code(),
+            sub_class_code: desc.sub_class_code(),
+            protocol_code: desc.protocol_code(),
+            description_string_index: desc.description_string_index(),
+            endpoints: desc
+                .endpoint_descriptors()
+                .map(|ep| EndpointNode::from(&ep))
+                .collect(),
+            extra: desc.extra().to_vec(),
+        }
+    }
+}
+
+/// An owned copy of an endpoint descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EndpointNode {
+    /// The `bLength` of the descriptor, which is 9 for audio endpoints,
+    /// and 7 for the others.
+    pub length: u8,
+    /// The endpoint address, including the direction bit.
+    pub address: u8,
+    /// The raw `bmAttributes` bitmap.
+    pub attributes: u8,
+    /// The raw `wMaxPacketSize` field.
+    pub max_packet_size: u16,
+    /// The polling interval.
+    pub interval: u8,
+    /// For audio endpoints, the rate at which synchronization feedback is
+    /// provided.
+    pub refresh: u8,
+    /// For audio endpoints, the address of the synch endpoint.
+    pub synch_address: u8,
+    /// The descriptors that follow the endpoint descriptor which aren't
+    /// parsed, such as the SuperSpeed companion.
+    pub extra: Vec<u8>,
+}
+
+impl From<&EndpointDescriptor<'_>> for EndpointNode {
+    fn from(ep: &EndpointDescriptor<'_>) -> Self {
+        Self {
+            length: ep.descriptor_length(),
+            address: ep.address(),
+            attributes: ep.attributes(),
+            max_packet_size: ep.max_packet_size(),
+            interval: ep.interval(),
+            refresh: ep.refresh(),
+            synch_address: ep.synch_address(),
+            extra: ep.extra().unwrap_or(&[]).to_vec(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ConfigDescriptorBuilder, DeviceDescriptorBuilder, EndpointBuilder, InterfaceBuilder,
+    };
+
+    fn descriptors() -> Vec<u8> {
+        let mut dev = DeviceDescriptorBuilder::new(0x1234, 0x5678);
+        dev.product_string_index(2).num_configurations(1);
+
+        let mut alt0 = InterfaceBuilder::new(0);
+        alt0.class(0xFF, 0, 0).description_string_index(4);
+        let mut alt1 = InterfaceBuilder::new(0);
+        alt1.alt_setting(1)
+            .class(0xFF, 0, 0)
+            .endpoint(EndpointBuilder::new(0x81).extra(&[0x03, 0x24, 0x01]));
+
+        let mut config = ConfigDescriptorBuilder::new(1);
+        config
+            .description_string_index(3)
+            .interface(&alt0)
+            .interface(&alt1);
+
+        let mut buf = dev.to_bytes();
+        buf.extend(config.to_bytes().unwrap());
+        buf
+    }
+
+    #[test]
+    fn parse_tree() {
+        let tree = DescriptorTree::parse(&descriptors(), ParseMode::Strict).unwrap();
+
+        assert_eq!(0x5678, tree.device.product_id);
+        assert_eq!(1, tree.configurations.len());
+
+        let config = &tree.configurations[0];
+        assert_eq!(1, config.interfaces.len());
+        assert_eq!(2, config.interfaces[0].alt_settings.len());
+
+        let alt1 = &config.interfaces[0].alt_settings[1];
+        assert_eq!(1, alt1.setting_number);
+        assert_eq!(0x81, alt1.endpoints[0].address);
+        assert_eq!(vec![0x03, 0x24, 0x01], alt1.endpoints[0].extra);
+        assert_eq!(7, alt1.endpoints[0].length);
+        assert_eq!(37, config.total_length);
+
+        let indices: Vec<u8> = tree.string_indices().into_iter().collect();
+        assert_eq!(vec![2, 3, 4], indices);
+    }
+
+    #[test]
+    fn audio_endpoint_length() {
+        let tree = crate::test_fixtures::audio_device();
+
+        let config = &tree.configurations[0];
+        assert_eq!(43, config.total_length);
+        let endpoints = &config.interfaces[0].alt_settings[0].endpoints;
+        assert_eq!(9, endpoints[0].length);
+        assert_eq!(0x82, endpoints[0].synch_address);
+        assert_eq!(9, endpoints[1].length);
+        assert_eq!(3, endpoints[1].refresh);
+    }
+
+    #[test]
+    fn parse_truncated_tree() {
+        let buf = descriptors();
+        let buf = &buf[..buf.len() - 4];
+
+        assert!(DescriptorTree::parse(buf, ParseMode::Strict).is_err());
+        let tree = DescriptorTree::parse(buf, ParseMode::Lenient).unwrap();
+        assert!(tree.configurations[0].interfaces[0].alt_settings[1]
+            .endpoints
+            .is_empty());
+    }
+
+    #[test]
+    fn info_strings() {
+        let tree = DescriptorTree::parse(&descriptors(), ParseMode::Strict).unwrap();
+        let mut info = DeviceInfo {
+            bus_number: 1,
+            address: 4,
+            port_numbers: vec![2, 3],
+            speed: Speed::High,
+            descriptors: tree,
+            strings: BTreeMap::new(),
+        };
+        assert_eq!(None, info.product());
+
+        info.strings.insert(2, "Widget".to_string());
+        assert_eq!(Some("Widget"), info.product());
+        assert_eq!(None, info.manufacturer());
+        assert_eq!("1-2.3", info.port().to_string());
+    }
+}
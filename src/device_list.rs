@@ -11,9 +11,9 @@
 //
 
 use crate::{Context, Device, Error, Result};
-use libc::c_int;
 use libusb1_sys as ffi;
 use std::{
+    os::raw::c_int,
     ptr::{self, NonNull},
     slice,
 };
@@ -85,7 +85,7 @@ impl DeviceList {
     /// Returns an iterator over the devices in the list.
     ///
     /// The iterator yields a sequence of `Device` objects.
-    pub fn iter(&self) -> Devices {
+    pub fn iter(&self) -> Devices<'_> {
         Devices { list: self, idx: 0 }
     }
 
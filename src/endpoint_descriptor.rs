@@ -10,14 +10,29 @@
 // to those terms.
 //
 
-use crate::fields::{Direction, IsoSyncType, IsoUsageType, TransferType};
+use crate::{
+    fields::{Direction, IsoSyncType, IsoUsageType, Speed, TransferType},
+    raw_descriptors::RawDescriptors,
+};
 use libusb1_sys::{constants::*, libusb_endpoint_descriptor};
-use std::{fmt, slice};
+use std::{fmt, slice, time::Duration};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Descriptor type of the SuperSpeedPlus Isochronous Endpoint Companion.
+pub(crate) const DT_SSP_ISO_ENDPOINT_COMPANION: u8 = 0x31;
 
 /// Describes an endpoint.
 pub struct EndpointDescriptor<'a>(&'a libusb_endpoint_descriptor);
 
 impl<'a> EndpointDescriptor<'a> {
+    /// Returns the length of the descriptor, which is 9 for audio
+    /// endpoints, and 7 for the others.
+    pub(crate) fn descriptor_length(&self) -> u8 {
+        self.0.bLength
+    }
+
     /// Returns the endpoint's address.
     pub fn address(&self) -> u8 {
         self.0.bEndpointAddress
@@ -33,6 +48,11 @@ impl<'a> EndpointDescriptor<'a> {
         Direction::from(self.0.bEndpointAddress & LIBUSB_ENDPOINT_DIR_MASK)
     }
 
+    /// Returns the raw `bmAttributes` bitmap of the endpoint.
+    pub fn attributes(&self) -> u8 {
+        self.0.bmAttributes
+    }
+
     /// Returns the endpoint's transfer type.
     pub fn transfer_type(&self) -> TransferType {
         TransferType::from(self.0.bmAttributes & LIBUSB_TRANSFER_TYPE_MASK)
@@ -52,16 +72,119 @@ impl<'a> EndpointDescriptor<'a> {
         IsoUsageType::from((self.0.bmAttributes & LIBUSB_ISO_USAGE_TYPE_MASK) >> 4)
     }
 
-    /// Returns the endpoint's maximum packet size.
+    /// Determines if this is a periodic (interrupt or isochronous)
+    /// endpoint, which has bandwidth reserved for it on the bus.
+    pub fn is_periodic(&self) -> bool {
+        matches!(
+            self.transfer_type(),
+            TransferType::Interrupt | TransferType::Isochronous
+        )
+    }
+
+    /// Returns the endpoint's raw `wMaxPacketSize` field.
+    ///
+    /// For high-speed periodic endpoints, this includes the number of
+    /// additional transactions per microframe in bits 11-12. Use
+    /// [`packet_size`](#method.packet_size) to get the size of a
+    /// single packet.
     pub fn max_packet_size(&self) -> u16 {
         self.0.wMaxPacketSize
     }
 
-    /// Returns the endpoint's polling interval.
+    /// Returns the maximum size of a single packet, in bytes.
+    ///
+    /// This is the lower 11 bits of `wMaxPacketSize`.
+    pub fn packet_size(&self) -> u16 {
+        self.0.wMaxPacketSize & 0x07FF
+    }
+
+    /// Returns the number of transactions per microframe, in the range 1-3.
+    ///
+    /// This is only meaningful for high-speed periodic endpoints. It is
+    /// always 1 for the others.
+    pub fn transactions_per_microframe(&self) -> u8 {
+        (((self.0.wMaxPacketSize >> 11) & 0x03) as u8 + 1).min(3)
+    }
+
+    /// Returns the endpoint's raw polling interval, `bInterval`.
+    ///
+    /// The meaning of this value depends on the speed of the device and
+    /// the transfer type of the endpoint. See
+    /// [`polling_period`](#method.polling_period).
     pub fn interval(&self) -> u8 {
         self.0.bInterval
     }
 
+    /// Returns the time between service intervals of a periodic endpoint,
+    /// when the device is operating at the specified speed.
+    ///
+    /// At low and full speed, the interval of an interrupt endpoint is a
+    /// number of 1ms frames, while for an isochronous endpoint it is
+    /// 2^(bInterval-1) frames. At high speed and above, it is
+    /// 2^(bInterval-1) microframes of 125us. An unknown speed is treated
+    /// as full speed.
+    ///
+    /// Bulk and control endpoints aren't polled, and return zero.
+    pub fn polling_period(&self, speed: Speed) -> Duration {
+        let interval = self.0.bInterval;
+        let exp = || u32::from(interval.clamp(1, 16) - 1);
+
+        match (self.transfer_type(), speed) {
+            (TransferType::Control, _) | (TransferType::Bulk, _) => Duration::ZERO,
+            (TransferType::Interrupt, Speed::Unknown | Speed::Low | Speed::Full) => {
+                Duration::from_millis(u64::from(interval.max(1)))
+            }
+            (TransferType::Isochronous, Speed::Unknown | Speed::Low | Speed::Full) => {
+                Duration::from_millis(1u64 << exp())
+            }
+            _ => Duration::from_micros(125u64 << exp()),
+        }
+    }
+
+    /// Returns the maximum number of bytes that a periodic endpoint can
+    /// transfer in a single service interval, when the device is operating
+    /// at the specified speed.
+    ///
+    /// At SuperSpeed and above, this comes from the endpoint companion
+    /// descriptors. At high speed, it is the packet size times the number
+    /// of transactions per microframe. Otherwise it's a single packet.
+    ///
+    /// Bulk and control endpoints have no reserved bandwidth, and return
+    /// zero.
+    pub fn bytes_per_interval(&self, speed: Speed) -> u32 {
+        if !self.is_periodic() {
+            return 0;
+        }
+
+        let packet_size = u32::from(self.packet_size());
+
+        match speed {
+            Speed::Super | Speed::SuperPlus => match self.ss_companion() {
+                Some(comp) if comp.has_ssp_iso_companion() => self
+                    .ssp_iso_companion()
+                    .map(|ssp| ssp.bytes_per_interval())
+                    .unwrap_or_else(|| u32::from(comp.bytes_per_interval())),
+                Some(comp) => u32::from(comp.bytes_per_interval()),
+                None => packet_size,
+            },
+            Speed::High => packet_size * u32::from(self.transactions_per_microframe()),
+            _ => packet_size,
+        }
+    }
+
+    /// Returns the bandwidth reserved for a periodic endpoint, in bytes
+    /// per second, when the device is operating at the specified speed.
+    ///
+    /// This is the number of bytes per interval divided by the polling
+    /// period. It is zero for bulk and control endpoints.
+    pub fn periodic_bandwidth(&self, speed: Speed) -> u64 {
+        let period = self.polling_period(speed).as_micros() as u64;
+        match period {
+            0 => 0,
+            _ => u64::from(self.bytes_per_interval(speed)) * 1_000_000 / period,
+        }
+    }
+
     /// Returns the unknown 'extra' bytes that libusb does not understand.
     pub fn extra(&'a self) -> Option<&'a [u8]> {
         unsafe {
@@ -72,6 +195,30 @@ impl<'a> EndpointDescriptor<'a> {
         }
     }
 
+    /// Returns the SuperSpeed Endpoint Companion descriptor, if the endpoint
+    /// has one.
+    ///
+    /// Every endpoint of a device that is operating at SuperSpeed or
+    /// faster has a companion, which is parsed from the [`extra`](#method.extra)
+    /// bytes.
+    pub fn ss_companion(&self) -> Option<SsEndpointCompanion> {
+        RawDescriptors::new(self.extra()?)
+            .find_type(LIBUSB_DT_SS_ENDPOINT_COMPANION)
+            .and_then(SsEndpointCompanion::parse)
+    }
+
+    /// Returns the SuperSpeedPlus Isochronous Endpoint Companion
+    /// descriptor, if the endpoint has one.
+    ///
+    /// This is present for isochronous endpoints that need more than the
+    /// 48KB per service interval that can be described by the SuperSpeed
+    /// companion.
+    pub fn ssp_iso_companion(&self) -> Option<SspIsoEndpointCompanion> {
+        RawDescriptors::new(self.extra()?)
+            .find_type(DT_SSP_ISO_ENDPOINT_COMPANION)
+            .and_then(SspIsoEndpointCompanion::parse)
+    }
+
     /// For audio devices only: return the rate at which synchronization feedback is provided.
     pub fn refresh(&self) -> u8 {
         self.0.bRefresh
@@ -104,6 +251,119 @@ impl<'a> From<&'a libusb_endpoint_descriptor> for EndpointDescriptor<'a> {
     }
 }
 
+/// The SuperSpeed Endpoint Companion descriptor.
+///
+/// This gives the burst size of an endpoint, and either the maximum number
+/// of streams for a bulk endpoint, or the packets and bytes per service
+/// interval for a periodic endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SsEndpointCompanion {
+    max_burst: u8,
+    attributes: u8,
+    bytes_per_interval: u16,
+}
+
+impl SsEndpointCompanion {
+    /// Parses the companion from its raw descriptor bytes.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        match buf {
+            [len, LIBUSB_DT_SS_ENDPOINT_COMPANION, max_burst, attributes, lo, hi, ..]
+                if *len >= 6 =>
+            {
+                Some(Self {
+                    max_burst: *max_burst,
+                    attributes: *attributes,
+                    bytes_per_interval: u16::from_le_bytes([*lo, *hi]),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the raw `bMaxBurst` value.
+    ///
+    /// This is one less than the number of packets that the endpoint can
+    /// send or receive as part of a burst, in the range 0-15.
+    pub fn max_burst(&self) -> u8 {
+        self.max_burst
+    }
+
+    /// Returns the number of packets that the endpoint can send or receive
+    /// in a burst, in the range 1-16.
+    pub fn burst_size(&self) -> u8 {
+        self.max_burst.min(15) + 1
+    }
+
+    /// Returns the raw `bmAttributes` value.
+    pub fn attributes(&self) -> u8 {
+        self.attributes
+    }
+
+    /// For bulk endpoints: returns the maximum number of streams that the
+    /// endpoint supports, or zero if it doesn't support streams.
+    pub fn max_streams(&self) -> u32 {
+        match self.attributes & 0x1F {
+            0 => 0,
+            n => 1 << n,
+        }
+    }
+
+    /// For isochronous endpoints: returns the raw `Mult` value.
+    ///
+    /// This is one less than the number of bursts in a service interval,
+    /// in the range 0-2.
+    pub fn mult(&self) -> u8 {
+        self.attributes & 0x03
+    }
+
+    /// For isochronous endpoints: returns the maximum number of packets in
+    /// a service interval, as the burst size times the number of bursts.
+    pub fn max_packets_per_interval(&self) -> u32 {
+        u32::from(self.burst_size()) * (u32::from(self.mult()) + 1)
+    }
+
+    /// For isochronous endpoints: determines if the endpoint has a
+    /// SuperSpeedPlus Isochronous Endpoint Companion, which gives the real
+    /// number of bytes per interval.
+    pub fn has_ssp_iso_companion(&self) -> bool {
+        self.attributes & 0x80 != 0
+    }
+
+    /// For periodic endpoints: returns the total number of bytes that the
+    /// endpoint will transfer in a service interval.
+    pub fn bytes_per_interval(&self) -> u16 {
+        self.bytes_per_interval
+    }
+}
+
+/// The SuperSpeedPlus Isochronous Endpoint Companion descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SspIsoEndpointCompanion {
+    bytes_per_interval: u32,
+}
+
+impl SspIsoEndpointCompanion {
+    /// Parses the companion from its raw descriptor bytes.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        match buf {
+            [len, DT_SSP_ISO_ENDPOINT_COMPANION, _, _, b0, b1, b2, b3, ..] if *len >= 8 => {
+                Some(Self {
+                    bytes_per_interval: u32::from_le_bytes([*b0, *b1, *b2, *b3]),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the total number of bytes that the endpoint will transfer in
+    /// a service interval.
+    pub fn bytes_per_interval(&self) -> u32 {
+        self.bytes_per_interval
+    }
+}
+
 #[cfg(test)]
 mod test {
     #![allow(unused_qualifications)]
@@ -277,4 +537,141 @@ mod test {
             EndpointDescriptor::from(&endpoint_descriptor!(bInterval: 255)).interval()
         );
     }
+
+    #[test]
+    fn it_splits_high_bandwidth_packet_size() {
+        let endpoint = endpoint_descriptor!(bmAttributes: 0b0000_0001, wMaxPacketSize: 0x1400);
+        let endpoint = EndpointDescriptor::from(&endpoint);
+        assert_eq!(0x1400, endpoint.max_packet_size());
+        assert_eq!(1024, endpoint.packet_size());
+        assert_eq!(3, endpoint.transactions_per_microframe());
+        assert_eq!(3072, endpoint.bytes_per_interval(Speed::High));
+        assert_eq!(1024, endpoint.bytes_per_interval(Speed::Full));
+
+        let endpoint = endpoint_descriptor!(wMaxPacketSize: 64);
+        assert_eq!(
+            1,
+            EndpointDescriptor::from(&endpoint).transactions_per_microframe()
+        );
+    }
+
+    #[test]
+    fn it_has_polling_period() {
+        let intr = endpoint_descriptor!(bmAttributes: 0b0000_0011, bInterval: 10);
+        let intr = EndpointDescriptor::from(&intr);
+        assert_eq!(Duration::from_millis(10), intr.polling_period(Speed::Full));
+        assert_eq!(Duration::from_millis(10), intr.polling_period(Speed::Low));
+        assert_eq!(Duration::from_millis(64), intr.polling_period(Speed::High));
+        assert_eq!(Duration::from_millis(64), intr.polling_period(Speed::Super));
+
+        let iso = endpoint_descriptor!(bmAttributes: 0b0000_0001, bInterval: 1);
+        let iso = EndpointDescriptor::from(&iso);
+        assert_eq!(Duration::from_millis(1), iso.polling_period(Speed::Full));
+        assert_eq!(Duration::from_micros(125), iso.polling_period(Speed::High));
+
+        let bulk = endpoint_descriptor!(bmAttributes: 0b0000_0010, bInterval: 1);
+        let bulk = EndpointDescriptor::from(&bulk);
+        assert_eq!(Duration::ZERO, bulk.polling_period(Speed::High));
+        assert_eq!(0, bulk.bytes_per_interval(Speed::High));
+        assert_eq!(0, bulk.periodic_bandwidth(Speed::High));
+    }
+
+    #[test]
+    fn it_has_periodic_bandwidth() {
+        // 3 x 1024 bytes every microframe
+        let iso = endpoint_descriptor!(
+            bmAttributes: 0b0000_0001,
+            wMaxPacketSize: 0x1400,
+            bInterval: 1
+        );
+        assert_eq!(
+            24_576_000,
+            EndpointDescriptor::from(&iso).periodic_bandwidth(Speed::High)
+        );
+
+        // 8 bytes every 10ms
+        let intr = endpoint_descriptor!(
+            bmAttributes: 0b0000_0011,
+            wMaxPacketSize: 8,
+            bInterval: 10
+        );
+        assert_eq!(
+            800,
+            EndpointDescriptor::from(&intr).periodic_bandwidth(Speed::Full)
+        );
+    }
+
+    #[test]
+    fn it_uses_companions_for_superspeed_bytes_per_interval() {
+        let extra = [0x06, 0x30, 0x03, 0x02, 0x00, 0x30];
+        let endpoint = endpoint_descriptor!(
+            bmAttributes: 0b0000_0001,
+            wMaxPacketSize: 1024,
+            extra: extra.as_ptr(),
+            extra_length: extra.len() as i32
+        );
+        let endpoint = EndpointDescriptor::from(&endpoint);
+        assert_eq!(0x3000, endpoint.bytes_per_interval(Speed::Super));
+        assert_eq!(1024, endpoint.bytes_per_interval(Speed::High));
+    }
+
+    #[test]
+    fn it_has_no_ss_companion_without_extra() {
+        let endpoint = endpoint_descriptor!(bmAttributes: 0b0000_0010);
+        assert_eq!(None, EndpointDescriptor::from(&endpoint).ss_companion());
+        assert_eq!(
+            None,
+            EndpointDescriptor::from(&endpoint).ssp_iso_companion()
+        );
+    }
+
+    #[test]
+    fn it_parses_bulk_ss_companion() {
+        let extra = [0x06, 0x30, 0x0F, 0x04, 0x00, 0x00];
+        let endpoint = endpoint_descriptor!(
+            bmAttributes: 0b0000_0010,
+            extra: extra.as_ptr(),
+            extra_length: extra.len() as i32
+        );
+        let comp = EndpointDescriptor::from(&endpoint).ss_companion().unwrap();
+        assert_eq!(15, comp.max_burst());
+        assert_eq!(16, comp.burst_size());
+        assert_eq!(16, comp.max_streams());
+        assert!(!comp.has_ssp_iso_companion());
+    }
+
+    #[test]
+    fn it_parses_iso_companions() {
+        let extra = [
+            0x06, 0x30, 0x03, 0x82, 0x00, 0x00, // SS companion
+            0x08, 0x31, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, // SSP iso companion
+        ];
+        let endpoint = endpoint_descriptor!(
+            bmAttributes: 0b0000_0001,
+            extra: extra.as_ptr(),
+            extra_length: extra.len() as i32
+        );
+        let endpoint = EndpointDescriptor::from(&endpoint);
+
+        let comp = endpoint.ss_companion().unwrap();
+        assert_eq!(4, comp.burst_size());
+        assert_eq!(2, comp.mult());
+        assert_eq!(12, comp.max_packets_per_interval());
+        assert!(comp.has_ssp_iso_companion());
+
+        let ssp = endpoint.ssp_iso_companion().unwrap();
+        assert_eq!(0x10000, ssp.bytes_per_interval());
+    }
+
+    #[test]
+    fn it_rejects_short_ss_companion() {
+        assert_eq!(
+            None,
+            SsEndpointCompanion::parse(&[0x05, 0x30, 0x00, 0x00, 0x00])
+        );
+        assert_eq!(
+            None,
+            SspIsoEndpointCompanion::parse(&[0x06, 0x31, 0, 0, 0, 0])
+        );
+    }
 }
@@ -10,15 +10,15 @@
 // to those terms.
 //
 
-use libc::c_int;
-use libusb1_sys::constants::*;
-use std::{fmt, result, str::Utf8Error, string::FromUtf16Error};
+use crate::{Port, SetupPacket};
+use libusb1_sys::{self as ffi, constants::*};
+use std::{ffi::CStr, fmt, io, os::raw::c_int, result, str::Utf8Error, string::FromUtf16Error};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// Errors returned by the `libusb` library.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Error {
     /// Input/output error.
@@ -78,6 +78,71 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Gets the `libusb` error code that corresponds to the error, if any.
+    ///
+    /// The errors that are generated by this crate, like `BadDescriptor`,
+    /// have no `libusb` code.
+    pub fn code(&self) -> Option<i32> {
+        use Error::*;
+        Some(match self {
+            Io => LIBUSB_ERROR_IO,
+            InvalidParam => LIBUSB_ERROR_INVALID_PARAM,
+            Access => LIBUSB_ERROR_ACCESS,
+            NoDevice => LIBUSB_ERROR_NO_DEVICE,
+            NotFound => LIBUSB_ERROR_NOT_FOUND,
+            Busy => LIBUSB_ERROR_BUSY,
+            Timeout => LIBUSB_ERROR_TIMEOUT,
+            Overflow => LIBUSB_ERROR_OVERFLOW,
+            Pipe => LIBUSB_ERROR_PIPE,
+            Interrupted => LIBUSB_ERROR_INTERRUPTED,
+            NoMem => LIBUSB_ERROR_NO_MEM,
+            NotSupported => LIBUSB_ERROR_NOT_SUPPORTED,
+            Other => LIBUSB_ERROR_OTHER,
+            BadDescriptor | BadUnicode => return None,
+        })
+    }
+
+    /// Gets the kind of I/O error that best describes the USB error.
+    pub fn io_error_kind(&self) -> io::ErrorKind {
+        use io::ErrorKind;
+        use Error::*;
+        match self {
+            InvalidParam => ErrorKind::InvalidInput,
+            Access => ErrorKind::PermissionDenied,
+            NoDevice => ErrorKind::NotConnected,
+            NotFound => ErrorKind::NotFound,
+            Timeout => ErrorKind::TimedOut,
+            Pipe => ErrorKind::BrokenPipe,
+            Interrupted => ErrorKind::Interrupted,
+            NoMem => ErrorKind::OutOfMemory,
+            NotSupported => ErrorKind::Unsupported,
+            Overflow | BadDescriptor | BadUnicode => ErrorKind::InvalidData,
+            Io | Busy | Other => ErrorKind::Other,
+        }
+    }
+
+    /// Gets the description of the error from `libusb_strerror()`.
+    ///
+    /// This is translated into the `libusb` locale, if one was set.
+    /// For errors that have no `libusb` code, this is the same as the
+    /// `Display` text.
+    pub fn strerror(&self) -> String {
+        match self.code() {
+            Some(code) => strerror(code),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    /// Converts the USB error into an I/O error, with a description from
+    /// `libusb_strerror()`.
+    fn from(err: Error) -> Self {
+        io::Error::new(err.io_error_kind(), err.strerror())
+    }
+}
+
 impl From<c_int> for Error {
     fn from(err: c_int) -> Self {
         match err {
@@ -113,6 +178,203 @@ impl From<FromUtf16Error> for Error {
 /// A result of a function that may return a USB `Error`.
 pub type Result<T> = result::Result<T, Error>;
 
+/// Gets the `libusb` description of an error code.
+fn strerror(code: c_int) -> String {
+    let s = unsafe { ffi::libusb_strerror(code) };
+    if s.is_null() {
+        return Error::from(code).to_string();
+    }
+    unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned()
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// What a failed device operation was acting upon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorTarget {
+    /// The device as a whole.
+    Device,
+    /// A configuration, by its value.
+    Configuration(u8),
+    /// An interface, by its number.
+    Interface(u8),
+    /// An alternate setting of an interface, as (interface, setting).
+    AltSetting(u8, u8),
+    /// An endpoint, by its address.
+    Endpoint(u8),
+    /// A control transfer, with its setup packet.
+    Control(SetupPacket),
+}
+
+impl fmt::Display for ErrorTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ErrorTarget::*;
+        match self {
+            Device => f.write_str("device"),
+            Configuration(config) => write!(f, "configuration {}", config),
+            Interface(iface) => write!(f, "interface {}", iface),
+            AltSetting(iface, setting) => {
+                write!(f, "interface {}, alt setting {}", iface, setting)
+            }
+            Endpoint(ep) => write!(f, "endpoint {:#04x}", ep),
+            Control(setup) => write!(f, "control [{}]", setup),
+        }
+    }
+}
+
+/// An error from an operation on an open device, with the context of
+/// where it happened.
+///
+/// This is returned by the [`DeviceHandle`](crate::DeviceHandle) methods.
+/// It carries the name of the operation, what it was acting upon, such as
+/// the endpoint or setup packet, and the port and VID:PID of the device.
+/// The underlying [`Error`] is available from [`DeviceError::kind`], and
+/// the error converts into an `Error` with `?`, dropping the context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceError {
+    kind: Error,
+    code: Option<i32>,
+    operation: &'static str,
+    target: ErrorTarget,
+    port: Option<Port>,
+    id: Option<(u16, u16)>,
+    transferred: usize,
+}
+
+impl DeviceError {
+    /// Creates a new error for an operation, without any device context.
+    pub fn new(kind: Error, operation: &'static str, target: ErrorTarget) -> Self {
+        Self {
+            kind,
+            code: kind.code(),
+            operation,
+            target,
+            port: None,
+            id: None,
+            transferred: 0,
+        }
+    }
+
+    /// Adds the port and VID:PID of the device to the error.
+    pub(crate) fn with_device(mut self, port: Option<Port>, id: Option<(u16, u16)>) -> Self {
+        self.port = port;
+        self.id = id;
+        self
+    }
+
+    /// Adds the number of bytes that were transferred before the error.
+    pub(crate) fn with_transferred(mut self, transferred: usize) -> Self {
+        self.transferred = transferred;
+        self
+    }
+
+    /// Gets the kind of error.
+    pub fn kind(&self) -> Error {
+        self.kind
+    }
+
+    /// Gets the raw `libusb` error code, if the error has one.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// Gets the name of the operation that failed, like "read_bulk".
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// Gets what the operation was acting upon.
+    pub fn target(&self) -> ErrorTarget {
+        self.target
+    }
+
+    /// Gets the endpoint address of a failed transfer, if any.
+    ///
+    /// Control transfers are on endpoint zero.
+    pub fn endpoint(&self) -> Option<u8> {
+        match self.target {
+            ErrorTarget::Endpoint(ep) => Some(ep),
+            ErrorTarget::Control(_) => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Gets the setup packet of a failed control transfer, if any.
+    pub fn setup_packet(&self) -> Option<SetupPacket> {
+        match self.target {
+            ErrorTarget::Control(setup) => Some(setup),
+            _ => None,
+        }
+    }
+
+    /// Gets the port of the device, if known.
+    pub fn port(&self) -> Option<&Port> {
+        self.port.as_ref()
+    }
+
+    /// Gets the vendor ID of the device, if known.
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.id.map(|(vid, _)| vid)
+    }
+
+    /// Gets the product ID of the device, if known.
+    pub fn product_id(&self) -> Option<u16> {
+        self.id.map(|(_, pid)| pid)
+    }
+
+    /// Gets the number of bytes that were transferred before the error.
+    ///
+    /// This is set by the operations that make several transfers, like
+    /// [`DeviceHandle::read_bulk_exact`](crate::DeviceHandle::read_bulk_exact),
+    /// and is zero for the others.
+    pub fn transferred(&self) -> usize {
+        self.transferred
+    }
+}
+
+impl fmt::Display for DeviceError {
+    /// Outputs the error with its context, like:
+    /// ```text
+    /// read_bulk on endpoint 0x81 of device 1-2.3 [1234:5678]: Pipe error (-9)
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on {}", self.operation, self.target)?;
+        match (&self.port, self.id) {
+            (Some(port), Some((vid, pid))) => {
+                write!(f, " of device {} [{:04x}:{:04x}]", port, vid, pid)?
+            }
+            (Some(port), None) => write!(f, " of device {}", port)?,
+            (None, Some((vid, pid))) => write!(f, " of device [{:04x}:{:04x}]", vid, pid)?,
+            (None, None) => (),
+        }
+        match self.code {
+            Some(code) => write!(f, ": {} ({})", strerror(code), code),
+            None => write!(f, ": {}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
+impl From<DeviceError> for Error {
+    /// Gets the kind of the error, discarding the context.
+    fn from(err: DeviceError) -> Self {
+        err.kind
+    }
+}
+
+impl From<DeviceError> for io::Error {
+    /// Converts the error into an I/O error, keeping the context in the
+    /// description.
+    fn from(err: DeviceError) -> Self {
+        io::Error::new(err.kind.io_error_kind(), err.to_string())
+    }
+}
+
+/// A result of a device operation that may fail with a `DeviceError`.
+pub type DeviceResult<T> = result::Result<T, DeviceError>;
+
 /// Converts an integer return value into a `Result<usize>`
 pub(crate) fn usb_result(res: c_int) -> Result<usize> {
     if res >= 0 {
@@ -131,3 +393,92 @@ macro_rules! try_unsafe {
         }
     };
 }
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_codes_round_trip() {
+        use Error::*;
+        for err in [
+            Io,
+            InvalidParam,
+            Access,
+            NoDevice,
+            NotFound,
+            Busy,
+            Timeout,
+            Overflow,
+            Pipe,
+            Interrupted,
+            NoMem,
+            NotSupported,
+            Other,
+        ] {
+            assert_eq!(err, Error::from(err.code().unwrap()));
+        }
+        assert_eq!(None, BadDescriptor.code());
+        assert_eq!(None, BadUnicode.code());
+    }
+
+    #[test]
+    fn error_into_io_error() {
+        let err = io::Error::from(Error::Timeout);
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        assert!(!err.to_string().is_empty());
+
+        let err = io::Error::from(Error::BadDescriptor);
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!(Error::BadDescriptor.to_string(), err.to_string());
+    }
+
+    #[test]
+    fn device_error_context() {
+        let err = DeviceError::new(Error::Pipe, "read_bulk", ErrorTarget::Endpoint(0x81))
+            .with_device(Some(Port::new(1, vec![2, 3])), Some((0x1234, 0x5678)));
+
+        assert_eq!(Error::Pipe, err.kind());
+        assert_eq!(Some(LIBUSB_ERROR_PIPE), err.code());
+        assert_eq!(Some(0x81), err.endpoint());
+        assert_eq!(None, err.setup_packet());
+        assert_eq!(Some(0x1234), err.vendor_id());
+        assert_eq!(0, err.transferred());
+        assert_eq!(512, err.clone().with_transferred(512).transferred());
+        assert!(err
+            .to_string()
+            .starts_with("read_bulk on endpoint 0x81 of device 1-2.3 [1234:5678]: "));
+        assert!(err.to_string().ends_with("(-9)"));
+
+        let io_err = io::Error::from(err.clone());
+        assert_eq!(io::ErrorKind::BrokenPipe, io_err.kind());
+        assert_eq!(err.to_string(), io_err.to_string());
+
+        assert_eq!(Error::Pipe, Error::from(err));
+    }
+
+    #[test]
+    fn device_error_control_target() {
+        let setup = SetupPacket {
+            request_type: 0x80,
+            request: 0x06,
+            value: 0x0100,
+            index: 0,
+            length: 18,
+        };
+        let err = DeviceError::new(
+            Error::BadDescriptor,
+            "read_languages",
+            ErrorTarget::Control(setup),
+        );
+        assert_eq!(None, err.code());
+        assert_eq!(Some(0), err.endpoint());
+        assert_eq!(Some(setup), err.setup_packet());
+        assert_eq!(
+            "read_languages on control [80 06 0100 0000 0012]: Malformed descriptor",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// yusb/src/event_thread.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{Context, Error, Result};
+use std::{
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// A background thread that handles events for a [`Context`].
+///
+/// The thread is created with [`Context::spawn_event_thread`] and runs
+/// [`Context::handle_events`] in a loop. It is stopped and joined when the
+/// object is dropped, or explicitly with [`EventThread::stop`].
+///
+/// Only one `EventThread` can be running for a context at any time.
+#[must_use = "the event thread is stopped when the EventThread is dropped"]
+pub struct EventThread {
+    ctx: Context,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl EventThread {
+    /// Spawns a new event handling thread for the context.
+    pub(crate) fn spawn(ctx: Context) -> Result<Self> {
+        if !ctx.acquire_event_thread() {
+            return Err(Error::Busy);
+        }
+
+        if ctx.event_handler_active() {
+            ctx.release_event_thread();
+            return Err(Error::Busy);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let ctx = ctx.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("yusb-events".to_string())
+                .spawn(move || Self::run(ctx, stop))
+        };
+
+        match thread {
+            Ok(thread) => Ok(Self {
+                ctx,
+                stop,
+                thread: Some(thread),
+            }),
+            Err(_) => {
+                ctx.release_event_thread();
+                Err(Error::Other)
+            }
+        }
+    }
+
+    /// The event loop.
+    fn run(ctx: Context, stop: Arc<AtomicBool>) -> Result<()> {
+        while !stop.load(Ordering::Acquire) {
+            match ctx.handle_events(None) {
+                Ok(()) | Err(Error::Interrupted) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the context that this thread is handling events for.
+    pub fn context(&self) -> Context {
+        self.ctx.clone()
+    }
+
+    /// Stops the event thread and waits for it to exit.
+    ///
+    /// Returns the error that caused the event loop to exit early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    /// Signals the thread to stop, wakes it up, and joins it.
+    fn shutdown(&mut self) -> Result<()> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+
+        self.stop.store(true, Ordering::Release);
+        self.ctx.interrupt_handle_events();
+
+        let res = thread.join().unwrap_or(Err(Error::Other));
+        self.ctx.release_event_thread();
+        res
+    }
+}
+
+impl Drop for EventThread {
+    /// Stops the event thread.
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+impl Debug for EventThread {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventThread")
+            .field("context", &self.ctx)
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates a new context, or `None` if `libusb` can't be initialized
+    /// on this host, in which case the test is skipped.
+    fn context() -> Option<Context> {
+        match Context::new() {
+            Ok(ctx) => Some(ctx),
+            Err(err) => {
+                eprintln!("Skipping test, libusb can't be initialized: {}", err);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn one_thread_per_context() {
+        let ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+
+        let events = ctx.spawn_event_thread().unwrap();
+        assert!(matches!(ctx.spawn_event_thread(), Err(Error::Busy)));
+
+        // Another context can have its own thread.
+        let other = Context::new().unwrap();
+        let other_events = other.spawn_event_thread().unwrap();
+
+        events.stop().unwrap();
+        other_events.stop().unwrap();
+    }
+
+    #[test]
+    fn guard_resets_after_drop() {
+        let ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+
+        assert!(!ctx.has_event_thread());
+        let events = ctx.spawn_event_thread().unwrap();
+        assert!(ctx.has_event_thread());
+        drop(events);
+        assert!(!ctx.has_event_thread());
+
+        let events = ctx.spawn_event_thread().unwrap();
+        assert!(events.stop().is_ok());
+        assert!(ctx.spawn_event_thread().is_ok());
+    }
+
+    #[test]
+    fn drop_joins_thread() {
+        let ctx = match context() {
+            Some(ctx) => ctx,
+            None => return,
+        };
+
+        // The thread holds a reference to the context until it exits.
+        let events = ctx.spawn_event_thread().unwrap();
+        assert!(ctx.ref_count() > 2);
+
+        drop(events);
+        assert_eq!(1, ctx.ref_count());
+    }
+}
@@ -11,9 +11,8 @@
 //
 
 use crate::Error;
-use libc::c_int;
 use libusb1_sys::constants::*;
-use std::{fmt, str::FromStr};
+use std::{fmt, os::raw::c_int, str::FromStr};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -65,6 +64,25 @@ impl Speed {
             _ => 0.0,
         }
     }
+
+    /// Gets the approximate bandwidth, in bytes per second, that the bus
+    /// can reserve for periodic (interrupt and isochronous) transfers at
+    /// this speed.
+    ///
+    /// This is 90% of a full or low speed frame, 80% of a high speed
+    /// microframe, and 90% of the data rate of a SuperSpeed link after
+    /// its line encoding. An unknown speed reports zero.
+    pub fn periodic_bandwidth_limit(&self) -> u64 {
+        use Speed::*;
+        match *self {
+            Low => 168_750,
+            Full => 1_350_000,
+            High => 48_000_000,
+            Super => 450_000_000,
+            SuperPlus => 1_090_909_090,
+            _ => 0,
+        }
+    }
 }
 
 impl fmt::Display for Speed {
@@ -334,6 +352,16 @@ impl Version {
         Version(major, minor, sub)
     }
 
+    /// Encodes the version as a binary coded decimal (BCD) field, as used in
+    /// USB descriptors. This is the inverse of [`Version::from_bcd`].
+    pub fn to_bcd(&self) -> u16 {
+        let major = u16::from(self.0 % 100);
+        (major / 10) << 12
+            | (major % 10) << 8
+            | u16::from(self.1 & 0x0F) << 4
+            | u16::from(self.2 & 0x0F)
+    }
+
     /// Returns the major version.
     pub fn major(&self) -> u8 {
         self.0
@@ -379,6 +407,46 @@ pub const fn request_type(
     (direction as u8) | (request_type as u8) | (recipient as u8)
 }
 
+/// The setup packet of a control transfer.
+///
+/// The fields are in host-endian byte order.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SetupPacket {
+    /// The `bmRequestType` field, as built by [`request_type()`].
+    pub request_type: u8,
+    /// The `bRequest` field.
+    pub request: u8,
+    /// The `wValue` field.
+    pub value: u16,
+    /// The `wIndex` field.
+    pub index: u16,
+    /// The `wLength` field; the number of bytes to transfer.
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// Gets the direction of the data stage of the transfer.
+    pub fn direction(&self) -> Direction {
+        if self.request_type & LIBUSB_ENDPOINT_DIR_MASK == LIBUSB_ENDPOINT_IN {
+            Direction::In
+        } else {
+            Direction::Out
+        }
+    }
+}
+
+impl fmt::Display for SetupPacket {
+    /// Outputs the fields of the packet in hex, like `80 06 0100 0000 0012`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x} {:02x} {:04x} {:04x} {:04x}",
+            self.request_type, self.request, self.value, self.index, self.length
+        )
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -457,6 +525,12 @@ mod test {
         assert_eq!(3, Version::from_bcd(0x0300).major());
     }
 
+    #[test]
+    fn version_to_bcd() {
+        assert_eq!(0x0210, Version(2, 1, 0).to_bcd());
+        assert_eq!(0x1234, Version::from_bcd(0x1234).to_bcd());
+    }
+
     #[test]
     fn version_parses_long_major_version() {
         assert_eq!(12, Version::from_bcd(0x1200).major());
@@ -579,4 +653,17 @@ mod test {
             0x03
         );
     }
+
+    #[test]
+    fn setup_packet_display() {
+        let setup = SetupPacket {
+            request_type: request_type(Direction::In, RequestType::Standard, Recipient::Device),
+            request: LIBUSB_REQUEST_GET_DESCRIPTOR,
+            value: 0x0100,
+            index: 0,
+            length: 18,
+        };
+        assert_eq!(Direction::In, setup.direction());
+        assert_eq!("80 06 0100 0000 0012", setup.to_string());
+    }
 }
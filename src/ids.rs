@@ -0,0 +1,309 @@
+// yusb/src/ids.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Vendor, product, and class names from the `usb.ids` database.
+//!
+//! The names can come from the copy of the database that is embedded in
+//! the `usb-ids` crate, or from a `usb.ids` file that is parsed at
+//! runtime, such as the one that the system keeps up to date in
+//! `/usr/share/hwdata/usb.ids`.
+
+use std::{collections::HashMap, fs, io, path::Path};
+use usb_ids::FromId;
+
+/// The places where a system usually keeps its copy of `usb.ids`.
+pub const SYSTEM_USB_IDS_PATHS: &[&str] = &[
+    "/usr/share/hwdata/usb.ids",
+    "/usr/share/misc/usb.ids",
+    "/usr/share/usb.ids",
+    "/var/lib/usbutils/usb.ids",
+];
+
+/// Looks up the name of a vendor in the embedded database.
+pub fn vendor_name(vendor_id: u16) -> Option<&'static str> {
+    usb_ids::Vendor::from_id(vendor_id).map(|vendor| vendor.name())
+}
+
+/// Looks up the name of a product in the embedded database.
+pub fn product_name(vendor_id: u16, product_id: u16) -> Option<&'static str> {
+    usb_ids::Device::from_vid_pid(vendor_id, product_id).map(|product| product.name())
+}
+
+/// Looks up the name of a class in the embedded database.
+pub fn class_name(class: u8) -> Option<&'static str> {
+    usb_ids::Class::from_id(class).map(|class| class.name())
+}
+
+/// Looks up the name of a subclass in the embedded database.
+pub fn sub_class_name(class: u8, sub_class: u8) -> Option<&'static str> {
+    usb_ids::SubClass::from_cid_scid(class, sub_class).map(|sub_class| sub_class.name())
+}
+
+/// Looks up the name of a protocol in the embedded database.
+pub fn protocol_name(class: u8, sub_class: u8, protocol: u8) -> Option<&'static str> {
+    usb_ids::Protocol::from_cid_scid_pid(class, sub_class, protocol).map(|protocol| protocol.name())
+}
+
+/// A vendor and its products.
+#[derive(Debug, Default, Clone)]
+struct VendorEntry {
+    name: String,
+    products: HashMap<u16, String>,
+}
+
+/// A class and its subclasses.
+#[derive(Debug, Default, Clone)]
+struct ClassEntry {
+    name: String,
+    sub_classes: HashMap<u8, SubClassEntry>,
+}
+
+/// A subclass and its protocols.
+#[derive(Debug, Default, Clone)]
+struct SubClassEntry {
+    name: String,
+    protocols: HashMap<u8, String>,
+}
+
+/// The section of the file that the parser is in.
+#[derive(Clone, Copy)]
+enum Section {
+    Vendor(u16),
+    Class(u8, Option<u8>),
+    Other,
+}
+
+/// A `usb.ids` database that was parsed at runtime.
+///
+/// This holds the vendors, products, classes, subclasses, and protocols
+/// from the file. The other sections, like the HID usages and languages,
+/// are skipped.
+#[derive(Debug, Default, Clone)]
+pub struct UsbIds {
+    vendors: HashMap<u16, VendorEntry>,
+    classes: HashMap<u8, ClassEntry>,
+}
+
+impl UsbIds {
+    /// Parses the contents of a `usb.ids` file.
+    ///
+    /// Lines that can't be parsed are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut ids = Self::default();
+        let mut section = Section::Other;
+
+        for line in text.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(line) = line.strip_prefix("\t\t") {
+                if let (Section::Class(class, Some(sub_class)), Some((id, name))) =
+                    (section, split_entry(line))
+                {
+                    if let (Ok(id), Some(entry)) = (
+                        u8::from_str_radix(id, 16),
+                        ids.classes
+                            .get_mut(&class)
+                            .and_then(|c| c.sub_classes.get_mut(&sub_class)),
+                    ) {
+                        entry.protocols.insert(id, name.to_string());
+                    }
+                }
+            } else if let Some(line) = line.strip_prefix('\t') {
+                let (id, name) = match split_entry(line) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                match section {
+                    Section::Vendor(vid) => {
+                        if let (Ok(pid), Some(vendor)) =
+                            (u16::from_str_radix(id, 16), ids.vendors.get_mut(&vid))
+                        {
+                            vendor.products.insert(pid, name.to_string());
+                        }
+                    }
+                    Section::Class(class, _) => {
+                        section = Section::Other;
+                        if let (Ok(sub_class), Some(entry)) =
+                            (u8::from_str_radix(id, 16), ids.classes.get_mut(&class))
+                        {
+                            entry.sub_classes.insert(
+                                sub_class,
+                                SubClassEntry {
+                                    name: name.to_string(),
+                                    ..SubClassEntry::default()
+                                },
+                            );
+                            section = Section::Class(class, Some(sub_class));
+                        }
+                    }
+                    Section::Other => {}
+                }
+            } else if let Some(line) = line.strip_prefix("C ") {
+                section = Section::Other;
+                if let Some((id, name)) = split_entry(line) {
+                    if let Ok(class) = u8::from_str_radix(id, 16) {
+                        ids.classes.insert(
+                            class,
+                            ClassEntry {
+                                name: name.to_string(),
+                                ..ClassEntry::default()
+                            },
+                        );
+                        section = Section::Class(class, None);
+                    }
+                }
+            } else {
+                // A vendor line starts with its ID, while the other
+                // sections start with a keyword.
+                section = Section::Other;
+                if let Some((id, name)) = split_entry(line) {
+                    if let (4, Ok(vid)) = (id.len(), u16::from_str_radix(id, 16)) {
+                        ids.vendors.insert(
+                            vid,
+                            VendorEntry {
+                                name: name.to_string(),
+                                ..VendorEntry::default()
+                            },
+                        );
+                        section = Section::Vendor(vid);
+                    }
+                }
+            }
+        }
+        ids
+    }
+
+    /// Reads and parses a `usb.ids` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let buf = fs::read(path)?;
+        Ok(Self::parse(&String::from_utf8_lossy(&buf)))
+    }
+
+    /// Reads the system's copy of `usb.ids`, from the first of the
+    /// [`SYSTEM_USB_IDS_PATHS`] that can be read.
+    pub fn system() -> Option<Self> {
+        SYSTEM_USB_IDS_PATHS
+            .iter()
+            .find_map(|path| Self::from_file(path).ok())
+    }
+
+    /// Gets the number of vendors in the database.
+    pub fn num_vendors(&self) -> usize {
+        self.vendors.len()
+    }
+
+    /// Looks up the name of a vendor.
+    pub fn vendor_name(&self, vendor_id: u16) -> Option<&str> {
+        self.vendors.get(&vendor_id).map(|v| v.name.as_str())
+    }
+
+    /// Looks up the name of a product.
+    pub fn product_name(&self, vendor_id: u16, product_id: u16) -> Option<&str> {
+        self.vendors
+            .get(&vendor_id)?
+            .products
+            .get(&product_id)
+            .map(String::as_str)
+    }
+
+    /// Looks up the name of a class.
+    pub fn class_name(&self, class: u8) -> Option<&str> {
+        self.classes.get(&class).map(|c| c.name.as_str())
+    }
+
+    /// Looks up the name of a subclass.
+    pub fn sub_class_name(&self, class: u8, sub_class: u8) -> Option<&str> {
+        self.classes
+            .get(&class)?
+            .sub_classes
+            .get(&sub_class)
+            .map(|s| s.name.as_str())
+    }
+
+    /// Looks up the name of a protocol.
+    pub fn protocol_name(&self, class: u8, sub_class: u8, protocol: u8) -> Option<&str> {
+        self.classes
+            .get(&class)?
+            .sub_classes
+            .get(&sub_class)?
+            .protocols
+            .get(&protocol)
+            .map(String::as_str)
+    }
+}
+
+/// Splits an entry line into its hex ID and name, which are separated by
+/// whitespace.
+fn split_entry(line: &str) -> Option<(&str, &str)> {
+    let (id, name) = line.split_once(char::is_whitespace)?;
+    let name = name.trim();
+    (id.chars().all(|c| c.is_ascii_hexdigit()) && !name.is_empty()).then(|| (id, name))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const USB_IDS: &str = "\
+# Sample of the usb.ids file
+#
+1d6b  Linux Foundation
+\t0001  1.1 root hub
+\t0002  2.0 root hub
+\t\t00  interface name, which is ignored
+abcd  Some Vendor
+C 03  Human Interface Device
+\t01  Boot Interface Subclass
+\t\t01  Keyboard
+\t\t02  Mouse
+C 08  Mass Storage
+\t06  SCSI
+\t\t50  Bulk-Only
+AT 0100  USB Streaming
+\t0101  Not a product
+HID 00  Undefined
+";
+
+    #[test]
+    fn parse_vendors_and_products() {
+        let ids = UsbIds::parse(USB_IDS);
+        assert_eq!(2, ids.num_vendors());
+        assert_eq!(Some("Linux Foundation"), ids.vendor_name(0x1d6b));
+        assert_eq!(Some("2.0 root hub"), ids.product_name(0x1d6b, 0x0002));
+        assert_eq!(Some("Some Vendor"), ids.vendor_name(0xabcd));
+        assert_eq!(None, ids.product_name(0xabcd, 0x0101));
+        assert_eq!(None, ids.vendor_name(0x0100));
+    }
+
+    #[test]
+    fn parse_classes() {
+        let ids = UsbIds::parse(USB_IDS);
+        assert_eq!(Some("Human Interface Device"), ids.class_name(0x03));
+        assert_eq!(
+            Some("Boot Interface Subclass"),
+            ids.sub_class_name(0x03, 0x01)
+        );
+        assert_eq!(Some("Keyboard"), ids.protocol_name(0x03, 0x01, 0x01));
+        assert_eq!(Some("Bulk-Only"), ids.protocol_name(0x08, 0x06, 0x50));
+        assert_eq!(None, ids.protocol_name(0x08, 0x06, 0x62));
+    }
+
+    #[test]
+    fn embedded_names() {
+        assert_eq!(Some("Linux Foundation"), vendor_name(0x1d6b));
+        assert_eq!(Some("2.0 root hub"), product_name(0x1d6b, 0x0002));
+        assert_eq!(Some("Human Interface Device"), class_name(0x03));
+        assert_eq!(Some("Keyboard"), protocol_name(0x03, 0x01, 0x01));
+    }
+}
@@ -0,0 +1,267 @@
+// yusb/src/instrument.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! `tracing` instrumentation and metrics of the USB operations.
+//!
+//! With the `tracing` feature enabled, each `DeviceHandle` carries a span
+//! that identifies the device by its port and VID:PID. Device operations
+//! emit events at the `DEBUG` level, and the individual transfers emit
+//! spans and events at the `TRACE` level, under the `yusb` target.
+//!
+//! Without the feature, these are all no-ops that compile away, apart from
+//! timing the transfers for the metrics, when they are enabled on a handle.
+//! Transfers are only timed when the metrics or an enabled span need it.
+
+use crate::{metrics::Recorder, DeviceHandle, Result};
+use std::time::Instant;
+
+#[cfg(feature = "tracing")]
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The default maximum number of payload bytes included in a hex dump.
+#[cfg(feature = "tracing")]
+pub(crate) const DEFAULT_DUMP_LIMIT: usize = 64;
+
+/// The maximum number of payload bytes included in a hex dump.
+#[cfg(feature = "tracing")]
+static DUMP_LIMIT: AtomicUsize = AtomicUsize::new(DEFAULT_DUMP_LIMIT);
+
+/// Sets the maximum number of payload bytes included in the hex dumps.
+#[cfg(feature = "tracing")]
+pub(crate) fn set_dump_limit(limit: usize) {
+    DUMP_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// Creates a hex dump of the payload, truncated to `limit` bytes.
+#[cfg(feature = "tracing")]
+pub(crate) fn hex_dump(data: &[u8], limit: usize) -> String {
+    let mut s = String::with_capacity(3 * data.len().min(limit) + 16);
+    for (i, b) in data.iter().take(limit).enumerate() {
+        if i != 0 {
+            s.push(' ');
+        }
+        let _ = write!(s, "{:02x}", b);
+    }
+    if data.len() > limit {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        let _ = write!(s, "...(+{} bytes)", data.len() - limit);
+    }
+    s
+}
+
+/// Creates the span that identifies a device handle.
+#[cfg(feature = "tracing")]
+pub(crate) fn device_span(handle: &DeviceHandle) -> tracing::Span {
+    tracing::debug_span!(
+        target: "yusb",
+        "usb_device",
+        port = %device_port(handle),
+        id = %device_id(handle),
+    )
+}
+
+#[cfg(feature = "tracing")]
+fn device_port(handle: &DeviceHandle) -> String {
+    handle
+        .port()
+        .map(|port| port.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "tracing")]
+fn device_id(handle: &DeviceHandle) -> String {
+    match handle.id() {
+        Some((vid, pid)) => format!("{:04x}:{:04x}", vid, pid),
+        None => String::from("????:????"),
+    }
+}
+
+/// Reports a device operation, like claiming an interface, and its result.
+#[cfg(feature = "tracing")]
+pub(crate) fn operation<T>(handle: &DeviceHandle, op: &'static str, arg: i32, res: &Result<T>) {
+    let _enter = handle.span().enter();
+    match res {
+        Ok(_) => tracing::debug!(target: "yusb", op, arg, result = "ok"),
+        Err(err) => tracing::debug!(target: "yusb", op, arg, result = %err),
+    }
+}
+
+/// Reports a device operation, like claiming an interface, and its result.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn operation<T>(_handle: &DeviceHandle, _op: &'static str, _arg: i32, _res: &Result<T>) {
+}
+
+/// An in-progress transfer, which is reported when it completes.
+///
+/// The result is emitted as a `tracing` event, and recorded in the transfer
+/// metrics of the handle, if they are enabled.
+pub(crate) struct Transfer<'a> {
+    metrics: Option<&'a Recorder>,
+    endpoint: u8,
+    start: Option<Instant>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'a> Transfer<'a> {
+    /// Starts a bulk or interrupt transfer on the endpoint.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn begin(
+        handle: &'a DeviceHandle,
+        kind: &'static str,
+        endpoint: u8,
+        len: usize,
+    ) -> Self {
+        Self {
+            metrics: handle.recorder(),
+            endpoint,
+            start: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::trace_span!(
+                target: "yusb",
+                parent: handle.span(),
+                "usb_transfer",
+                kind,
+                endpoint = %format_args!("{:#04x}", endpoint),
+                len,
+            ),
+        }
+        .started()
+    }
+
+    /// Starts a control transfer with the specified setup packet.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn control(
+        handle: &'a DeviceHandle,
+        kind: &'static str,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        len: usize,
+    ) -> Self {
+        Self {
+            metrics: handle.recorder(),
+            endpoint: 0,
+            start: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::trace_span!(
+                target: "yusb",
+                parent: handle.span(),
+                "usb_transfer",
+                kind,
+                endpoint = "0x00",
+                len,
+                setup = %format_args!(
+                    "{:02x} {:02x} {:04x} {:04x} {:04x}",
+                    request_type, request, value, index, len
+                ),
+            ),
+        }
+        .started()
+    }
+
+    /// Starts timing the transfer, if anything needs it.
+    ///
+    /// This avoids reading the clock when the handle has no metrics and
+    /// no `tracing` subscriber is interested in the transfer span.
+    fn started(mut self) -> Self {
+        self.start = (self.metrics.is_some() || self.traced()).then(Instant::now);
+        self
+    }
+
+    /// Determines if the transfer span is enabled.
+    #[cfg(feature = "tracing")]
+    fn traced(&self) -> bool {
+        !self.span.is_disabled()
+    }
+
+    /// Determines if the transfer span is enabled.
+    #[cfg(not(feature = "tracing"))]
+    fn traced(&self) -> bool {
+        false
+    }
+
+    /// Reports the result of the transfer, and a dump of the `n` bytes of
+    /// `data` that were transferred.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn end(self, res: &Result<usize>, data: &[u8]) {
+        let duration = self.start.map(|start| start.elapsed());
+
+        if let (Some(metrics), Some(duration)) = (self.metrics, duration) {
+            metrics.record(self.endpoint, res, duration);
+        }
+
+        #[cfg(feature = "tracing")]
+        {
+            let duration = duration.unwrap_or_default();
+            let _enter = self.span.enter();
+            match res {
+                Ok(n) => {
+                    let data = &data[..(*n).min(data.len())];
+                    tracing::trace!(
+                        target: "yusb",
+                        ?duration,
+                        transferred = n,
+                        data = %hex_dump(data, DUMP_LIMIT.load(Ordering::Relaxed)),
+                        result = "ok",
+                    );
+                }
+                Err(err) => tracing::trace!(target: "yusb", ?duration, result = %err),
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transfer(metrics: Option<&Recorder>) -> Transfer<'_> {
+        Transfer {
+            metrics,
+            endpoint: 0x81,
+            start: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::none(),
+        }
+    }
+
+    #[test]
+    fn transfers_timed_only_when_needed() {
+        let recorder = Recorder::new();
+        assert!(transfer(Some(&recorder)).started().start.is_some());
+
+        // Without a subscriber, the span is disabled.
+        assert!(transfer(None).started().start.is_none());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn hex_dump_formats_bytes() {
+        assert_eq!("", hex_dump(&[], 8));
+        assert_eq!("01 ab ff", hex_dump(&[0x01, 0xab, 0xff], 8));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn hex_dump_truncates() {
+        assert_eq!("00 01 ...(+2 bytes)", hex_dump(&[0, 1, 2, 3], 2));
+        assert_eq!("...(+1 bytes)", hex_dump(&[0], 0));
+    }
+}
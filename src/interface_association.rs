@@ -0,0 +1,284 @@
+// yusb/src/interface_association.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{
+    class_code::{ClassCode, ClassTriple},
+    raw_descriptors::RawDescriptors,
+    ConfigDescriptor,
+};
+use std::ops::RangeInclusive;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Descriptor type of an Interface Association Descriptor (IAD).
+pub(crate) const DT_INTERFACE_ASSOCIATION: u8 = 0x0B;
+
+/// A function of a composite device.
+///
+/// This is described by an Interface Association Descriptor (IAD), which
+/// groups a number of contiguous interfaces that together implement a
+/// single function of the device, such as a CDC-ACM serial port, or a UVC
+/// camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Function {
+    first_interface: u8,
+    interface_count: u8,
+    class_code: u8,
+    sub_class_code: u8,
+    protocol_code: u8,
+    description_string_index: u8,
+}
+
+impl Function {
+    /// Creates a function for the interfaces, starting at `first_interface`.
+    pub fn new(
+        first_interface: u8,
+        interface_count: u8,
+        class_code: u8,
+        sub_class_code: u8,
+        protocol_code: u8,
+        description_string_index: Option<u8>,
+    ) -> Self {
+        Self {
+            first_interface,
+            interface_count,
+            class_code,
+            sub_class_code,
+            protocol_code,
+            description_string_index: description_string_index.unwrap_or(0),
+        }
+    }
+
+    /// Parses a function from a raw Interface Association Descriptor.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        match *buf {
+            [len, DT_INTERFACE_ASSOCIATION, first, count, class, sub_class, protocol, string, ..]
+                if len >= 8 =>
+            {
+                Some(Self {
+                    first_interface: first,
+                    interface_count: count,
+                    class_code: class,
+                    sub_class_code: sub_class,
+                    protocol_code: protocol,
+                    description_string_index: string,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Encodes the function as an Interface Association Descriptor.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        [
+            8,
+            DT_INTERFACE_ASSOCIATION,
+            self.first_interface,
+            self.interface_count,
+            self.class_code,
+            self.sub_class_code,
+            self.protocol_code,
+            self.description_string_index,
+        ]
+    }
+
+    /// Returns the number of the first interface of the function.
+    pub fn first_interface(&self) -> u8 {
+        self.first_interface
+    }
+
+    /// Returns the number of contiguous interfaces in the function.
+    pub fn interface_count(&self) -> u8 {
+        self.interface_count
+    }
+
+    /// Returns the range of the interface numbers in the function.
+    ///
+    /// Any interfaces that would be numbered past 255 are left out.
+    pub fn interfaces(&self) -> RangeInclusive<u8> {
+        match self.interface_count {
+            // An empty range.
+            0 => RangeInclusive::new(1, 0),
+            n => self.first_interface..=self.first_interface.saturating_add(n - 1),
+        }
+    }
+
+    /// Determines if the interface is part of the function.
+    pub fn contains(&self, iface: u8) -> bool {
+        self.interfaces().contains(&iface)
+    }
+
+    /// Returns the function's class code.
+    pub fn class_code(&self) -> u8 {
+        self.class_code
+    }
+
+    /// Returns the function's sub class code.
+    pub fn sub_class_code(&self) -> u8 {
+        self.sub_class_code
+    }
+
+    /// Returns the function's protocol code.
+    pub fn protocol_code(&self) -> u8 {
+        self.protocol_code
+    }
+
+    /// Returns the function's class.
+    pub fn class(&self) -> ClassCode {
+        ClassCode::from_raw(self.class_code)
+    }
+
+    /// Returns the function's class, subclass, and protocol codes.
+    pub fn class_triple(&self) -> ClassTriple {
+        ClassTriple::new(self.class_code, self.sub_class_code, self.protocol_code)
+    }
+
+    /// Returns the index of the string descriptor that describes the
+    /// function.
+    pub fn description_string_index(&self) -> Option<u8> {
+        match self.description_string_index {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
+
+/// Finds all the functions in a configuration.
+///
+/// `libusb` doesn't parse IADs, so they end up in the 'extra' bytes of the
+/// configuration, or of whichever interface or endpoint precedes them.
+pub(crate) fn parse_functions(config: &ConfigDescriptor) -> Vec<Function> {
+    let parse = |extra: &[u8]| -> Vec<Function> {
+        RawDescriptors::new(extra)
+            .filter_map(Function::parse)
+            .collect()
+    };
+
+    let mut functions = parse(config.extra());
+
+    for iface in config.interfaces() {
+        for desc in iface.descriptors() {
+            functions.extend(parse(desc.extra()));
+            for ep in desc.endpoint_descriptors() {
+                if let Some(extra) = ep.extra() {
+                    functions.extend(parse(extra));
+                }
+            }
+        }
+    }
+
+    functions.sort_by_key(|f| f.first_interface);
+    functions.dedup();
+    functions
+}
+
+#[cfg(libusb_iad)]
+pub(crate) mod ffi {
+    use libusb1_sys::libusb_device;
+    use std::os::raw::c_int;
+
+    #[allow(non_snake_case)]
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    pub struct libusb_interface_association_descriptor {
+        pub bLength: u8,
+        pub bDescriptorType: u8,
+        pub bFirstInterface: u8,
+        pub bInterfaceCount: u8,
+        pub bFunctionClass: u8,
+        pub bFunctionSubClass: u8,
+        pub bFunctionProtocol: u8,
+        pub iFunction: u8,
+    }
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    pub struct libusb_interface_association_descriptor_array {
+        pub iad: *const libusb_interface_association_descriptor,
+        pub length: c_int,
+    }
+
+    extern "system" {
+        pub fn libusb_get_interface_association_descriptors(
+            dev: *mut libusb_device,
+            config_index: u8,
+            iad_array: *mut *mut libusb_interface_association_descriptor_array,
+        ) -> c_int;
+
+        pub fn libusb_free_interface_association_descriptors(
+            iad_array: *mut libusb_interface_association_descriptor_array,
+        );
+    }
+}
+
+#[cfg(libusb_iad)]
+impl From<&ffi::libusb_interface_association_descriptor> for Function {
+    fn from(iad: &ffi::libusb_interface_association_descriptor) -> Self {
+        Self {
+            first_interface: iad.bFirstInterface,
+            interface_count: iad.bInterfaceCount,
+            class_code: iad.bFunctionClass,
+            sub_class_code: iad.bFunctionSubClass,
+            protocol_code: iad.bFunctionProtocol,
+            description_string_index: iad.iFunction,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_iad() {
+        // CDC-ACM function on interfaces 2 and 3
+        let buf = [0x08, 0x0B, 0x02, 0x02, 0x02, 0x02, 0x01, 0x05];
+        let func = Function::parse(&buf).unwrap();
+
+        assert_eq!(2, func.first_interface());
+        assert_eq!(2, func.interface_count());
+        assert_eq!(2..=3, func.interfaces());
+        assert!(!func.contains(1));
+        assert!(func.contains(2));
+        assert!(func.contains(3));
+        assert!(!func.contains(4));
+        assert_eq!(0x02, func.class_code());
+        assert_eq!(0x02, func.sub_class_code());
+        assert_eq!(0x01, func.protocol_code());
+        assert_eq!(Some(5), func.description_string_index());
+        assert_eq!(buf, func.to_bytes());
+        assert_eq!(func, Function::new(2, 2, 0x02, 0x02, 0x01, Some(5)));
+    }
+
+    #[test]
+    fn interface_range_edges() {
+        let func = Function::new(255, 1, 0x0E, 0x03, 0x00, None);
+        assert_eq!(255..=255, func.interfaces());
+        assert!(func.contains(255));
+        assert_eq!(1, func.interfaces().count());
+
+        let func = Function::new(250, 10, 0x0E, 0x03, 0x00, None);
+        assert_eq!(6, func.interfaces().count());
+
+        let func = Function::new(3, 0, 0x0E, 0x03, 0x00, None);
+        assert_eq!(0, func.interfaces().count());
+        assert!(!func.contains(3));
+    }
+
+    #[test]
+    fn parse_bad_iad() {
+        assert_eq!(None, Function::parse(&[0x07, 0x0B, 0, 1, 2, 3, 4]));
+        assert_eq!(None, Function::parse(&[0x08, 0x04, 0, 1, 2, 3, 4, 0]));
+    }
+}
@@ -10,7 +10,11 @@
 // to those terms.
 //
 
-use crate::EndpointDescriptor;
+use crate::{
+    class_code::{ClassCode, ClassTriple},
+    fields::{Direction, TransferType},
+    EndpointDescriptor, Speed,
+};
 use libusb1_sys as ffi;
 use std::{fmt, slice};
 
@@ -34,6 +38,37 @@ impl<'a> Interface<'a> {
             iter: self.descriptors.iter(),
         }
     }
+
+    /// Returns the largest periodic bandwidth of any of the interface's
+    /// alternate settings, in bytes per second, when the device is
+    /// operating at the specified speed.
+    pub fn max_periodic_bandwidth(&self, speed: Speed) -> u64 {
+        self.descriptors()
+            .map(|setting| setting.periodic_bandwidth(speed))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Picks the alternate setting that meets the requirements with the
+    /// least periodic bandwidth.
+    ///
+    /// This is the setting with the smallest bandwidth that has an endpoint
+    /// matching the requirements. If several settings use the same
+    /// bandwidth, the lowest-numbered one is chosen. Returns `None` if no
+    /// setting has a suitable endpoint.
+    pub fn best_alt_setting(
+        &self,
+        requirements: &EndpointRequirements,
+    ) -> Option<InterfaceDescriptor<'a>> {
+        self.descriptors()
+            .filter(|setting| setting.find_endpoint(requirements).is_some())
+            .min_by_key(|setting| {
+                (
+                    setting.periodic_bandwidth(requirements.speed),
+                    setting.setting_number(),
+                )
+            })
+    }
 }
 
 impl<'a> From<&'a ffi::libusb_interface> for Interface<'a> {
@@ -93,6 +128,20 @@ impl<'a> InterfaceDescriptor<'a> {
         self.0.bInterfaceProtocol
     }
 
+    /// Returns the interface's class.
+    pub fn class(&self) -> ClassCode {
+        ClassCode::from_raw(self.0.bInterfaceClass)
+    }
+
+    /// Returns the interface's class, subclass, and protocol codes.
+    pub fn class_triple(&self) -> ClassTriple {
+        ClassTriple::new(
+            self.0.bInterfaceClass,
+            self.0.bInterfaceSubClass,
+            self.0.bInterfaceProtocol,
+        )
+    }
+
     /// Returns the index of the string descriptor that describes the interface.
     pub fn description_string_index(&self) -> Option<u8> {
         match self.0.iInterface {
@@ -108,14 +157,36 @@ impl<'a> InterfaceDescriptor<'a> {
 
     /// Returns an iterator over the interface's endpoint descriptors.
     pub fn endpoint_descriptors(&self) -> EndpointDescriptors<'a> {
-        let endpoints =
-            unsafe { slice::from_raw_parts(self.0.endpoint, self.0.bNumEndpoints as usize) };
+        // libusb leaves the pointer null for a setting without endpoints
+        let endpoints = match self.0.bNumEndpoints {
+            0 => &[],
+            n => unsafe { slice::from_raw_parts(self.0.endpoint, n as usize) },
+        };
 
         EndpointDescriptors {
             iter: endpoints.iter(),
         }
     }
 
+    /// Returns the total bandwidth reserved by the periodic endpoints of
+    /// this alternate setting, in bytes per second, when the device is
+    /// operating at the specified speed.
+    pub fn periodic_bandwidth(&self, speed: Speed) -> u64 {
+        self.endpoint_descriptors()
+            .map(|endpoint| endpoint.periodic_bandwidth(speed))
+            .sum()
+    }
+
+    /// Finds the first endpoint of this alternate setting that meets the
+    /// requirements.
+    pub fn find_endpoint(
+        &self,
+        requirements: &EndpointRequirements,
+    ) -> Option<EndpointDescriptor<'a>> {
+        self.endpoint_descriptors()
+            .find(|endpoint| requirements.matches(endpoint))
+    }
+
     /// Returns the unknown 'extra' bytes that libusb does not understand.
     pub fn extra(&self) -> &[u8] {
         unsafe {
@@ -145,6 +216,71 @@ impl<'a> fmt::Debug for InterfaceDescriptor<'a> {
     }
 }
 
+/// The requirements for an endpoint, used to choose an alternate setting
+/// of an interface.
+///
+/// An endpoint matches if it has the requested direction and transfer
+/// type, and can move at least the requested number of bytes per service
+/// interval at the speed of the device. For bulk and control endpoints,
+/// which have no service interval, the packet size is compared instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EndpointRequirements {
+    speed: Speed,
+    direction: Direction,
+    transfer_type: TransferType,
+    bytes_per_interval: u32,
+}
+
+impl EndpointRequirements {
+    /// Creates the requirements for an endpoint of a device operating at
+    /// the specified speed.
+    pub fn new(
+        speed: Speed,
+        direction: Direction,
+        transfer_type: TransferType,
+        bytes_per_interval: u32,
+    ) -> Self {
+        Self {
+            speed,
+            direction,
+            transfer_type,
+            bytes_per_interval,
+        }
+    }
+
+    /// Gets the speed of the device.
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Gets the required direction of the endpoint.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Gets the required transfer type of the endpoint.
+    pub fn transfer_type(&self) -> TransferType {
+        self.transfer_type
+    }
+
+    /// Gets the minimum number of bytes per service interval.
+    pub fn bytes_per_interval(&self) -> u32 {
+        self.bytes_per_interval
+    }
+
+    /// Determines if the endpoint meets the requirements.
+    pub fn matches(&self, endpoint: &EndpointDescriptor) -> bool {
+        let capacity = if endpoint.is_periodic() {
+            endpoint.bytes_per_interval(self.speed)
+        } else {
+            u32::from(endpoint.packet_size())
+        };
+        endpoint.direction() == self.direction
+            && endpoint.transfer_type() == self.transfer_type
+            && capacity >= self.bytes_per_interval
+    }
+}
+
 /// Iterator over an interface's endpoint descriptors.
 pub struct EndpointDescriptors<'a> {
     iter: slice::Iter<'a, ffi::libusb_endpoint_descriptor>,
@@ -229,6 +365,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn it_has_class_triple() {
+        let libusb_interface = interface!(interface_descriptor!(
+            bInterfaceClass: 0x08,
+            bInterfaceSubClass: 0x06,
+            bInterfaceProtocol: 0x50
+        ));
+        let setting = Interface::from(&libusb_interface)
+            .descriptors()
+            .next()
+            .unwrap();
+        assert_eq!(ClassCode::MassStorage, setting.class());
+        assert_eq!(
+            "Mass Storage: SCSI (Bulk-Only)",
+            setting.class_triple().to_string()
+        );
+    }
+
     #[test]
     fn it_has_description_string_index() {
         assert_eq!(
@@ -282,4 +436,71 @@ mod test {
 
         assert_eq!(vec![0x87], endpoint_addresses);
     }
+
+    #[test]
+    fn it_has_periodic_bandwidth() {
+        let libusb_interface = interface!(
+            interface_descriptor!(endpoint_descriptor!(bmAttributes: 0b0000_0010)),
+            interface_descriptor!(
+                endpoint_descriptor!(bmAttributes: 0b0000_0001, wMaxPacketSize: 192),
+                endpoint_descriptor!(bmAttributes: 0b0000_0011, wMaxPacketSize: 8, bInterval: 8)
+            )
+        );
+        let interface = Interface::from(&libusb_interface);
+
+        assert_eq!(
+            vec![0, 192_000 + 1_000],
+            interface
+                .descriptors()
+                .map(|setting| setting.periodic_bandwidth(Speed::Full))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(193_000, interface.max_periodic_bandwidth(Speed::Full));
+    }
+
+    #[test]
+    fn it_picks_best_alt_setting() {
+        let mut alt1 = interface_descriptor!(
+            endpoint_descriptor!(bEndpointAddress: 0x81, bmAttributes: 0b0000_0101, wMaxPacketSize: 0x1400),
+            endpoint_descriptor!(bEndpointAddress: 0x82, bmAttributes: 0b0000_0011, wMaxPacketSize: 8)
+        );
+        alt1.bAlternateSetting = 1;
+        let mut alt2 = interface_descriptor!(
+            endpoint_descriptor!(bEndpointAddress: 0x81, bmAttributes: 0b0000_0101, wMaxPacketSize: 512)
+        );
+        alt2.bAlternateSetting = 2;
+        let mut alt3 = interface_descriptor!(
+            endpoint_descriptor!(bEndpointAddress: 0x81, bmAttributes: 0b0000_0101, wMaxPacketSize: 1024)
+        );
+        alt3.bAlternateSetting = 3;
+
+        let libusb_interface = interface!(
+            interface_descriptor!(bAlternateSetting: 0),
+            alt1,
+            alt2,
+            alt3
+        );
+        let interface = Interface::from(&libusb_interface);
+
+        let req =
+            EndpointRequirements::new(Speed::High, Direction::In, TransferType::Isochronous, 600);
+        let setting = interface.best_alt_setting(&req).unwrap();
+        assert_eq!(3, setting.setting_number());
+        assert_eq!(0x81, setting.find_endpoint(&req).unwrap().address());
+
+        let req =
+            EndpointRequirements::new(Speed::High, Direction::In, TransferType::Isochronous, 2048);
+        assert_eq!(
+            1,
+            interface.best_alt_setting(&req).unwrap().setting_number()
+        );
+
+        let req =
+            EndpointRequirements::new(Speed::High, Direction::In, TransferType::Isochronous, 4096);
+        assert!(interface.best_alt_setting(&req).is_none());
+
+        let req =
+            EndpointRequirements::new(Speed::High, Direction::Out, TransferType::Isochronous, 1);
+        assert!(interface.best_alt_setting(&req).is_none());
+    }
 }
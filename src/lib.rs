@@ -51,31 +51,66 @@ pub use libusb1_sys::{self as ffi, constants};
 pub use crate::options::disable_device_discovery;
 
 pub use crate::{
+    bos_descriptor::{
+        AlternateMode, AlternateModeState, BillboardCapability, BosDescriptor, DeviceCapability,
+        Guid, PlatformCapability, RawCapability, SublinkSpeed, SuperSpeedCapability,
+        SuperSpeedPlusCapability, Usb2ExtensionCapability,
+    },
+    chapter9::Chapter9Runner,
+    class_code::{ClassCode, ClassTriple},
+    compliance::{ComplianceChecker, Finding},
     config_descriptor::{ConfigDescriptor, Interfaces},
     context::{Context, LogLevel},
+    descriptor_builder::{
+        BosDescriptorBuilder, ConfigDescriptorBuilder, DeviceDescriptorBuilder, EndpointBuilder,
+        InterfaceBuilder,
+    },
+    descriptor_parser::ParseMode,
     device::Device,
     device_descriptor::DeviceDescriptor,
     device_handle::DeviceHandle,
+    device_info::{
+        AltSettingNode, ConfigNode, DescriptorTree, DeviceInfo, DeviceNode, EndpointNode,
+        InterfaceNode,
+    },
     device_list::{DeviceList, Devices},
-    endpoint_descriptor::EndpointDescriptor,
-    error::{Error, Result},
+    endpoint_descriptor::{EndpointDescriptor, SsEndpointCompanion, SspIsoEndpointCompanion},
+    error::{DeviceError, DeviceResult, Error, ErrorTarget, Result},
+    event_thread::EventThread,
     fields::{
-        request_type, Direction, IsoSyncType, IsoUsageType, Port, Recipient, RequestType, Speed,
-        TransferType, Version,
+        request_type, Direction, IsoSyncType, IsoUsageType, Port, Recipient, RequestType,
+        SetupPacket, Speed, TransferType, Version,
     },
     hotplug::{Hotplug, HotplugBuilder, Registration},
+    interface_association::Function,
     interface_descriptor::{
-        EndpointDescriptors, Interface, InterfaceDescriptor, InterfaceDescriptors,
+        EndpointDescriptors, EndpointRequirements, Interface, InterfaceDescriptor,
+        InterfaceDescriptors,
     },
     language::{Language, PrimaryLanguage, SubLanguage},
+    logging::{LogCallback, LIBUSB_LOG_TARGET},
+    metrics::{LatencyStats, TransferMetrics, TransferStats},
+    ms_os_descriptor::{
+        CompatIdFunction, CompatibleId, ExtendedCompatId, ExtendedProperties,
+        MsOs20ConfigurationSubset, MsOs20DescriptorSet, MsOs20DescriptorSetInfo, MsOs20Feature,
+        MsOs20FunctionSubset, MsOsStringDescriptor, RegistryProperty, RegistryValue,
+    },
     options::UsbOption,
+    timeout::Timeout,
     version::{version, LibraryVersion},
+    webusb::{UrlScheme, WebUsbCapability, WebUsbUrl},
 };
 
+#[cfg(feature = "usb-ids")]
+pub use crate::ids::UsbIds;
+
 #[cfg(test)]
 #[macro_use]
 mod test_helpers;
 
+#[cfg(test)]
+mod test_fixtures;
+
 #[macro_use]
 mod error;
 mod version;
@@ -83,16 +118,35 @@ mod version;
 mod context;
 mod device;
 mod device_handle;
+mod device_info;
 mod device_list;
+mod event_thread;
+
+#[cfg(feature = "usb-ids")]
+pub mod ids;
 
+pub mod bos_descriptor;
+pub mod chapter9;
+mod class_code;
+pub mod compliance;
 mod config_descriptor;
+pub mod descriptor_builder;
+mod descriptor_parser;
 mod device_descriptor;
 mod endpoint_descriptor;
 mod fields;
 mod hotplug;
+mod instrument;
+mod interface_association;
 mod interface_descriptor;
 mod language;
+pub mod logging;
+mod metrics;
+pub mod ms_os_descriptor;
 mod options;
+mod raw_descriptors;
+mod timeout;
+pub mod webusb;
 
 /// Tests whether the running `libusb` library supports capability API.
 pub fn has_capability() -> bool {
@@ -130,6 +184,19 @@ pub fn set_log_level(level: LogLevel) {
     }
 }
 
+/// Sets where the log messages for all `libusb` contexts are sent.
+///
+/// This installs a global log callback that receives the messages from
+/// every context, and sets the log level of the global context to match the
+/// maximum level currently enabled in the selected logger. The messages from
+/// other contexts are still filtered by their own log levels.
+///
+/// Returns `NotSupported` if the linked `libusb` is too old to support log
+/// callbacks.
+pub fn set_log_callback(cb: LogCallback) -> Result<()> {
+    cb.apply(Context::global().as_raw(), true)
+}
+
 /// Convenience function to open a device by its vendor ID and product ID.
 /// Using global context
 ///
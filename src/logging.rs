@@ -0,0 +1,215 @@
+// yusb/src/logging.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Routing of `libusb` log messages into the Rust logging ecosystem.
+//!
+//! By default `libusb` prints its log messages to `stderr`. With the `log`
+//! or `tracing` features enabled, the messages can instead be forwarded to
+//! the [log](https://docs.rs/log) crate or emitted as
+//! [tracing](https://docs.rs/tracing) events, under the target
+//! [`LIBUSB_LOG_TARGET`].
+//!
+//! This requires `libusb` v1.0.23 or later, which is detected at build time.
+//!
+//! The `tracing` feature also instruments the USB operations themselves.
+//! Opening and closing devices, claiming interfaces, and changing the
+//! configuration or alternate settings emit `DEBUG` events, and every
+//! control, bulk, and interrupt transfer emits a `TRACE` span and event
+//! with the endpoint, length, duration, result, and a hex dump of the
+//! payload, under the `yusb` target. The events are recorded within a span
+//! that identifies the device by its [`Port`](crate::Port) and VID:PID.
+
+use crate::LogLevel;
+
+#[cfg(libusb_set_log_cb)]
+use crate::{Error, Result};
+
+#[cfg(libusb_set_log_cb)]
+use libusb1_sys::{self as ffi, constants::*};
+
+#[cfg(all(libusb_set_log_cb, any(feature = "log", feature = "tracing")))]
+use std::{
+    ffi::{c_void, CStr},
+    os::raw::c_int,
+};
+
+/// The log target used for messages forwarded from `libusb`.
+pub const LIBUSB_LOG_TARGET: &str = "yusb::libusb";
+
+/// Sets the maximum number of payload bytes included in the hex dumps of
+/// the transfer events.
+///
+/// Longer payloads are truncated, noting the number of bytes left out. The
+/// default is 64 bytes.
+#[cfg(feature = "tracing")]
+pub fn set_payload_dump_limit(limit: usize) {
+    crate::instrument::set_dump_limit(limit);
+}
+
+/// Where to send the log messages from `libusb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogCallback {
+    /// Restore the default `libusb` behavior of printing to `stderr`.
+    Stderr,
+    /// Forward the messages to the `log` crate.
+    #[cfg(feature = "log")]
+    Log,
+    /// Emit the messages as `tracing` events.
+    #[cfg(feature = "tracing")]
+    Tracing,
+}
+
+impl LogCallback {
+    /// Gets the most verbose level that the selected logger would accept.
+    ///
+    /// `libusb` discards any messages above its own log level before they
+    /// reach the callback, so this is the level that it should be set to.
+    /// Returns `None` for [`LogCallback::Stderr`], which leaves the level
+    /// unchanged.
+    pub fn max_level(&self) -> Option<LogLevel> {
+        match *self {
+            LogCallback::Stderr => None,
+            #[cfg(feature = "log")]
+            LogCallback::Log => Some(match log::max_level() {
+                log::LevelFilter::Off => LogLevel::None,
+                log::LevelFilter::Error => LogLevel::Error,
+                log::LevelFilter::Warn => LogLevel::Warning,
+                log::LevelFilter::Info => LogLevel::Info,
+                _ => LogLevel::Debug,
+            }),
+            #[cfg(feature = "tracing")]
+            LogCallback::Tracing => {
+                use tracing::level_filters::LevelFilter;
+                Some(match LevelFilter::current() {
+                    LevelFilter::OFF => LogLevel::None,
+                    LevelFilter::ERROR => LogLevel::Error,
+                    LevelFilter::WARN => LogLevel::Warning,
+                    LevelFilter::INFO => LogLevel::Info,
+                    _ => LogLevel::Debug,
+                })
+            }
+        }
+    }
+
+    /// Gets the raw `libusb` callback function for the logger.
+    #[cfg(libusb_set_log_cb)]
+    pub(crate) fn as_raw(&self) -> Option<ffi::libusb_log_cb> {
+        match *self {
+            LogCallback::Stderr => None,
+            #[cfg(feature = "log")]
+            LogCallback::Log => Some(log_callback),
+            #[cfg(feature = "tracing")]
+            LogCallback::Tracing => Some(tracing_callback),
+        }
+    }
+
+    /// Installs the callback for the context.
+    ///
+    /// If `ctx` is null and `global` is set, the callback is installed as
+    /// the global one, which receives the messages from all the contexts.
+    #[cfg(libusb_set_log_cb)]
+    pub(crate) fn apply(&self, ctx: *mut ffi::libusb_context, global: bool) -> Result<()> {
+        let mode = if global {
+            LIBUSB_LOG_CB_GLOBAL
+        } else {
+            LIBUSB_LOG_CB_CONTEXT
+        };
+        unsafe {
+            ffi::libusb_set_log_cb(ctx, self.as_raw(), mode);
+        }
+
+        if let Some(level) = self.max_level() {
+            let rc =
+                unsafe { ffi::libusb_set_option(ctx, LIBUSB_OPTION_LOG_LEVEL, level.as_c_int()) };
+            if rc != LIBUSB_SUCCESS {
+                return Err(Error::from(rc));
+            }
+        }
+        Ok(())
+    }
+
+    /// Installs the callback for the context.
+    ///
+    /// The linked `libusb` does not support log callbacks.
+    #[cfg(not(libusb_set_log_cb))]
+    pub(crate) fn apply(
+        &self,
+        _ctx: *mut libusb1_sys::libusb_context,
+        _global: bool,
+    ) -> crate::Result<()> {
+        Err(crate::Error::NotSupported)
+    }
+}
+
+/// Gets the text of a `libusb` log message, without the trailing newline.
+///
+/// # Safety
+///
+/// `msg` must be null or point to a valid, nul-terminated C string.
+#[cfg(all(libusb_set_log_cb, any(feature = "log", feature = "tracing")))]
+unsafe fn message(msg: *mut c_void) -> Option<String> {
+    if msg.is_null() {
+        return None;
+    }
+    let msg = CStr::from_ptr(msg as *const _).to_string_lossy();
+    Some(msg.trim_end().to_string())
+}
+
+/// The `libusb` callback that forwards messages to the `log` crate.
+#[cfg(all(libusb_set_log_cb, feature = "log"))]
+extern "system" fn log_callback(_ctx: *mut ffi::libusb_context, level: c_int, msg: *mut c_void) {
+    let _ = std::panic::catch_unwind(|| {
+        let level = match LogLevel::from(level) {
+            LogLevel::None => return,
+            LogLevel::Error => log::Level::Error,
+            LogLevel::Warning => log::Level::Warn,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Debug => log::Level::Debug,
+        };
+        if let Some(msg) = unsafe { message(msg) } {
+            log::log!(target: LIBUSB_LOG_TARGET, level, "{}", msg);
+        }
+    });
+}
+
+/// The `libusb` callback that emits messages as `tracing` events.
+#[cfg(all(libusb_set_log_cb, feature = "tracing"))]
+extern "system" fn tracing_callback(
+    _ctx: *mut ffi::libusb_context,
+    level: c_int,
+    msg: *mut c_void,
+) {
+    let _ = std::panic::catch_unwind(|| {
+        let msg = match unsafe { message(msg) } {
+            Some(msg) => msg,
+            None => return,
+        };
+        match LogLevel::from(level) {
+            LogLevel::None => (),
+            LogLevel::Error => tracing::error!(target: LIBUSB_LOG_TARGET, "{}", msg),
+            LogLevel::Warning => tracing::warn!(target: LIBUSB_LOG_TARGET, "{}", msg),
+            LogLevel::Info => tracing::info!(target: LIBUSB_LOG_TARGET, "{}", msg),
+            LogLevel::Debug => tracing::debug!(target: LIBUSB_LOG_TARGET, "{}", msg),
+        }
+    });
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stderr_has_no_level() {
+        assert_eq!(None, LogCallback::Stderr.max_level());
+    }
+}
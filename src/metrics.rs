@@ -0,0 +1,400 @@
+// yusb/src/metrics.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Opt-in transfer statistics for a [`DeviceHandle`](crate::DeviceHandle).
+//!
+//! When enabled with [`DeviceHandle::enable_metrics`](crate::DeviceHandle::enable_metrics),
+//! every control, bulk, and interrupt transfer on the handle is counted,
+//! per endpoint and in total. A copy of the current values can be taken at
+//! any time with [`DeviceHandle::metrics`](crate::DeviceHandle::metrics).
+//!
+//! Latencies are kept in a log-scale histogram with eight linear buckets
+//! per power of two, so the percentiles are accurate to within about 12%,
+//! using a fixed amount of memory regardless of the number of transfers.
+
+use crate::{Error, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The number of linear sub-buckets per power of two, as a bit count.
+const SUB_BUCKET_BITS: u32 = 3;
+
+/// The number of linear sub-buckets per power of two.
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// The number of histogram buckets needed to cover any `u64` value.
+const NUM_BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A snapshot of the transfer statistics of a device handle.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransferMetrics {
+    /// The time over which the statistics were collected, since they were
+    /// enabled or last reset.
+    pub elapsed: Duration,
+    /// The statistics of all the transfers on the device.
+    pub total: TransferStats,
+    /// The statistics for each endpoint, by endpoint address.
+    ///
+    /// All control transfers are counted under endpoint `0x00`.
+    pub endpoints: BTreeMap<u8, TransferStats>,
+}
+
+impl TransferMetrics {
+    /// Gets the statistics for the specified endpoint address, if any
+    /// transfers were made on it.
+    pub fn endpoint(&self, endpoint: u8) -> Option<&TransferStats> {
+        self.endpoints.get(&endpoint)
+    }
+}
+
+/// The statistics of the transfers on an endpoint, or a whole device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransferStats {
+    /// The number of transfers attempted.
+    pub transfers: u64,
+    /// The number of bytes transferred successfully.
+    pub bytes: u64,
+    /// The number of transfers that failed, by error.
+    ///
+    /// This includes the transfers that timed out.
+    pub errors: HashMap<Error, u64>,
+    /// The number of transfers that timed out.
+    pub timeouts: u64,
+    /// The latency of the transfers, successful or not.
+    pub latency: LatencyStats,
+}
+
+impl TransferStats {
+    /// Gets the total number of failed transfers.
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+}
+
+/// A summary of the distribution of the transfer latencies.
+///
+/// All the values are zero if no transfers were made.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LatencyStats {
+    /// The shortest latency.
+    pub min: Duration,
+    /// The longest latency.
+    pub max: Duration,
+    /// The mean latency.
+    pub mean: Duration,
+    /// The median latency.
+    pub p50: Duration,
+    /// The 90th percentile latency.
+    pub p90: Duration,
+    /// The 99th percentile latency.
+    pub p99: Duration,
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+/// A log-scale histogram of latencies, in microseconds.
+#[derive(Clone)]
+struct Histogram {
+    buckets: Box<[u64]>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: vec![0; NUM_BUCKETS].into_boxed_slice(),
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    /// Gets the bucket index for a value.
+    fn index(v: u64) -> usize {
+        if v < SUB_BUCKETS as u64 {
+            v as usize
+        } else {
+            let shift = 63 - v.leading_zeros() - SUB_BUCKET_BITS;
+            let sub = (v >> shift) as usize & (SUB_BUCKETS - 1);
+            (shift as usize + 1) * SUB_BUCKETS + sub
+        }
+    }
+
+    /// Gets the highest value that lands in the bucket.
+    fn upper_bound(idx: usize) -> u64 {
+        if idx < SUB_BUCKETS {
+            idx as u64
+        } else {
+            let shift = (idx / SUB_BUCKETS - 1) as u32;
+            let low = ((SUB_BUCKETS + idx % SUB_BUCKETS) as u64) << shift;
+            low + ((1u64 << shift) - 1)
+        }
+    }
+
+    fn record(&mut self, v: u64) {
+        self.buckets[Self::index(v)] += 1;
+        self.count += 1;
+        self.sum += u128::from(v);
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *a += b;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Gets the value at the specified quantile, in the range [0.0, 1.0].
+    fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return Self::upper_bound(idx).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    fn stats(&self) -> LatencyStats {
+        if self.count == 0 {
+            return LatencyStats::default();
+        }
+        LatencyStats {
+            min: Duration::from_micros(self.min),
+            max: Duration::from_micros(self.max),
+            mean: Duration::from_micros((self.sum / u128::from(self.count)) as u64),
+            p50: Duration::from_micros(self.quantile(0.50)),
+            p90: Duration::from_micros(self.quantile(0.90)),
+            p99: Duration::from_micros(self.quantile(0.99)),
+        }
+    }
+}
+
+/// The running counters for an endpoint.
+#[derive(Clone)]
+struct Counters {
+    transfers: u64,
+    bytes: u64,
+    errors: HashMap<Error, u64>,
+    latency: Histogram,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Self {
+            transfers: 0,
+            bytes: 0,
+            errors: HashMap::new(),
+            latency: Histogram::new(),
+        }
+    }
+
+    fn record(&mut self, res: &Result<usize>, latency: Duration) {
+        self.transfers += 1;
+        match res {
+            Ok(n) => self.bytes += *n as u64,
+            Err(err) => *self.errors.entry(*err).or_insert(0) += 1,
+        }
+        self.latency
+            .record(u64::try_from(latency.as_micros()).unwrap_or(u64::MAX));
+    }
+
+    fn merge(&mut self, other: &Counters) {
+        self.transfers += other.transfers;
+        self.bytes += other.bytes;
+        for (err, n) in &other.errors {
+            *self.errors.entry(*err).or_insert(0) += n;
+        }
+        self.latency.merge(&other.latency);
+    }
+
+    fn stats(&self) -> TransferStats {
+        TransferStats {
+            transfers: self.transfers,
+            bytes: self.bytes,
+            errors: self.errors.clone(),
+            timeouts: self.errors.get(&Error::Timeout).copied().unwrap_or(0),
+            latency: self.latency.stats(),
+        }
+    }
+}
+
+/// The state of the recorder, protected by its lock.
+struct Inner {
+    start: Instant,
+    endpoints: BTreeMap<u8, Counters>,
+}
+
+impl Inner {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            endpoints: BTreeMap::new(),
+        }
+    }
+}
+
+/// Collects the transfer statistics for a device handle.
+pub(crate) struct Recorder {
+    inner: Mutex<Inner>,
+}
+
+impl Recorder {
+    /// Creates a new, empty, recorder.
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner::new()),
+        }
+    }
+
+    /// Records the result of a transfer on the endpoint.
+    pub(crate) fn record(&self, endpoint: u8, res: &Result<usize>, latency: Duration) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner
+                .endpoints
+                .entry(endpoint)
+                .or_insert_with(Counters::new)
+                .record(res, latency);
+        }
+    }
+
+    /// Gets a snapshot of the statistics.
+    pub(crate) fn snapshot(&self) -> TransferMetrics {
+        let inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut total = Counters::new();
+        let mut endpoints = BTreeMap::new();
+
+        for (ep, counters) in &inner.endpoints {
+            total.merge(counters);
+            endpoints.insert(*ep, counters.stats());
+        }
+
+        TransferMetrics {
+            elapsed: inner.start.elapsed(),
+            total: total.stats(),
+            endpoints,
+        }
+    }
+
+    /// Clears all the statistics, and restarts the collection period.
+    pub(crate) fn reset(&self) {
+        match self.inner.lock() {
+            Ok(mut inner) => *inner = Inner::new(),
+            Err(poisoned) => *poisoned.into_inner() = Inner::new(),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_ordered() {
+        let mut last = 0;
+        for v in (0..100_000u64).chain([u64::MAX / 2, u64::MAX]) {
+            let idx = Histogram::index(v);
+            assert!(idx < NUM_BUCKETS);
+            assert!(idx >= last);
+            assert!(v <= Histogram::upper_bound(idx));
+            last = idx;
+        }
+    }
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut hist = Histogram::new();
+        for v in 1..=100 {
+            hist.record(v);
+        }
+        let stats = hist.stats();
+        assert_eq!(Duration::from_micros(1), stats.min);
+        assert_eq!(Duration::from_micros(100), stats.max);
+        assert_eq!(Duration::from_micros(50), stats.mean);
+
+        // Within the precision of the buckets
+        assert!((50..=56).contains(&hist.quantile(0.5)));
+        assert!((90..=96).contains(&hist.quantile(0.9)));
+        assert!((99..=100).contains(&hist.quantile(0.99)));
+    }
+
+    #[test]
+    fn empty_stats_are_zero() {
+        let rec = Recorder::new();
+        let metrics = rec.snapshot();
+        assert_eq!(0, metrics.total.transfers);
+        assert_eq!(LatencyStats::default(), metrics.total.latency);
+        assert!(metrics.endpoints.is_empty());
+    }
+
+    #[test]
+    fn recorder_counts_per_endpoint() {
+        let rec = Recorder::new();
+        rec.record(0x81, &Ok(64), Duration::from_micros(100));
+        rec.record(0x81, &Err(Error::Timeout), Duration::from_millis(10));
+        rec.record(0x02, &Ok(16), Duration::from_micros(200));
+        rec.record(0x02, &Err(Error::Pipe), Duration::from_micros(50));
+
+        let metrics = rec.snapshot();
+
+        let ep = metrics.endpoint(0x81).unwrap();
+        assert_eq!(2, ep.transfers);
+        assert_eq!(64, ep.bytes);
+        assert_eq!(1, ep.timeouts);
+        assert_eq!(1, ep.error_count());
+
+        let ep = metrics.endpoint(0x02).unwrap();
+        assert_eq!(Some(&1), ep.errors.get(&Error::Pipe));
+        assert_eq!(0, ep.timeouts);
+
+        assert_eq!(4, metrics.total.transfers);
+        assert_eq!(80, metrics.total.bytes);
+        assert_eq!(2, metrics.total.error_count());
+        assert_eq!(Duration::from_micros(50), metrics.total.latency.min);
+        assert_eq!(Duration::from_millis(10), metrics.total.latency.max);
+
+        rec.reset();
+        assert_eq!(
+            TransferMetrics::default().endpoints,
+            rec.snapshot().endpoints
+        );
+    }
+}
@@ -0,0 +1,976 @@
+// yusb/src/ms_os_descriptor.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Microsoft OS descriptors, versions 1.0 and 2.0.
+//!
+//! These are vendor-specific descriptors that let Windows automatically
+//! bind a driver, such as WinUSB, to a device or function, and set
+//! registry properties for it, like the device interface GUIDs.
+//!
+//! With version 1.0, the device reports a vendor request code in the
+//! special string descriptor at index 0xEE. That code is then used to
+//! request the Extended Compat ID and Extended Properties descriptors.
+//!
+//! With version 2.0, the device reports the vendor code, and the length of
+//! the whole descriptor set, in a platform capability of its BOS
+//! descriptor.
+
+use crate::{bos_descriptor::PlatformCapability, Error, Guid, Result};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The index of the MS OS 1.0 string descriptor.
+pub const MS_OS_STRING_INDEX: u8 = 0xEE;
+
+/// The `wIndex` of the request for the MS OS 1.0 Extended Compat ID
+/// descriptor.
+pub const EXTENDED_COMPAT_ID_INDEX: u16 = 0x0004;
+
+/// The `wIndex` of the request for the MS OS 1.0 Extended Properties
+/// descriptor.
+pub const EXTENDED_PROPERTIES_INDEX: u16 = 0x0005;
+
+/// The `wIndex` of the request for the MS OS 2.0 descriptor set.
+pub const MS_OS_20_DESCRIPTOR_INDEX: u16 = 0x0007;
+
+/// The UUID of the MS OS 2.0 platform capability in the BOS descriptor.
+pub const MS_OS_20_PLATFORM_UUID: Guid = Guid::from_fields(
+    0xD8DD60DF,
+    0x4589,
+    0x4CC7,
+    [0x9C, 0xD2, 0x65, 0x9D, 0x9E, 0x64, 0x8A, 0x9F],
+);
+
+/// The signature in the MS OS 1.0 string descriptor, "MSFT100".
+const MS_OS_SIGNATURE: &[u8] = b"M\0S\0F\0T\x001\x000\x000\0";
+
+/// The size of the header of the Extended Compat ID descriptor.
+pub(crate) const EXTENDED_COMPAT_ID_HEADER_SIZE: usize = 16;
+
+/// The size of each function section of the Extended Compat ID descriptor.
+const EXTENDED_COMPAT_ID_FUNCTION_SIZE: usize = 24;
+
+/// The size of the header of the Extended Properties descriptor.
+pub(crate) const EXTENDED_PROPERTIES_HEADER_SIZE: usize = 10;
+
+// MS OS 2.0 descriptor types
+const MS_OS_20_SET_HEADER_DESCRIPTOR: u16 = 0x00;
+const MS_OS_20_SUBSET_HEADER_CONFIGURATION: u16 = 0x01;
+const MS_OS_20_SUBSET_HEADER_FUNCTION: u16 = 0x02;
+const MS_OS_20_FEATURE_COMPATIBLE_ID: u16 = 0x03;
+const MS_OS_20_FEATURE_REG_PROPERTY: u16 = 0x04;
+const MS_OS_20_FEATURE_MIN_RESUME_TIME: u16 = 0x05;
+const MS_OS_20_FEATURE_MODEL_ID: u16 = 0x06;
+const MS_OS_20_FEATURE_CCGP_DEVICE: u16 = 0x07;
+const MS_OS_20_FEATURE_VENDOR_REVISION: u16 = 0x08;
+
+/// Reads a little-endian u16 from the buffer at the offset.
+fn le_u16(buf: &[u8], off: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*buf.get(off)?, *buf.get(off + 1)?]))
+}
+
+/// Reads a little-endian u32 from the buffer at the offset.
+fn le_u32(buf: &[u8], off: usize) -> Option<u32> {
+    let b = buf.get(off..off + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Decodes a UTF-16LE string, removing any trailing nulls.
+fn utf16_string(buf: &[u8]) -> Result<String> {
+    let chars: Vec<u16> = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    let s = String::from_utf16(&chars).map_err(|_| Error::BadUnicode)?;
+    Ok(s.trim_end_matches('\0').to_string())
+}
+
+/// Decodes a null-padded ASCII ID, like a compatible ID.
+fn ascii_id(buf: &[u8]) -> Result<String> {
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let id = &buf[..end];
+    if !id.is_ascii() {
+        return Err(Error::BadDescriptor);
+    }
+    Ok(String::from_utf8_lossy(id).into_owned())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// MS OS 1.0
+
+/// The MS OS 1.0 string descriptor, at string index 0xEE.
+///
+/// This tells that the device supports MS OS 1.0 descriptors, and gives
+/// the vendor request code that is used to retrieve them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MsOsStringDescriptor {
+    vendor_code: u8,
+    flags: u8,
+}
+
+impl MsOsStringDescriptor {
+    /// Parses the string descriptor from its raw bytes, as read from the
+    /// device.
+    ///
+    /// Returns `Error::NotFound` if the descriptor doesn't have the
+    /// "MSFT100" signature, as is the case for a device that uses string
+    /// index 0xEE for something else.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 18 || buf[0] < 18 || buf[1] != libusb1_sys::constants::LIBUSB_DT_STRING {
+            return Err(Error::BadDescriptor);
+        }
+        if &buf[2..16] != MS_OS_SIGNATURE {
+            return Err(Error::NotFound);
+        }
+        Ok(Self {
+            vendor_code: buf[16],
+            flags: buf[17],
+        })
+    }
+
+    /// Gets the vendor request code to retrieve the OS feature descriptors.
+    pub fn vendor_code(&self) -> u8 {
+        self.vendor_code
+    }
+
+    /// Gets the flags byte.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Determines if the device supports the ContainerID descriptor.
+    pub fn container_id_supported(&self) -> bool {
+        self.flags & 0x02 != 0
+    }
+}
+
+/// A compatible ID and sub-compatible ID, which Windows uses to find a
+/// driver, such as `WINUSB`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompatibleId {
+    id: String,
+    sub_id: String,
+}
+
+impl CompatibleId {
+    /// Parses the 8-byte compatible and sub-compatible IDs.
+    fn parse(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 16 {
+            return Err(Error::BadDescriptor);
+        }
+        Ok(Self {
+            id: ascii_id(&buf[..8])?,
+            sub_id: ascii_id(&buf[8..16])?,
+        })
+    }
+
+    /// Gets the compatible ID, like "WINUSB".
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Gets the sub-compatible ID, which is usually empty.
+    pub fn sub_id(&self) -> &str {
+        &self.sub_id
+    }
+}
+
+/// A function section of the MS OS 1.0 Extended Compat ID descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompatIdFunction {
+    first_interface: u8,
+    compatible_id: CompatibleId,
+}
+
+impl CompatIdFunction {
+    /// Gets the number of the first interface of the function.
+    pub fn first_interface(&self) -> u8 {
+        self.first_interface
+    }
+
+    /// Gets the compatible ID of the function.
+    pub fn compatible_id(&self) -> &CompatibleId {
+        &self.compatible_id
+    }
+}
+
+/// The MS OS 1.0 Extended Compat ID descriptor.
+///
+/// This gives the compatible ID of each function of the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExtendedCompatId {
+    version: u16,
+    functions: Vec<CompatIdFunction>,
+}
+
+impl ExtendedCompatId {
+    /// Parses the descriptor from its raw bytes, as read from the device.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        let len = le_u32(buf, 0).ok_or(Error::BadDescriptor)? as usize;
+        if len < EXTENDED_COMPAT_ID_HEADER_SIZE
+            || buf.len() < len
+            || le_u16(buf, 6) != Some(EXTENDED_COMPAT_ID_INDEX)
+        {
+            return Err(Error::BadDescriptor);
+        }
+
+        let version = le_u16(buf, 4).ok_or(Error::BadDescriptor)?;
+        let count = usize::from(buf[8]);
+
+        let sections = &buf[EXTENDED_COMPAT_ID_HEADER_SIZE..len];
+        if sections.len() < count * EXTENDED_COMPAT_ID_FUNCTION_SIZE {
+            return Err(Error::BadDescriptor);
+        }
+
+        let functions = sections
+            .chunks_exact(EXTENDED_COMPAT_ID_FUNCTION_SIZE)
+            .take(count)
+            .map(|sect| {
+                Ok(CompatIdFunction {
+                    first_interface: sect[0],
+                    compatible_id: CompatibleId::parse(&sect[2..18])?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { version, functions })
+    }
+
+    /// Gets the version of the descriptor, in BCD. This should be 0x0100.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Gets the function sections of the descriptor.
+    pub fn functions(&self) -> &[CompatIdFunction] {
+        &self.functions
+    }
+
+    /// Gets the compatible ID of the function that starts at the
+    /// interface, if any.
+    pub fn compatible_id(&self, first_interface: u8) -> Option<&CompatibleId> {
+        self.functions
+            .iter()
+            .find(|f| f.first_interface == first_interface)
+            .map(|f| &f.compatible_id)
+    }
+}
+
+/// The MS OS 1.0 Extended Properties descriptor.
+///
+/// This holds the registry properties of a device or interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExtendedProperties {
+    version: u16,
+    properties: Vec<RegistryProperty>,
+}
+
+impl ExtendedProperties {
+    /// Parses the descriptor from its raw bytes, as read from the device.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        let len = le_u32(buf, 0).ok_or(Error::BadDescriptor)? as usize;
+        if len < EXTENDED_PROPERTIES_HEADER_SIZE
+            || buf.len() < len
+            || le_u16(buf, 6) != Some(EXTENDED_PROPERTIES_INDEX)
+        {
+            return Err(Error::BadDescriptor);
+        }
+
+        let version = le_u16(buf, 4).ok_or(Error::BadDescriptor)?;
+        let count = le_u16(buf, 8).ok_or(Error::BadDescriptor)?;
+
+        let mut rest = &buf[EXTENDED_PROPERTIES_HEADER_SIZE..len];
+        let mut properties = Vec::with_capacity(usize::from(count));
+
+        for _ in 0..count {
+            // dwSize, dwPropertyDataType, wPropertyNameLength, bPropertyName,
+            // dwPropertyDataLength, bPropertyData
+            let size = le_u32(rest, 0).ok_or(Error::BadDescriptor)? as usize;
+            if size < 14 || rest.len() < size {
+                return Err(Error::BadDescriptor);
+            }
+            let sect = &rest[..size];
+            let data_type = le_u32(sect, 4).ok_or(Error::BadDescriptor)?;
+            let name_len = usize::from(le_u16(sect, 8).ok_or(Error::BadDescriptor)?);
+            let name = sect.get(10..10 + name_len).ok_or(Error::BadDescriptor)?;
+            let data_len = le_u32(sect, 10 + name_len).ok_or(Error::BadDescriptor)? as usize;
+            let data = sect
+                .get(14 + name_len..14 + name_len + data_len)
+                .ok_or(Error::BadDescriptor)?;
+
+            properties.push(RegistryProperty::new(name, data_type, data)?);
+            rest = &rest[size..];
+        }
+
+        Ok(Self {
+            version,
+            properties,
+        })
+    }
+
+    /// Gets the version of the descriptor, in BCD. This should be 0x0100.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Gets the registry properties.
+    pub fn properties(&self) -> &[RegistryProperty] {
+        &self.properties
+    }
+
+    /// Finds a registry property by name, ignoring case, as Windows does.
+    pub fn property(&self, name: &str) -> Option<&RegistryProperty> {
+        find_property(&self.properties, name)
+    }
+}
+
+/// Finds a registry property by name, ignoring case.
+fn find_property<'a>(props: &'a [RegistryProperty], name: &str) -> Option<&'a RegistryProperty> {
+    props.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Registry properties
+
+/// A registry property, as set for a device by an MS OS descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistryProperty {
+    name: String,
+    value: RegistryValue,
+}
+
+impl RegistryProperty {
+    /// Creates a property from the raw UTF-16 name, the data type, and the
+    /// raw data.
+    fn new(name: &[u8], data_type: u32, data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            name: utf16_string(name)?,
+            value: RegistryValue::new(data_type, data)?,
+        })
+    }
+
+    /// Gets the name of the property, like "DeviceInterfaceGUIDs".
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the value of the property.
+    pub fn value(&self) -> &RegistryValue {
+        &self.value
+    }
+}
+
+/// The value of a registry property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum RegistryValue {
+    /// A string (`REG_SZ`).
+    String(String),
+    /// A string with environment variable references (`REG_EXPAND_SZ`).
+    ExpandString(String),
+    /// Free-form binary data (`REG_BINARY`).
+    Binary(Vec<u8>),
+    /// A little-endian 32-bit number (`REG_DWORD_LITTLE_ENDIAN`).
+    DwordLittleEndian(u32),
+    /// A big-endian 32-bit number (`REG_DWORD_BIG_ENDIAN`).
+    DwordBigEndian(u32),
+    /// A symbolic link (`REG_LINK`).
+    Link(String),
+    /// A list of strings (`REG_MULTI_SZ`).
+    MultiString(Vec<String>),
+    /// A value of an unknown type, with the raw type and data.
+    Unknown(u32, Vec<u8>),
+}
+
+impl RegistryValue {
+    /// Decodes a value from the raw data type and data.
+    fn new(data_type: u32, data: &[u8]) -> Result<Self> {
+        let dword = || le_u32(data, 0).ok_or(Error::BadDescriptor);
+
+        Ok(match data_type {
+            1 => RegistryValue::String(utf16_string(data)?),
+            2 => RegistryValue::ExpandString(utf16_string(data)?),
+            3 => RegistryValue::Binary(data.to_vec()),
+            4 => RegistryValue::DwordLittleEndian(dword()?),
+            5 => RegistryValue::DwordBigEndian(dword()?.swap_bytes()),
+            6 => RegistryValue::Link(utf16_string(data)?),
+            7 => RegistryValue::MultiString(
+                utf16_string(data)?.split('\0').map(String::from).collect(),
+            ),
+            _ => RegistryValue::Unknown(data_type, data.to_vec()),
+        })
+    }
+
+    /// Gets the value as a string, if it is one of the string types.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            RegistryValue::String(s) | RegistryValue::ExpandString(s) | RegistryValue::Link(s) => {
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a 32-bit number, if it is one.
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            RegistryValue::DwordLittleEndian(n) | RegistryValue::DwordBigEndian(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a list of strings.
+    ///
+    /// A single string is returned as a list of one.
+    pub fn as_strings(&self) -> Option<Vec<&str>> {
+        match self {
+            RegistryValue::MultiString(v) => Some(v.iter().map(String::as_str).collect()),
+            _ => self.as_str().map(|s| vec![s]),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// MS OS 2.0
+
+/// The information about an MS OS 2.0 descriptor set, from the platform
+/// capability in the BOS descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MsOs20DescriptorSetInfo {
+    windows_version: u32,
+    total_length: u16,
+    vendor_code: u8,
+    alt_enum_code: u8,
+}
+
+impl MsOs20DescriptorSetInfo {
+    /// Parses the descriptor set information from an MS OS 2.0 platform
+    /// capability.
+    ///
+    /// A capability can describe several descriptor sets, each for a
+    /// different version of Windows. Returns `None` if the capability is
+    /// not for MS OS 2.0 descriptors.
+    pub fn from_capability(cap: &PlatformCapability) -> Option<Vec<Self>> {
+        if cap.uuid() != MS_OS_20_PLATFORM_UUID {
+            return None;
+        }
+        Some(
+            cap.data()
+                .chunks_exact(8)
+                .map(|info| Self {
+                    windows_version: u32::from_le_bytes([info[0], info[1], info[2], info[3]]),
+                    total_length: u16::from_le_bytes([info[4], info[5]]),
+                    vendor_code: info[6],
+                    alt_enum_code: info[7],
+                })
+                .collect(),
+        )
+    }
+
+    /// Gets the minimum version of Windows that the set applies to, such
+    /// as 0x06030000 for Windows 8.1.
+    pub fn windows_version(&self) -> u32 {
+        self.windows_version
+    }
+
+    /// Gets the total length of the descriptor set.
+    pub fn total_length(&self) -> u16 {
+        self.total_length
+    }
+
+    /// Gets the vendor request code to retrieve the descriptor set.
+    pub fn vendor_code(&self) -> u8 {
+        self.vendor_code
+    }
+
+    /// Gets the code to send to the device for an alternate enumeration,
+    /// if it supports one.
+    pub fn alt_enum_code(&self) -> Option<u8> {
+        match self.alt_enum_code {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
+
+/// A feature descriptor in an MS OS 2.0 descriptor set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum MsOs20Feature {
+    /// The compatible ID of the device or function.
+    CompatibleId(CompatibleId),
+    /// A registry property for the device or function.
+    RegistryProperty(RegistryProperty),
+    /// The resume recovery and signaling times, in milliseconds.
+    MinResumeTime {
+        /// The time to recover after a resume.
+        recovery_time: u8,
+        /// The time to signal a remote wake.
+        signaling_time: u8,
+    },
+    /// A model ID, which uniquely identifies the physical device.
+    ModelId(Guid),
+    /// Windows should treat the device as a composite device.
+    CcgpDevice,
+    /// The revision of the vendor's firmware.
+    VendorRevision(u16),
+    /// A feature that isn't decoded, with the descriptor type and the data
+    /// after the header.
+    Unknown(u16, Vec<u8>),
+}
+
+impl MsOs20Feature {
+    /// Parses a feature descriptor, including its header.
+    fn parse(desc: &[u8]) -> Result<Self> {
+        let desc_type = le_u16(desc, 2).ok_or(Error::BadDescriptor)?;
+        let data = &desc[4..];
+
+        Ok(match desc_type {
+            MS_OS_20_FEATURE_COMPATIBLE_ID => {
+                MsOs20Feature::CompatibleId(CompatibleId::parse(data)?)
+            }
+            MS_OS_20_FEATURE_REG_PROPERTY => {
+                let data_type = le_u16(data, 0).ok_or(Error::BadDescriptor)?;
+                let name_len = usize::from(le_u16(data, 2).ok_or(Error::BadDescriptor)?);
+                let name = data.get(4..4 + name_len).ok_or(Error::BadDescriptor)?;
+                let data_len = usize::from(le_u16(data, 4 + name_len).ok_or(Error::BadDescriptor)?);
+                let value = data
+                    .get(6 + name_len..6 + name_len + data_len)
+                    .ok_or(Error::BadDescriptor)?;
+                MsOs20Feature::RegistryProperty(RegistryProperty::new(
+                    name,
+                    u32::from(data_type),
+                    value,
+                )?)
+            }
+            MS_OS_20_FEATURE_MIN_RESUME_TIME if data.len() >= 2 => MsOs20Feature::MinResumeTime {
+                recovery_time: data[0],
+                signaling_time: data[1],
+            },
+            MS_OS_20_FEATURE_MODEL_ID => {
+                MsOs20Feature::ModelId(Guid::from_slice(data).ok_or(Error::BadDescriptor)?)
+            }
+            MS_OS_20_FEATURE_CCGP_DEVICE => MsOs20Feature::CcgpDevice,
+            MS_OS_20_FEATURE_VENDOR_REVISION => {
+                MsOs20Feature::VendorRevision(le_u16(data, 0).ok_or(Error::BadDescriptor)?)
+            }
+            _ => MsOs20Feature::Unknown(desc_type, data.to_vec()),
+        })
+    }
+}
+
+/// Gets the compatible ID from a list of features, if any.
+fn compatible_id(features: &[MsOs20Feature]) -> Option<&CompatibleId> {
+    features.iter().find_map(|f| match f {
+        MsOs20Feature::CompatibleId(id) => Some(id),
+        _ => None,
+    })
+}
+
+/// Gets the registry properties from a list of features.
+fn registry_properties(features: &[MsOs20Feature]) -> impl Iterator<Item = &RegistryProperty> {
+    features.iter().filter_map(|f| match f {
+        MsOs20Feature::RegistryProperty(prop) => Some(prop),
+        _ => None,
+    })
+}
+
+/// The MS OS 2.0 descriptor set.
+///
+/// The features at the top of the set apply to the whole device. The
+/// others apply to a configuration, or to a function within one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MsOs20DescriptorSet {
+    windows_version: u32,
+    features: Vec<MsOs20Feature>,
+    configurations: Vec<MsOs20ConfigurationSubset>,
+}
+
+/// A configuration subset of an MS OS 2.0 descriptor set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MsOs20ConfigurationSubset {
+    configuration_value: u8,
+    features: Vec<MsOs20Feature>,
+    functions: Vec<MsOs20FunctionSubset>,
+}
+
+/// A function subset of an MS OS 2.0 descriptor set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MsOs20FunctionSubset {
+    first_interface: u8,
+    features: Vec<MsOs20Feature>,
+}
+
+/// Iterator over the descriptors in an MS OS 2.0 descriptor set, which
+/// have a 16-bit length and type.
+struct MsOs20Descriptors<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for MsOs20Descriptors<'a> {
+    type Item = Result<(u16, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let res = match (le_u16(self.buf, 0), le_u16(self.buf, 2)) {
+            (Some(len), Some(desc_type))
+                if usize::from(len) >= 4 && usize::from(len) <= self.buf.len() =>
+            {
+                let (desc, rest) = self.buf.split_at(usize::from(len));
+                self.buf = rest;
+                Ok((desc_type, desc))
+            }
+            _ => {
+                self.buf = &[];
+                Err(Error::BadDescriptor)
+            }
+        };
+        Some(res)
+    }
+}
+
+impl MsOs20DescriptorSet {
+    /// Parses the descriptor set from its raw bytes, as read from the
+    /// device.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        if le_u16(buf, 0) != Some(10) || le_u16(buf, 2) != Some(MS_OS_20_SET_HEADER_DESCRIPTOR) {
+            return Err(Error::BadDescriptor);
+        }
+        let windows_version = le_u32(buf, 4).ok_or(Error::BadDescriptor)?;
+        let total_length = usize::from(le_u16(buf, 8).ok_or(Error::BadDescriptor)?);
+        if total_length < 10 || buf.len() < total_length {
+            return Err(Error::BadDescriptor);
+        }
+
+        let mut set = Self {
+            windows_version,
+            features: Vec::new(),
+            configurations: Vec::new(),
+        };
+
+        // The subset headers give the length of the whole subset, but some
+        // firmware gets that wrong, so the features are assigned to the
+        // most recent subset header, in order.
+        for desc in (MsOs20Descriptors {
+            buf: &buf[10..total_length],
+        }) {
+            let (desc_type, desc) = desc?;
+            match desc_type {
+                MS_OS_20_SUBSET_HEADER_CONFIGURATION => {
+                    set.configurations.push(MsOs20ConfigurationSubset {
+                        configuration_value: *desc.get(4).ok_or(Error::BadDescriptor)?,
+                        features: Vec::new(),
+                        functions: Vec::new(),
+                    });
+                }
+                MS_OS_20_SUBSET_HEADER_FUNCTION => {
+                    let func = MsOs20FunctionSubset {
+                        first_interface: *desc.get(4).ok_or(Error::BadDescriptor)?,
+                        features: Vec::new(),
+                    };
+                    set.configurations
+                        .last_mut()
+                        .ok_or(Error::BadDescriptor)?
+                        .functions
+                        .push(func);
+                }
+                _ => {
+                    let feature = MsOs20Feature::parse(desc)?;
+                    let features = match set.configurations.last_mut() {
+                        Some(config) => match config.functions.last_mut() {
+                            Some(func) => &mut func.features,
+                            None => &mut config.features,
+                        },
+                        None => &mut set.features,
+                    };
+                    features.push(feature);
+                }
+            }
+        }
+
+        Ok(set)
+    }
+
+    /// Gets the minimum version of Windows that the set applies to.
+    pub fn windows_version(&self) -> u32 {
+        self.windows_version
+    }
+
+    /// Gets the features that apply to the whole device.
+    pub fn features(&self) -> &[MsOs20Feature] {
+        &self.features
+    }
+
+    /// Gets the configuration subsets.
+    pub fn configurations(&self) -> &[MsOs20ConfigurationSubset] {
+        &self.configurations
+    }
+
+    /// Gets the compatible ID of the whole device, if any.
+    pub fn compatible_id(&self) -> Option<&CompatibleId> {
+        compatible_id(&self.features)
+    }
+
+    /// Gets the registry properties of the whole device.
+    pub fn registry_properties(&self) -> impl Iterator<Item = &RegistryProperty> {
+        registry_properties(&self.features)
+    }
+
+    /// Finds the function subset that starts at the interface, in any of
+    /// the configurations.
+    pub fn function(&self, first_interface: u8) -> Option<&MsOs20FunctionSubset> {
+        self.configurations
+            .iter()
+            .flat_map(|config| config.functions.iter())
+            .find(|func| func.first_interface == first_interface)
+    }
+}
+
+impl MsOs20ConfigurationSubset {
+    /// Gets the index of the configuration that the subset applies to.
+    ///
+    /// Note that, despite the name in the specification, this is the
+    /// zero-based index of the configuration, not its `bConfigurationValue`.
+    pub fn configuration_value(&self) -> u8 {
+        self.configuration_value
+    }
+
+    /// Gets the features that apply to the whole configuration.
+    pub fn features(&self) -> &[MsOs20Feature] {
+        &self.features
+    }
+
+    /// Gets the function subsets.
+    pub fn functions(&self) -> &[MsOs20FunctionSubset] {
+        &self.functions
+    }
+
+    /// Gets the compatible ID of the configuration, if any.
+    pub fn compatible_id(&self) -> Option<&CompatibleId> {
+        compatible_id(&self.features)
+    }
+
+    /// Gets the registry properties of the configuration.
+    pub fn registry_properties(&self) -> impl Iterator<Item = &RegistryProperty> {
+        registry_properties(&self.features)
+    }
+}
+
+impl MsOs20FunctionSubset {
+    /// Gets the number of the first interface of the function.
+    pub fn first_interface(&self) -> u8 {
+        self.first_interface
+    }
+
+    /// Gets the features of the function.
+    pub fn features(&self) -> &[MsOs20Feature] {
+        &self.features
+    }
+
+    /// Gets the compatible ID of the function, if any.
+    pub fn compatible_id(&self) -> Option<&CompatibleId> {
+        compatible_id(&self.features)
+    }
+
+    /// Gets the registry properties of the function.
+    pub fn registry_properties(&self) -> impl Iterator<Item = &RegistryProperty> {
+        registry_properties(&self.features)
+    }
+
+    /// Finds a registry property of the function by name, ignoring case.
+    pub fn property(&self, name: &str) -> Option<&RegistryProperty> {
+        self.registry_properties()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Encodes a string as null-terminated UTF-16LE.
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain(Some(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    #[test]
+    fn parse_ms_os_string() {
+        let mut buf = vec![0x12, 0x03];
+        buf.extend_from_slice(MS_OS_SIGNATURE);
+        buf.extend_from_slice(&[0x20, 0x00]);
+
+        let desc = MsOsStringDescriptor::parse(&buf).unwrap();
+        assert_eq!(0x20, desc.vendor_code());
+        assert!(!desc.container_id_supported());
+
+        buf[2] = b'X';
+        assert_eq!(Err(Error::NotFound), MsOsStringDescriptor::parse(&buf));
+        assert_eq!(
+            Err(Error::BadDescriptor),
+            MsOsStringDescriptor::parse(&buf[..10])
+        );
+    }
+
+    #[test]
+    fn parse_extended_compat_id() {
+        let mut buf = vec![
+            0x28, 0, 0, 0, 0x00, 0x01, 0x04, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        buf.extend_from_slice(&[0x02, 0x01]);
+        buf.extend_from_slice(b"WINUSB\0\0\0\0\0\0\0\0\0\0");
+        buf.extend_from_slice(&[0; 6]);
+
+        let desc = ExtendedCompatId::parse(&buf).unwrap();
+        assert_eq!(0x0100, desc.version());
+        assert_eq!(1, desc.functions().len());
+        assert_eq!(2, desc.functions()[0].first_interface());
+        assert_eq!("WINUSB", desc.compatible_id(2).unwrap().id());
+        assert_eq!("", desc.compatible_id(2).unwrap().sub_id());
+        assert_eq!(None, desc.compatible_id(0));
+
+        assert_eq!(
+            Err(Error::BadDescriptor),
+            ExtendedCompatId::parse(&buf[..30])
+        );
+    }
+
+    #[test]
+    fn parse_extended_properties() {
+        let name = utf16("DeviceInterfaceGUID");
+        let value = utf16("{12345678-1234-1234-1234-123456789ABC}");
+
+        let mut prop = Vec::new();
+        let size = 14 + name.len() + value.len();
+        prop.extend_from_slice(&(size as u32).to_le_bytes());
+        prop.extend_from_slice(&1u32.to_le_bytes());
+        prop.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        prop.extend_from_slice(&name);
+        prop.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        prop.extend_from_slice(&value);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&((10 + prop.len()) as u32).to_le_bytes());
+        buf.extend_from_slice(&[0x00, 0x01, 0x05, 0x00, 0x01, 0x00]);
+        buf.extend_from_slice(&prop);
+
+        let desc = ExtendedProperties::parse(&buf).unwrap();
+        assert_eq!(1, desc.properties().len());
+        let prop = desc.property("deviceinterfaceguid").unwrap();
+        assert_eq!("DeviceInterfaceGUID", prop.name());
+        assert_eq!(
+            Some("{12345678-1234-1234-1234-123456789ABC}"),
+            prop.value().as_str()
+        );
+    }
+
+    #[test]
+    fn registry_values() {
+        assert_eq!(
+            RegistryValue::DwordBigEndian(0x01020304),
+            RegistryValue::new(5, &[1, 2, 3, 4]).unwrap()
+        );
+        assert_eq!(
+            Some(vec!["a", "bc"]),
+            RegistryValue::new(7, &utf16("a\0bc\0"))
+                .unwrap()
+                .as_strings()
+        );
+        assert_eq!(Err(Error::BadDescriptor), RegistryValue::new(4, &[1, 2]));
+    }
+
+    #[test]
+    fn parse_set_info() {
+        let mut data = MS_OS_20_PLATFORM_UUID.as_bytes().to_vec();
+        data.extend_from_slice(&[0x00, 0x00, 0x03, 0x06, 0xB2, 0x00, 0x01, 0x00]);
+        let mut buf = vec![0x1C, 0x10, 0x05, 0x00];
+        buf.extend_from_slice(&data);
+
+        let cap = match crate::DeviceCapability::parse(&buf) {
+            crate::DeviceCapability::Platform(cap) => cap,
+            cap => panic!("unexpected capability: {:?}", cap),
+        };
+        let infos = MsOs20DescriptorSetInfo::from_capability(&cap).unwrap();
+        assert_eq!(1, infos.len());
+        assert_eq!(0x06030000, infos[0].windows_version());
+        assert_eq!(0xB2, infos[0].total_length());
+        assert_eq!(0x01, infos[0].vendor_code());
+        assert_eq!(None, infos[0].alt_enum_code());
+    }
+
+    #[test]
+    fn parse_descriptor_set() {
+        let name = utf16("DeviceInterfaceGUIDs");
+        let value = utf16("{CDB3B5AD-293B-4663-AA36-1AAE46463776}\0");
+
+        let mut reg = Vec::new();
+        let len = 10 + name.len() + value.len();
+        reg.extend_from_slice(&(len as u16).to_le_bytes());
+        reg.extend_from_slice(&[0x04, 0x00, 0x07, 0x00]);
+        reg.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        reg.extend_from_slice(&name);
+        reg.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        reg.extend_from_slice(&value);
+
+        let mut compat = vec![0x14, 0x00, 0x03, 0x00];
+        compat.extend_from_slice(b"WINUSB\0\0\0\0\0\0\0\0\0\0");
+
+        let func_len = 8 + compat.len() + reg.len();
+        let config_len = 8 + func_len;
+        let total_len = 10 + config_len;
+
+        let mut buf = vec![0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x06];
+        buf.extend_from_slice(&(total_len as u16).to_le_bytes());
+        buf.extend_from_slice(&[0x08, 0x00, 0x01, 0x00, 0x00, 0x00]);
+        buf.extend_from_slice(&(config_len as u16).to_le_bytes());
+        buf.extend_from_slice(&[0x08, 0x00, 0x02, 0x00, 0x01, 0x00]);
+        buf.extend_from_slice(&(func_len as u16).to_le_bytes());
+        buf.extend_from_slice(&compat);
+        buf.extend_from_slice(&reg);
+
+        let set = MsOs20DescriptorSet::parse(&buf).unwrap();
+        assert_eq!(0x06030000, set.windows_version());
+        assert!(set.features().is_empty());
+        assert_eq!(1, set.configurations().len());
+        assert_eq!(0, set.configurations()[0].configuration_value());
+
+        let func = set.function(1).unwrap();
+        assert_eq!("WINUSB", func.compatible_id().unwrap().id());
+        let prop = func.property("DeviceInterfaceGUIDs").unwrap();
+        assert_eq!(
+            Some(vec!["{CDB3B5AD-293B-4663-AA36-1AAE46463776}"]),
+            prop.value().as_strings()
+        );
+
+        assert_eq!(
+            Err(Error::BadDescriptor),
+            MsOs20DescriptorSet::parse(&buf[..20])
+        );
+    }
+}
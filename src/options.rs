@@ -10,18 +10,53 @@
 // to those terms.
 //
 
-use crate::{Context, Error, Result};
+use crate::{Context, Error, LogCallback, LogLevel, Result};
 use libusb1_sys::{self as ffi, constants::*};
 
 #[cfg(unix)]
 use std::ptr;
 
+#[cfg(libusb_init_context)]
+use std::mem;
+
+#[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+use std::os::raw::c_int;
+
+/// The option to set a log callback, added in libusb v1.0.27.
+#[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+const LIBUSB_OPTION_LOG_CB: u32 = 0x03;
+
 /// A `libusb` runtime option that can be enabled for a context.
+///
+/// Options are applied when a context is created with
+/// [`Context::with_options`].
+#[derive(Debug, Clone, Copy)]
 pub struct UsbOption {
     inner: OptionInner,
 }
 
 impl UsbOption {
+    /// Sets the log level for the context.
+    ///
+    /// This is the same as calling [`Context::set_log_level`], but takes
+    /// effect before any devices are discovered.
+    pub fn log_level(level: LogLevel) -> Self {
+        Self {
+            inner: OptionInner::LogLevel(level),
+        }
+    }
+
+    /// Sets where the log messages for the context are sent.
+    ///
+    /// This is the same as calling [`Context::set_log_callback`], but takes
+    /// effect before any devices are discovered, if the linked `libusb`
+    /// supports it.
+    pub fn log_callback(cb: LogCallback) -> Self {
+        Self {
+            inner: OptionInner::LogCallback(cb),
+        }
+    }
+
     /// Use the [UsbDk] backend if available.
     ///
     /// **Note**: This method is available on **Windows** only!
@@ -34,23 +69,174 @@ impl UsbOption {
         }
     }
 
+    /// Do not scan for devices when the context is created.
+    ///
+    /// This is useful in combination with [`Context::open_device_with_fd()`],
+    /// which can access a device directly without prior device scanning,
+    /// such as on Android, where the process has no authority to enumerate
+    /// the devices. Hotplug functionality is also deactivated.
+    ///
+    /// This is the same `libusb` option as [`UsbOption::weak_authority`].
+    ///
+    /// For the option to take effect before device discovery, the linked
+    /// `libusb` must support `libusb_init_context()` (v1.0.27 or later).
+    /// Otherwise creating the context fails with [`Error::NotSupported`],
+    /// and discovery must instead be disabled globally, before any contexts
+    /// are created, with
+    /// [`disable_device_discovery`](crate::disable_device_discovery).
+    pub fn no_device_discovery() -> Self {
+        Self {
+            inner: OptionInner::NoDeviceDiscovery,
+        }
+    }
+
+    /// Flag that the process has weak authority over the USB devices.
+    ///
+    /// This is the original name of the [`UsbOption::no_device_discovery`]
+    /// option, from `libusb` v1.0.24.
+    pub fn weak_authority() -> Self {
+        Self::no_device_discovery()
+    }
+
+    /// Applies the option to an existing context.
+    #[cfg_attr(libusb_init_context, allow(dead_code))]
     pub(crate) fn apply(&self, ctx: &mut Context) -> Result<()> {
+        let err = match self.inner {
+            OptionInner::LogLevel(level) => unsafe {
+                ffi::libusb_set_option(ctx.as_raw(), LIBUSB_OPTION_LOG_LEVEL, level.as_c_int())
+            },
+            OptionInner::LogCallback(cb) => return ctx.set_log_callback(cb),
+            OptionInner::UseUsbdk => unsafe {
+                ffi::libusb_set_option(ctx.as_raw(), LIBUSB_OPTION_USE_USBDK)
+            },
+            // Discovery has already run by the time a context exists, and on
+            // some backends this sets a process-wide flag instead.
+            OptionInner::NoDeviceDiscovery => return Err(Error::NotSupported),
+        };
+
+        if err == LIBUSB_SUCCESS {
+            Ok(())
+        } else {
+            Err(Error::from(err))
+        }
+    }
+
+    /// Gets the raw `libusb` init options to create a context with this
+    /// option.
+    ///
+    /// A log callback also sets the log level to match the logger.
+    #[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+    fn init_options(&self) -> Vec<libusb_init_option> {
         match self.inner {
-            OptionInner::UseUsbdk => {
-                let err = unsafe { ffi::libusb_set_option(ctx.as_raw(), LIBUSB_OPTION_USE_USBDK) };
-                if err == LIBUSB_SUCCESS {
-                    Ok(())
-                } else {
-                    Err(Error::from(err))
+            OptionInner::LogLevel(level) => vec![libusb_init_option::int(
+                LIBUSB_OPTION_LOG_LEVEL,
+                level.as_c_int(),
+            )],
+            OptionInner::LogCallback(cb) => {
+                let mut opts = vec![libusb_init_option {
+                    option: LIBUSB_OPTION_LOG_CB,
+                    value: libusb_init_option_value {
+                        log_cbval: cb.as_raw(),
+                    },
+                }];
+                if let Some(level) = cb.max_level() {
+                    opts.push(libusb_init_option::int(
+                        LIBUSB_OPTION_LOG_LEVEL,
+                        level.as_c_int(),
+                    ));
                 }
+                opts
             }
+            OptionInner::UseUsbdk => vec![libusb_init_option::int(LIBUSB_OPTION_USE_USBDK, 0)],
+            OptionInner::NoDeviceDiscovery => vec![libusb_init_option::int(
+                LIBUSB_OPTION_NO_DEVICE_DISCOVERY,
+                0,
+            )],
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 enum OptionInner {
+    LogLevel(LogLevel),
+    LogCallback(LogCallback),
     #[cfg_attr(not(windows), allow(dead_code))] // only constructed on Windows
     UseUsbdk,
+    NoDeviceDiscovery,
+}
+
+/// Creates a new context with the specified options.
+///
+/// When the linked `libusb` supports `libusb_init_context()`, the options
+/// are applied before device discovery runs. Otherwise the context is
+/// created first, and the options are then applied to it. In that case,
+/// [`UsbOption::no_device_discovery`] can't be honored, and is an error.
+pub(crate) fn new_context(opts: &[UsbOption]) -> Result<Context> {
+    #[cfg(libusb_init_context)]
+    {
+        let opts: Vec<_> = opts.iter().flat_map(|opt| opt.init_options()).collect();
+        let mut ctx = mem::MaybeUninit::<*mut ffi::libusb_context>::uninit();
+
+        try_unsafe!(libusb_init_context(
+            ctx.as_mut_ptr(),
+            opts.as_ptr(),
+            opts.len() as c_int
+        ));
+        Ok(unsafe { Context::from_raw(ctx.assume_init()) })
+    }
+
+    #[cfg(not(libusb_init_context))]
+    {
+        if opts
+            .iter()
+            .any(|opt| matches!(opt.inner, OptionInner::NoDeviceDiscovery))
+        {
+            return Err(Error::NotSupported);
+        }
+        let mut ctx = Context::new()?;
+        for opt in opts {
+            opt.apply(&mut ctx)?;
+        }
+        Ok(ctx)
+    }
+}
+
+/// An option for `libusb_init_context()`.
+#[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+struct libusb_init_option {
+    option: u32,
+    value: libusb_init_option_value,
+}
+
+#[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+impl libusb_init_option {
+    /// Creates an option with an integer value.
+    fn int(option: u32, ival: c_int) -> Self {
+        Self {
+            option,
+            value: libusb_init_option_value { ival },
+        }
+    }
+}
+
+/// The value of an option for `libusb_init_context()`.
+#[cfg(any(libusb_init_context, all(test, libusb_set_log_cb)))]
+#[repr(C)]
+#[allow(non_camel_case_types)]
+union libusb_init_option_value {
+    ival: c_int,
+    log_cbval: Option<ffi::libusb_log_cb>,
+}
+
+#[cfg(libusb_init_context)]
+extern "system" {
+    fn libusb_init_context(
+        ctx: *mut *mut ffi::libusb_context,
+        options: *const libusb_init_option,
+        num_options: c_int,
+    ) -> c_int;
 }
 
 /// Disable device scanning in `libusb` init.
@@ -58,7 +244,9 @@ enum OptionInner {
 /// Hotplug functionality will also be deactivated.
 ///
 /// This is a Linux only option and it must be set before any [`Context`]
-/// creation.
+/// creation. It then applies to all the contexts that are created.
+/// To disable device scanning for a single context, use
+/// [`UsbOption::no_device_discovery`].
 ///
 /// The option is useful in combination with [`Context::open_device_with_fd()`],
 /// which can access a device directly without prior device scanning.
@@ -70,3 +258,71 @@ pub fn disable_device_discovery() -> Result<()> {
     ));
     Ok(())
 }
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, libusb_set_log_cb))]
+mod test {
+    use super::*;
+
+    /// Gets the option numbers and integer values of the init options.
+    fn int_options(opt: UsbOption) -> Vec<(u32, c_int)> {
+        opt.init_options()
+            .iter()
+            .filter(|o| o.option != LIBUSB_OPTION_LOG_CB)
+            .map(|o| (o.option, unsafe { o.value.ival }))
+            .collect()
+    }
+
+    #[test]
+    fn option_numbers() {
+        assert_eq!(0, LIBUSB_OPTION_LOG_LEVEL);
+        assert_eq!(1, LIBUSB_OPTION_USE_USBDK);
+        assert_eq!(2, LIBUSB_OPTION_NO_DEVICE_DISCOVERY);
+        assert_eq!(3, LIBUSB_OPTION_LOG_CB);
+
+        assert_eq!(
+            vec![(LIBUSB_OPTION_LOG_LEVEL, LIBUSB_LOG_LEVEL_WARNING)],
+            int_options(UsbOption::log_level(LogLevel::Warning))
+        );
+        assert_eq!(
+            vec![(LIBUSB_OPTION_NO_DEVICE_DISCOVERY, 0)],
+            int_options(UsbOption::no_device_discovery())
+        );
+        assert_eq!(
+            vec![(LIBUSB_OPTION_NO_DEVICE_DISCOVERY, 0)],
+            int_options(UsbOption::weak_authority())
+        );
+    }
+
+    #[test]
+    fn stderr_callback_keeps_level() {
+        let opts = UsbOption::log_callback(LogCallback::Stderr).init_options();
+        assert_eq!(1, opts.len());
+        assert_eq!(LIBUSB_OPTION_LOG_CB, opts[0].option);
+        assert!(unsafe { opts[0].value.log_cbval }.is_none());
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_callback_sets_level() {
+        let cb = LogCallback::Log;
+        let opts = UsbOption::log_callback(cb).init_options();
+        assert_eq!(2, opts.len());
+        assert_eq!(LIBUSB_OPTION_LOG_CB, opts[0].option);
+        assert!(unsafe { opts[0].value.log_cbval }.is_some());
+
+        log::set_max_level(log::LevelFilter::Info);
+        assert_eq!(
+            vec![(LIBUSB_OPTION_LOG_LEVEL, LIBUSB_LOG_LEVEL_INFO)],
+            int_options(UsbOption::log_callback(cb))
+        );
+    }
+
+    #[cfg(not(libusb_init_context))]
+    #[test]
+    fn no_discovery_not_supported() {
+        let res = new_context(&[UsbOption::no_device_discovery()]);
+        assert!(matches!(res, Err(Error::NotSupported)));
+    }
+}
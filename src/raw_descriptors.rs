@@ -0,0 +1,70 @@
+// yusb/src/raw_descriptors.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+/// Iterator over the individual descriptors in a buffer of concatenated
+/// descriptors, such as the 'extra' bytes that `libusb` doesn't parse.
+///
+/// Each item is the full descriptor, including the `bLength` and
+/// `bDescriptorType` header. The iteration stops at the first descriptor
+/// that is malformed or truncated.
+#[derive(Debug, Clone)]
+pub(crate) struct RawDescriptors<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> RawDescriptors<'a> {
+    /// Creates an iterator over the descriptors in the buffer.
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// Finds the first descriptor of the specified type.
+    pub(crate) fn find_type(mut self, desc_type: u8) -> Option<&'a [u8]> {
+        self.find(|desc| desc[1] == desc_type)
+    }
+}
+
+impl<'a> Iterator for RawDescriptors<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = usize::from(*self.buf.first()?);
+        if len < 2 || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+        let (desc, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(desc)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn iterates_descriptors() {
+        let buf = [0x03, 0x30, 0x01, 0x02, 0x31, 0x04, 0x0B];
+        let descs: Vec<_> = RawDescriptors::new(&buf).collect();
+        assert_eq!(vec![&buf[..3], &buf[3..5]], descs);
+        assert_eq!(Some(&buf[3..5]), RawDescriptors::new(&buf).find_type(0x31));
+        assert_eq!(None, RawDescriptors::new(&buf).find_type(0x0B));
+    }
+
+    #[test]
+    fn stops_on_bad_length() {
+        assert_eq!(0, RawDescriptors::new(&[0x00, 0x30]).count());
+        assert_eq!(0, RawDescriptors::new(&[0x01, 0x30]).count());
+        assert_eq!(0, RawDescriptors::new(&[]).count());
+    }
+}
@@ -0,0 +1,52 @@
+// yusb/src/test_fixtures.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Descriptor fixtures, built with the descriptor builders, that are shared
+//! by the tests of the library and of the `yusb` command-line tool.
+//!
+//! The module that includes this file provides the builder types, from
+//! either the crate root or the `yusb` library.
+
+use super::{
+    ConfigDescriptorBuilder, DescriptorTree, DeviceDescriptorBuilder, EndpointBuilder,
+    InterfaceBuilder, ParseMode, TransferType,
+};
+
+/// Creates the descriptors of a device with an audio streaming interface,
+/// with a class-specific descriptor, and two isochronous endpoints in the
+/// 9-byte audio form, with `bRefresh` and `bSynchAddress`.
+///
+/// The configuration has a `wTotalLength` of 43 bytes.
+pub fn audio_device() -> DescriptorTree {
+    let mut dev = DeviceDescriptorBuilder::new(0x1234, 0x5678);
+    dev.num_configurations(1);
+
+    let mut alt = InterfaceBuilder::new(1);
+    alt.class(0x01, 0x02, 0)
+        .extra(&[0x07, 0x24, 0x01, 0x01, 0x01, 0x01, 0x00])
+        .endpoint(
+            EndpointBuilder::new(0x01)
+                .transfer_type(TransferType::Isochronous)
+                .max_packet_size(192)
+                .audio(0, 0x82),
+        )
+        .endpoint(
+            EndpointBuilder::new(0x82)
+                .transfer_type(TransferType::Isochronous)
+                .max_packet_size(3)
+                .audio(3, 0),
+        );
+    let mut config = ConfigDescriptorBuilder::new(1);
+    config.interface(&alt);
+
+    let mut buf = dev.to_bytes();
+    buf.extend(config.to_bytes().unwrap());
+    DescriptorTree::parse(&buf, ParseMode::Strict).unwrap()
+}
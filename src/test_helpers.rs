@@ -23,6 +23,8 @@ macro_rules! merge {
     }
 }
 
+/// Creates a `libusb_endpoint_descriptor` with default values,
+/// overriding the specified fields.
 #[macro_export]
 macro_rules! endpoint_descriptor {
     ($($key:ident : $value:expr),*) => {
@@ -43,6 +45,8 @@ macro_rules! endpoint_descriptor {
     }
 }
 
+/// Creates a `libusb_interface_descriptor` with default values, overriding
+/// the specified fields, or containing the specified endpoints.
 #[macro_export]
 macro_rules! interface_descriptor {
     ($($key:ident : $value:expr),*) => {
@@ -89,6 +93,7 @@ macro_rules! interface_descriptor {
     }
 }
 
+/// Creates a `libusb_interface` from a list of interface descriptors.
 #[macro_export]
 macro_rules! interface {
     ($($descriptor:expr),*) => {
@@ -107,6 +112,8 @@ macro_rules! interface {
     }
 }
 
+/// Creates a `libusb_config_descriptor` with default values, overriding
+/// the specified fields, or containing the specified interfaces.
 #[macro_export]
 macro_rules! config_descriptor {
     ($($key:ident : $value:expr),*) => {
@@ -151,6 +158,8 @@ macro_rules! config_descriptor {
     }
 }
 
+/// Creates a `libusb_device_descriptor` with default values, overriding
+/// the specified fields.
 #[macro_export]
 macro_rules! device_descriptor {
     ($($key:ident : $value:expr),*) => {
@@ -0,0 +1,193 @@
+// yusb/src/timeout.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+use crate::{Error, Result};
+use std::{
+    os::raw::c_uint,
+    time::{Duration, Instant},
+};
+
+/// The time limit for a USB transfer.
+///
+/// All the [`DeviceHandle`](crate::DeviceHandle) transfer methods accept
+/// anything that converts into a `Timeout`, including a plain `Duration`.
+///
+/// `libusb` takes timeouts in whole milliseconds, with zero meaning to wait
+/// forever. As in `libusb`, a zero duration waits forever, the same as
+/// [`Timeout::Infinite`]. A short, non-zero, duration is rounded up to one
+/// millisecond, rather than to zero, and a very long one is limited to the
+/// longest timeout that `libusb` can represent (about 49 days).
+///
+/// A `Deadline` is useful to limit the overall time of an operation that is
+/// made of several transfers. A transfer that is started after the deadline
+/// has passed fails immediately with `Error::Timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Timeout {
+    /// Wait forever for the transfer to complete.
+    Infinite,
+    /// Wait up to the specified time for the transfer to complete.
+    /// A zero duration waits forever.
+    After(Duration),
+    /// Wait until the specified instant for the transfer to complete.
+    Deadline(Instant),
+}
+
+impl Timeout {
+    /// Converts the timeout into a fixed deadline, starting from now.
+    ///
+    /// This is used by operations that make several transfers, so that the
+    /// timeout applies to the whole operation, rather than to each of the
+    /// individual transfers.
+    pub fn to_deadline(self) -> Self {
+        match self {
+            Timeout::After(dur) if dur.is_zero() => Timeout::Infinite,
+            Timeout::After(dur) => match Instant::now().checked_add(dur) {
+                Some(deadline) => Timeout::Deadline(deadline),
+                None => Timeout::Infinite,
+            },
+            _ => self,
+        }
+    }
+
+    /// Gets the time that remains until the timeout expires.
+    ///
+    /// Returns `None` for an infinite timeout.
+    pub fn remaining(&self) -> Option<Duration> {
+        match *self {
+            Timeout::Infinite => None,
+            Timeout::After(dur) if dur.is_zero() => None,
+            Timeout::After(dur) => Some(dur),
+            Timeout::Deadline(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+        }
+    }
+
+    /// Determines if the timeout has already expired.
+    ///
+    /// This is only possible for a deadline that has passed.
+    pub fn is_expired(&self) -> bool {
+        match *self {
+            Timeout::Deadline(deadline) => deadline <= Instant::now(),
+            _ => false,
+        }
+    }
+
+    /// Gets the timeout as the number of milliseconds for `libusb`, where
+    /// zero means to wait forever.
+    ///
+    /// Returns `Error::Timeout` if a deadline has already passed.
+    pub(crate) fn as_libusb_millis(&self) -> Result<c_uint> {
+        match *self {
+            Timeout::Infinite => Ok(0),
+            Timeout::After(dur) if dur.is_zero() => Ok(0),
+            Timeout::After(dur) => Ok(millis_ceil(dur)),
+            Timeout::Deadline(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(dur) if !dur.is_zero() => Ok(millis_ceil(dur)),
+                _ => Err(Error::Timeout),
+            },
+        }
+    }
+}
+
+impl Default for Timeout {
+    /// The default is to wait forever.
+    fn default() -> Self {
+        Timeout::Infinite
+    }
+}
+
+impl From<Duration> for Timeout {
+    /// Creates a timeout that expires after the duration.
+    fn from(dur: Duration) -> Self {
+        Timeout::After(dur)
+    }
+}
+
+impl From<Instant> for Timeout {
+    /// Creates a timeout that expires at the instant.
+    fn from(deadline: Instant) -> Self {
+        Timeout::Deadline(deadline)
+    }
+}
+
+impl From<Option<Duration>> for Timeout {
+    /// Creates a timeout that expires after the duration, or an infinite
+    /// one for `None`.
+    fn from(dur: Option<Duration>) -> Self {
+        dur.map_or(Timeout::Infinite, Timeout::After)
+    }
+}
+
+/// Converts a duration to milliseconds, rounding up any fraction, and
+/// clamping to the range of a non-zero `libusb` timeout.
+fn millis_ceil(dur: Duration) -> c_uint {
+    let ms = (dur.as_nanos() + 999_999) / 1_000_000;
+    ms.clamp(1, u128::from(c_uint::MAX)) as c_uint
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn infinite_is_zero() {
+        assert_eq!(Ok(0), Timeout::Infinite.as_libusb_millis());
+        assert_eq!(None, Timeout::Infinite.remaining());
+        assert_eq!(Timeout::Infinite, Timeout::from(None));
+    }
+
+    #[test]
+    fn short_durations_round_up() {
+        let ms = |dur| Timeout::from(dur).as_libusb_millis().unwrap();
+        assert_eq!(1, ms(Duration::from_nanos(1)));
+        assert_eq!(1, ms(Duration::from_micros(1)));
+        assert_eq!(1, ms(Duration::from_millis(1)));
+        assert_eq!(2, ms(Duration::from_micros(1001)));
+        assert_eq!(500, ms(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn zero_duration_is_infinite() {
+        let t = Timeout::from(Duration::ZERO);
+        assert_eq!(Ok(0), t.as_libusb_millis());
+        assert_eq!(None, t.remaining());
+        assert!(!t.is_expired());
+        assert_eq!(Timeout::Infinite, t.to_deadline());
+    }
+
+    #[test]
+    fn long_durations_are_clamped() {
+        let t = Timeout::from(Duration::from_secs(u64::MAX));
+        assert_eq!(Ok(c_uint::MAX), t.as_libusb_millis());
+    }
+
+    #[test]
+    fn expired_deadline_times_out() {
+        let t = Timeout::Deadline(Instant::now());
+        assert!(t.is_expired());
+        assert_eq!(Err(Error::Timeout), t.as_libusb_millis());
+    }
+
+    #[test]
+    fn to_deadline() {
+        let t = Timeout::from(Duration::from_secs(10)).to_deadline();
+        assert!(matches!(t, Timeout::Deadline(_)));
+        assert!(!t.is_expired());
+        let ms = t.as_libusb_millis().unwrap();
+        assert!(ms > 9_000 && ms <= 10_000);
+
+        assert_eq!(Timeout::Infinite, Timeout::Infinite.to_deadline());
+        assert_eq!(
+            Timeout::Infinite,
+            Timeout::from(Duration::MAX).to_deadline()
+        );
+    }
+}
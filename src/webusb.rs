@@ -0,0 +1,205 @@
+// yusb/src/webusb.rs
+//
+// Copyright (c) 2023, Frank Pagliughi
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! The WebUSB platform capability and URL descriptors.
+//!
+//! A WebUSB device reports a vendor request code, and the index of its
+//! landing page URL, in a platform capability of its BOS descriptor. The
+//! URL is then read with the vendor `GET_URL` request.
+
+use crate::{bos_descriptor::PlatformCapability, Error, Guid, Result, Version};
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The UUID of the WebUSB platform capability in the BOS descriptor.
+pub const WEBUSB_PLATFORM_UUID: Guid = Guid::from_fields(
+    0x3408B638,
+    0x09A9,
+    0x47A0,
+    [0x8B, 0xFD, 0xA0, 0x76, 0x88, 0x15, 0xB6, 0x65],
+);
+
+/// The `wIndex` of the vendor `GET_URL` request.
+pub const WEBUSB_GET_URL: u16 = 0x0002;
+
+/// The descriptor type of a WebUSB URL descriptor.
+pub const WEBUSB_URL_DESCRIPTOR: u8 = 0x03;
+
+/// The WebUSB platform capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WebUsbCapability {
+    version: u16,
+    vendor_code: u8,
+    landing_page: u8,
+}
+
+impl WebUsbCapability {
+    /// Parses the WebUSB capability from a platform capability.
+    ///
+    /// Returns `None` if the capability is not for WebUSB, or is too short.
+    pub fn from_capability(cap: &PlatformCapability) -> Option<Self> {
+        if cap.uuid() != WEBUSB_PLATFORM_UUID {
+            return None;
+        }
+        match *cap.data() {
+            [lo, hi, vendor_code, landing_page, ..] => Some(Self {
+                version: u16::from_le_bytes([lo, hi]),
+                vendor_code,
+                landing_page,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Gets the version of the WebUSB specification that the device
+    /// supports.
+    pub fn version(&self) -> Version {
+        Version::from_bcd(self.version)
+    }
+
+    /// Gets the vendor request code for the WebUSB requests.
+    pub fn vendor_code(&self) -> u8 {
+        self.vendor_code
+    }
+
+    /// Gets the index of the URL descriptor of the landing page, if the
+    /// device has one.
+    pub fn landing_page_index(&self) -> Option<u8> {
+        match self.landing_page {
+            0 => None,
+            n => Some(n),
+        }
+    }
+}
+
+/// The scheme of a WebUSB URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum UrlScheme {
+    /// `http://`
+    Http,
+    /// `https://`
+    Https,
+    /// The scheme is included in the URL.
+    None,
+    /// A reserved scheme value.
+    Unknown(u8),
+}
+
+impl UrlScheme {
+    /// Gets the scheme from its value in a URL descriptor.
+    pub fn from_raw(raw: u8) -> Self {
+        match raw {
+            0 => UrlScheme::Http,
+            1 => UrlScheme::Https,
+            255 => UrlScheme::None,
+            n => UrlScheme::Unknown(n),
+        }
+    }
+
+    /// Gets the prefix that the scheme adds to the URL.
+    pub fn prefix(&self) -> &'static str {
+        match *self {
+            UrlScheme::Http => "http://",
+            UrlScheme::Https => "https://",
+            _ => "",
+        }
+    }
+}
+
+/// A WebUSB URL descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WebUsbUrl {
+    scheme: UrlScheme,
+    url: String,
+}
+
+impl WebUsbUrl {
+    /// Parses a URL descriptor from its raw bytes, as read from the device.
+    pub fn parse(buf: &[u8]) -> Result<Self> {
+        match *buf {
+            [len, WEBUSB_URL_DESCRIPTOR, scheme, ..]
+                if usize::from(len) >= 3 && buf.len() >= usize::from(len) =>
+            {
+                let url = std::str::from_utf8(&buf[3..usize::from(len)])
+                    .map_err(|_| Error::BadUnicode)?;
+                Ok(Self {
+                    scheme: UrlScheme::from_raw(scheme),
+                    url: url.to_string(),
+                })
+            }
+            _ => Err(Error::BadDescriptor),
+        }
+    }
+
+    /// Gets the scheme of the URL.
+    pub fn scheme(&self) -> UrlScheme {
+        self.scheme
+    }
+
+    /// Gets the URL, without the scheme prefix.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl fmt::Display for WebUsbUrl {
+    /// Writes the full URL, including the scheme prefix.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.scheme.prefix(), self.url)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DeviceCapability;
+
+    #[test]
+    fn parse_capability() {
+        let mut buf = vec![0x18, 0x10, 0x05, 0x00];
+        buf.extend_from_slice(WEBUSB_PLATFORM_UUID.as_bytes());
+        buf.extend_from_slice(&[0x00, 0x01, 0x01, 0x01]);
+
+        let cap = match DeviceCapability::parse(&buf) {
+            DeviceCapability::Platform(cap) => cap,
+            cap => panic!("unexpected capability: {:?}", cap),
+        };
+        assert_eq!(
+            "3408B638-09A9-47A0-8BFD-A0768815B665",
+            cap.uuid().to_string()
+        );
+
+        let webusb = WebUsbCapability::from_capability(&cap).unwrap();
+        assert_eq!(Version(1, 0, 0), webusb.version());
+        assert_eq!(0x01, webusb.vendor_code());
+        assert_eq!(Some(1), webusb.landing_page_index());
+    }
+
+    #[test]
+    fn parse_url() {
+        let mut buf = vec![0x0E, 0x03, 0x01];
+        buf.extend_from_slice(b"example.com");
+
+        let url = WebUsbUrl::parse(&buf).unwrap();
+        assert_eq!(UrlScheme::Https, url.scheme());
+        assert_eq!("example.com", url.url());
+        assert_eq!("https://example.com", url.to_string());
+
+        assert_eq!(Err(Error::BadDescriptor), WebUsbUrl::parse(&buf[..8]));
+        assert_eq!(UrlScheme::None, UrlScheme::from_raw(255));
+    }
+}